@@ -0,0 +1,3719 @@
+use crate::art::{self, AlbumArt};
+use crate::audio::{self, AudioPlayer};
+use crate::browser::{Browser, BrowserEntry};
+use crate::commands::Command;
+use crate::config::{BadFileAction, Config, EmptySearchEnter, FolderEnter, ShuffleWeighting, ThemeName};
+use crate::eq::{self, Preset};
+use crate::events::{EventBus, PlayerEvent};
+use crate::keybindings::KeyBindings;
+use crate::picker::DirPicker;
+use crate::state::State;
+use crate::stats::{self, LibraryStats, StatsUpdate};
+use crate::theme::{self, Theme};
+use crate::trim::{self, TrimUpdate};
+use crate::watch::{self, LibraryWatcher};
+use crate::scan::{ScanStats, ScanUpdate, SortKey};
+use crate::{identify, nowplaying, playlist, scan, tags, template};
+use anyhow::Result;
+use rand::seq::SliceRandom;
+use ratatui::layout::Rect;
+use ratatui::widgets::ListState;
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct MusicFile {
+    pub path: PathBuf,
+    pub name: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    /// Position on the album, from the tags.
+    pub track_number: Option<u32>,
+    pub duration: Option<Duration>,
+    /// Volume factor that brings the track to the ReplayGain reference
+    /// loudness, when it is tagged.
+    pub replay_gain: Option<f32>,
+}
+
+impl MusicFile {
+    /// Reads the tags of `path`; the name is always the file name.
+    pub fn from_path(path: PathBuf) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        let tags = tags::read(&path);
+        MusicFile {
+            path,
+            name,
+            artist: tags.artist,
+            album: tags.album,
+            title: tags.title,
+            track_number: tags.track_number,
+            duration: tags.duration,
+            replay_gain: tags.replay_gain,
+        }
+    }
+
+    /// Case-insensitive substring match against the file name and tags.
+    /// `query` must already be lowercase.
+    pub fn matches(&self, query: &str) -> bool {
+        [Some(&self.name), self.artist.as_ref(), self.album.as_ref(), self.title.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(query))
+    }
+
+    /// `Artist - Title` from the tags, just the title if there's no artist,
+    /// and the file name when untagged.
+    pub fn display_name(&self) -> String {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+            (None, Some(title)) => title.clone(),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+/// Track count and summed length of the whole list, for the header.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LibraryTotal {
+    pub tracks: usize,
+    pub duration: Duration,
+    /// Some tracks have no known length, so `duration` falls short.
+    pub partial: bool,
+}
+
+impl LibraryTotal {
+    /// Sums the lengths read from the tags during the scan.
+    pub fn of(files: &[MusicFile]) -> Self {
+        let mut total = LibraryTotal { tracks: files.len(), ..Default::default() };
+        for file in files {
+            match file.duration {
+                Some(duration) => total.duration += duration,
+                None => total.partial = true,
+            }
+        }
+        total
+    }
+
+    /// `120 tracks · 8h 14m`, with a `+` after the length when it's a lower
+    /// bound.
+    pub fn label(&self) -> String {
+        let minutes = self.duration.as_secs() / 60;
+        let length = match minutes / 60 {
+            0 => format!("{}m", minutes),
+            hours => format!("{}h {}m", hours, minutes % 60),
+        };
+        format!(
+            "{} track{} · {}{}",
+            self.tracks,
+            if self.tracks == 1 { "" } else { "s" },
+            length,
+            if self.partial { "+" } else { "" }
+        )
+    }
+}
+
+pub struct App {
+    pub music_files: Vec<MusicFile>,
+    /// Totals of `music_files`, kept up to date by `reconcile_selection`.
+    pub library_total: LibraryTotal,
+    pub selected_index: usize,
+    pub list_state: ListState,
+    /// Rows the file list had room for in the last frame; set by the UI.
+    pub list_height: Cell<usize>,
+    /// Set by the UI on every frame for mouse hit-testing.
+    pub hit_areas: Cell<HitAreas>,
+    /// When and on which screen row the last list click landed.
+    last_click: Option<(Instant, u16)>,
+    /// Position the progress bar is being dragged to; seeked on release.
+    pub seek_drag: Option<Duration>,
+    pub audio_player: AudioPlayer,
+    /// Path of the track the player has open.
+    pub current_playing: Option<PathBuf>,
+    /// Length of the playing track, if the decoder reports one.
+    pub current_duration: Option<Duration>,
+    pub repeat: RepeatMode,
+    pub shuffle: bool,
+    /// Order scans and rescans put the list in.
+    pub sort_key: SortKey,
+    pub sort_descending: bool,
+    /// Tracks lined up to play next, ahead of list order or shuffle. Paths so
+    /// entries survive rescans; ones that disappear are dropped when reached.
+    pub queue: VecDeque<PathBuf>,
+    /// Tracks played while shuffling, oldest first; previous walks back through it.
+    /// Kept as paths so it survives rescans.
+    shuffle_history: Vec<PathBuf>,
+    /// Tracks already played in the current shuffle pass.
+    shuffle_played: HashSet<PathBuf>,
+    pub status_message: String,
+    /// Earlier status messages with when they appeared, oldest first.
+    pub status_log: VecDeque<(Instant, String)>,
+    /// The directories scanned into the library; never empty. See
+    /// [`App::music_directory`] for where single-folder features start.
+    pub music_roots: Vec<PathBuf>,
+    /// The M3U the list was loaded from, which `r` rereads instead of
+    /// scanning `music_roots`.
+    pub playlist: Option<PathBuf>,
+    /// A playlist read by [`App::load_saved_playlist`], held while
+    /// [`Mode::ConfirmPlaylist`] asks what to do with it.
+    pub pending_playlist: Option<(PathBuf, Vec<MusicFile>)>,
+    pub is_paused: bool,
+    pub volume: f32,
+    /// Output is silenced while `volume` keeps the level to restore.
+    pub muted: bool,
+    /// Scale tracks by their ReplayGain tags so they play at a similar
+    /// loudness.
+    pub normalize: bool,
+    /// Advance at the end of a track's audible part; see `skip_silence` in
+    /// the config.
+    pub skip_silence: bool,
+    /// Where the playing track falls silent for good, once found and when
+    /// there is enough silence after it to skip.
+    audible_end: Option<Duration>,
+    /// The limiter's count of limited samples as of the last tick, and when
+    /// it last went up.
+    limited_seen: u64,
+    limiter_engaged_at: Option<Instant>,
+    silence_rx: Option<Receiver<(PathBuf, Option<Duration>)>>,
+    /// Playback speed factor; 1.0 is normal.
+    pub speed: f32,
+    pub bad_files: HashSet<PathBuf>,
+    pub config: Config,
+    pub tick_rate: Duration,
+    pub show_debug: bool,
+    /// Which layout fills the screen.
+    pub view: View,
+    pub keys: KeyBindings,
+    /// Show the spectrum under the progress bar.
+    pub show_visualizer: bool,
+    /// Show the playing track's cover above the visualizer.
+    pub show_album_art: bool,
+    /// Cover of the playing track, once loaded.
+    pub album_art: Option<AlbumArt>,
+    art_rx: Option<Receiver<AlbumArt>>,
+    /// Redraw as fast as possible to measure rendering performance.
+    pub stress_mode: bool,
+    pub frame_stats: FrameStats,
+    pub events: EventBus,
+    pub mode: Mode,
+    pub search_query: String,
+    /// Digits typed after `v`, echoed in the footer.
+    pub volume_input: String,
+    /// Time typed after `g`, echoed in the footer.
+    pub seek_input: String,
+    /// Indices into `music_files` shown in the list, in display order.
+    pub visible: Vec<usize>,
+    pub device_volumes: HashMap<String, f32>,
+    pub identity: Option<identify::FileIdentity>,
+    pub library_stats: Option<LibraryStats>,
+    pub dir_picker: Option<DirPicker>,
+    /// Folder view shown in place of the flat list while set.
+    pub browser: Option<Browser>,
+    /// Set while a background scan is running; the list is replaced when it finishes.
+    pub scanning: bool,
+    /// Music files the running scan has found so far.
+    pub scan_found: usize,
+    /// Device names listed by the output device picker.
+    pub output_devices: Vec<String>,
+    /// Auto-advance stops once this track finishes; cleared when honored.
+    pub stop_after: Option<PathBuf>,
+    /// When the sleep timer stops playback. It runs on the wall clock, so
+    /// pausing does not hold it back.
+    pub sleep_timer: Option<Instant>,
+    /// The preset the sleep timer was last set to, for cycling.
+    sleep_timer_minutes: Option<u64>,
+    /// Set when the app wants the host loop to exit, e.g. once the sleep
+    /// timer has run out with `sleep_timer_quit` on.
+    pub quit_requested: bool,
+    /// Start and end of the A-B loop in the current track; playback jumps
+    /// back to `loop_a` on passing `loop_b`. Cleared when the track changes.
+    pub loop_a: Option<Duration>,
+    pub loop_b: Option<Duration>,
+    /// Most recently started track, saved on exit.
+    pub last_played: Option<PathBuf>,
+    /// Position in `last_played` to pick up from with
+    /// [`App::resume_last_session`]; only set for long tracks.
+    pub resume_position: Option<Duration>,
+    pub theme: Theme,
+    /// Whether the theme tracks the system dark/light preference.
+    pub follow_system_theme: bool,
+    /// Set on terminals without color; states are told apart by attributes.
+    pub monochrome: bool,
+    command_tx: Sender<Command>,
+    command_rx: Receiver<Command>,
+    /// Set when playback was paused by a suspend/lock so wake only resumes that.
+    suspended_playback: bool,
+    trim_rx: Option<Receiver<TrimUpdate>>,
+    trim_unplayable: Vec<PathBuf>,
+    stats_rx: Option<Receiver<StatsUpdate>>,
+    scan_rx: Option<Receiver<ScanUpdate>>,
+    /// Set by [`App::watch_library`].
+    library_watcher: Option<LibraryWatcher>,
+    /// A change was seen while a scan was running; rescan once it finishes.
+    rescan_pending: bool,
+    /// The `--range` to keep, applied again after every rescan.
+    range: Option<(usize, usize)>,
+    /// Start playing once the running scan delivers the list.
+    play_when_loaded: bool,
+    /// When the next track starts after a natural end, if a delay is configured.
+    advance_at: Option<Instant>,
+    /// When the playing track started, for [`START_GRACE`].
+    playback_started_at: Option<Instant>,
+    /// Tracks marked with `*`, saved in the state file as soon as they change.
+    pub favorites: BTreeSet<PathBuf>,
+    /// Gain in dB remembered for single tracks, saved in the state file as
+    /// soon as it changes.
+    pub track_gains: HashMap<PathBuf, f32>,
+    /// Tracks started most recently, newest first, without repeats in a row.
+    pub history: VecDeque<PathBuf>,
+    /// Limits the list to `favorites`, on top of any search.
+    pub favorites_only: bool,
+    /// Per-track crossfade lengths in ms; see [`App::crossfade_out`].
+    pub crossfade_overrides: HashMap<PathBuf, u64>,
+    /// Set while auto-advance starts the next track early, so
+    /// [`App::play_index`] crossfades into it over this long.
+    crossfade_next: Option<Duration>,
+    /// Set when stepping the volume to 0% paused playback.
+    paused_by_volume: bool,
+    /// Set while playback is held after an underrun, until enough of the
+    /// track is decoded again.
+    rebuffering: bool,
+    system_theme_rx: Option<Receiver<Option<ThemeName>>>,
+    /// The configured theme, used when not following the system.
+    base_theme: Theme,
+}
+
+/// Where the last frame drew the parts that take mouse clicks; empty when
+/// they were not drawn.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HitAreas {
+    /// The track list or folder view, borders included.
+    pub list: Rect,
+    /// Entry shown in the list's first row.
+    pub list_offset: usize,
+    /// The progress bar, borders included.
+    pub progress: Rect,
+}
+
+/// The layout drawn under any overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    /// The track list beside the info panel.
+    Normal,
+    /// Full-screen figlet title and clock for ambient displays.
+    Banner,
+    /// Full-screen cover, tags and progress of the playing track.
+    NowPlaying,
+}
+
+impl View {
+    /// `view` if this isn't it already, otherwise back to the list.
+    pub fn toggled(self, view: View) -> View {
+        if self == view {
+            View::Normal
+        } else {
+            view
+        }
+    }
+}
+
+/// Which set of key bindings is active; overlays capture input while open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    Search,
+    PinnedDirs { selected: usize },
+    Identify,
+    Stats,
+    DirPicker { selected: usize },
+    Devices { selected: usize },
+    /// The equalizer panel, with `selected` the band being adjusted.
+    Equalizer { selected: usize },
+    /// Recently played tracks in place of the info panel.
+    History { selected: usize },
+    /// `queue` in place of the help panel, with `selected` an index into it.
+    Queue { selected: usize },
+    /// `status_log` in place of the help panel, with `selected` counted
+    /// from the newest message.
+    StatusLog { selected: usize },
+    /// Typing a volume percentage into `volume_input`.
+    VolumeEntry,
+    /// Typing a time to seek to into `seek_input`.
+    SeekEntry,
+    /// Waiting for y/n after a quit with `confirm_quit` on.
+    ConfirmQuit,
+    /// Waiting for y/n before moving the selected track to the trash.
+    ConfirmTrash,
+    /// Waiting for y/a/n before `pending_playlist` replaces the list.
+    ConfirmPlaylist,
+}
+
+/// Where `App::build` gets its tracks from.
+enum Library {
+    Scan,
+    Empty,
+    /// A fixed list, for tests.
+    #[cfg(test)]
+    Files(Vec<MusicFile>),
+    /// The tracks of an M3U playlist, reread on refresh.
+    Playlist(PathBuf),
+}
+
+/// What happens when a track finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatMode {
+    /// Stop after the last track in the list.
+    Off,
+    /// Play the same track again.
+    One,
+    /// Wrap around to the first track after the last.
+    #[default]
+    All,
+}
+
+impl RepeatMode {
+    pub fn next(self) -> Self {
+        match self {
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+            RepeatMode::Off => RepeatMode::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RepeatMode::Off => "Off",
+            RepeatMode::One => "One",
+            RepeatMode::All => "All",
+        }
+    }
+
+    /// Parses `off`, `one` or `all`, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "off" => Some(RepeatMode::Off),
+            "one" => Some(RepeatMode::One),
+            "all" => Some(RepeatMode::All),
+            _ => None,
+        }
+    }
+}
+
+/// Draw timings shown by the debug overlay.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    /// How long the most recent `terminal.draw` took.
+    pub draw_time: Duration,
+    /// Frames drawn per second, measured over the last full second.
+    pub fps: f64,
+    frames: u32,
+    window_start: Instant,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        FrameStats {
+            draw_time: Duration::ZERO,
+            fps: 0.0,
+            frames: 0,
+            window_start: Instant::now(),
+        }
+    }
+}
+
+impl FrameStats {
+    pub fn record(&mut self, draw_time: Duration) {
+        self.draw_time = draw_time;
+        self.frames += 1;
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.frames = 0;
+            self.window_start = Instant::now();
+        }
+    }
+}
+
+/// Redraw rates the tick rate can be stepped through, in frames per second.
+const TICK_RATES_FPS: [u64; 6] = [2, 4, 5, 10, 20, 30];
+const DEFAULT_TICK_RATE: Duration = Duration::from_millis(200);
+/// Bounds for a tick rate read from `state.toml`, so a hand-edited 0 can't
+/// make the event loop spin.
+const MIN_TICK_RATE: Duration = Duration::from_millis(16);
+const MAX_TICK_RATE: Duration = Duration::from_millis(1000);
+const DEFAULT_VOLUME: f32 = 0.7;
+const SEEK_STEP: Duration = Duration::from_secs(5);
+const SPEED_STEP: f32 = 0.1;
+const MIN_SPEED: f32 = 0.5;
+const MAX_SPEED: f32 = 3.0;
+/// How long before the end of a track the next one is decoded and queued.
+const PRELOAD_AHEAD: Duration = Duration::from_secs(10);
+/// Shortest A-B loop, so the jump back to A can't land past B again.
+const MIN_LOOP: Duration = Duration::from_secs(1);
+/// How long after a track starts an empty sink is not taken as its end,
+/// since the sink can briefly look empty before playback gets going.
+const START_GRACE: Duration = Duration::from_millis(300);
+/// Two clicks on the same row within this time play it.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+/// Durations the sleep timer key steps through before turning it off.
+const SLEEP_TIMER_MINUTES: [u64; 3] = [15, 30, 60];
+/// Tracks kept in the play history.
+const HISTORY_LEN: usize = 50;
+/// Per-track crossfade lengths `ctrl-f` steps through, in seconds; 0 is a
+/// hard cut.
+const TRACK_CROSSFADE_SECS: [u64; 4] = [0, 2, 5, 10];
+/// Step and limit of the per-track gain, in dB.
+const TRACK_GAIN_STEP_DB: f32 = 1.0;
+const MAX_TRACK_GAIN_DB: f32 = 12.0;
+/// Status messages kept for the log panel.
+const STATUS_LOG_LEN: usize = 100;
+/// How long the info panel keeps showing the limiter as engaged after it
+/// last bent a sample.
+const LIMITER_HOLD: Duration = Duration::from_secs(1);
+
+impl App {
+    pub fn new(music_dir: PathBuf) -> Result<Self> {
+        Self::with_config(vec![music_dir], Config::load())
+    }
+
+    /// Like [`App::new`], but scans and merges every directory in `roots`
+    /// (which must not be empty), with `config` instead of the settings in
+    /// `config.toml`, e.g. with command line options applied.
+    pub fn with_config(roots: Vec<PathBuf>, config: Config) -> Result<Self> {
+        Self::build(roots, Library::Scan, config)
+    }
+
+    /// Starts without scanning and lets the user browse from `start` to the
+    /// directory to play.
+    pub fn with_directory_picker(start: PathBuf) -> Result<Self> {
+        let mut app = Self::build(vec![start], Library::Empty, Config::load())?;
+        app.open_dir_picker();
+        Ok(app)
+    }
+
+    /// Starts with the tracks of an M3U playlist, in playlist order, with
+    /// `config` as for [`App::with_config`].
+    pub fn from_playlist(path: &Path, config: Config) -> Result<Self> {
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut app = Self::build(vec![dir], Library::Playlist(path.to_path_buf()), config)?;
+        app.set_status(format!(
+            "Loaded playlist {} - {} tracks",
+            path.display(),
+            app.music_files.len()
+        ));
+        Ok(app)
+    }
+
+    fn build(music_roots: Vec<PathBuf>, library: Library, config: Config) -> Result<Self> {
+        Self::build_with_state(music_roots, library, config, State::load())
+    }
+
+    /// Like [`App::build`], but restores `state` instead of `state.toml`.
+    fn build_with_state(music_roots: Vec<PathBuf>, library: Library, config: Config, state: State) -> Result<Self> {
+        let scan = matches!(library, Library::Scan);
+        let (music_files, scan_stats, playlist) = match library {
+            Library::Scan | Library::Empty => Default::default(),
+            #[cfg(test)]
+            Library::Files(files) => (files, ScanStats::default(), None),
+            Library::Playlist(path) => (playlist::load_playlist(&path)?, ScanStats::default(), Some(path)),
+        };
+        let mut events = EventBus::default();
+        if let Some(ref path) = config.now_playing_file {
+            events.subscribe(nowplaying::spawn_writer(
+                path.clone(),
+                config.now_playing_format.clone(),
+            ));
+        }
+        let (command_tx, command_rx) = mpsc::channel();
+        #[cfg(all(feature = "logind", target_os = "linux"))]
+        if config.pause_on_suspend {
+            crate::logind::spawn(command_tx.clone());
+        }
+        #[cfg(all(feature = "mpris", target_os = "linux"))]
+        if config.mpris {
+            events.subscribe(crate::mpris::spawn(command_tx.clone()));
+        }
+        let selected_index = state
+            .last_played
+            .as_ref()
+            .and_then(|last| music_files.iter().position(|f| &f.path == last))
+            .unwrap_or(0);
+        let mut list_state = ListState::default();
+        if !music_files.is_empty() {
+            list_state.select(Some(selected_index));
+        }
+        let visible = (0..music_files.len()).collect();
+        let library_total = LibraryTotal::of(&music_files);
+
+        let status_message = if music_files.is_empty() {
+            format!("No music files found{} - Press 'r' to refresh or 'q' to quit", scan_stats.summary())
+        } else {
+            format!("Ready{} - Use ↑/↓ to navigate, Enter to play (auto-advances to next song), 'q' to quit", scan_stats.summary())
+        };
+
+        let mut audio_player = match config.output_device {
+            Some(ref name) => AudioPlayer::with_device(name, config.prewarm_audio, config.sample_rate)?,
+            None => AudioPlayer::new(config.prewarm_audio, config.sample_rate)?,
+        };
+        let volume = audio_player
+            .device_name()
+            .and_then(|name| state.device_volumes.get(name).copied())
+            .or(state.volume)
+            .unwrap_or(DEFAULT_VOLUME)
+            .min(config.max_volume());
+        audio_player.set_fade(Duration::from_millis(config.fade_ms));
+        audio_player.set_prefetch(config.pause_on_underrun.then(|| {
+            // Room for the rebuffer threshold and then some, so it can be reached
+            (Duration::from_millis(config.rebuffer_ms) * 2).max(Duration::from_secs(1))
+        }));
+        audio_player.set_eq_gains(&state.eq_gains);
+        audio_player.limiter().set_enabled(config.limiter);
+        let show_visualizer = config.visualizer;
+        let show_album_art = config.album_art;
+        let mut startup_warnings = Vec::new();
+        let (keys, key_errors) = KeyBindings::new(&config.keys);
+        startup_warnings.extend(key_errors.into_iter().map(|error| format!("key binding {}", error)));
+        audio_player.tap().set_enabled(show_visualizer);
+
+        let mut theme = Theme::named(config.theme);
+        if let Some(ref path) = config.theme_file {
+            match theme::load(path, theme) {
+                Ok(loaded) => theme = loaded,
+                Err(e) => startup_warnings.push(format!("theme file: {:#}", e)),
+            }
+        }
+        let follow_system_theme = config.theme_follow_system;
+
+        let mut app = App {
+            music_files,
+            library_total,
+            selected_index,
+            list_state,
+            list_height: Cell::new(0),
+            hit_areas: Cell::new(HitAreas::default()),
+            last_click: None,
+            seek_drag: None,
+            audio_player,
+            current_playing: None,
+            current_duration: None,
+            repeat: RepeatMode::default(),
+            shuffle: false,
+            sort_key: SortKey::default(),
+            sort_descending: false,
+            queue: VecDeque::new(),
+            shuffle_history: Vec::new(),
+            shuffle_played: HashSet::new(),
+            status_message: String::new(),
+            status_log: VecDeque::new(),
+            music_roots,
+            playlist,
+            pending_playlist: None,
+            is_paused: false,
+            volume,
+            muted: false,
+            normalize: config.normalize,
+            skip_silence: config.skip_silence,
+            audible_end: None,
+            limited_seen: 0,
+            limiter_engaged_at: None,
+            silence_rx: None,
+            speed: 1.0,
+            bad_files: HashSet::new(),
+            config,
+            tick_rate: state
+                .tick_rate_ms
+                .map(|ms| Duration::from_millis(ms).clamp(MIN_TICK_RATE, MAX_TICK_RATE))
+                .unwrap_or(DEFAULT_TICK_RATE),
+            show_debug: false,
+            view: View::Normal,
+            keys,
+            show_visualizer,
+            show_album_art,
+            album_art: None,
+            art_rx: None,
+            stress_mode: false,
+            frame_stats: FrameStats::default(),
+            events,
+            mode: Mode::Normal,
+            search_query: String::new(),
+            volume_input: String::new(),
+            seek_input: String::new(),
+            visible,
+            device_volumes: state.device_volumes,
+            identity: None,
+            library_stats: None,
+            dir_picker: None,
+            browser: None,
+            scanning: false,
+            scan_found: 0,
+            output_devices: Vec::new(),
+            stop_after: None,
+            sleep_timer: None,
+            sleep_timer_minutes: None,
+            quit_requested: false,
+            loop_a: None,
+            loop_b: None,
+            resume_position: state
+                .last_position_ms
+                .map(Duration::from_millis)
+                .filter(|_| state.last_played.as_ref().is_some_and(|path| path.exists())),
+            last_played: state.last_played,
+            theme,
+            follow_system_theme,
+            monochrome: !theme::colors_supported() || theme.monochrome,
+            command_tx,
+            command_rx,
+            suspended_playback: false,
+            trim_rx: None,
+            trim_unplayable: Vec::new(),
+            stats_rx: None,
+            scan_rx: None,
+            library_watcher: None,
+            rescan_pending: false,
+            range: None,
+            play_when_loaded: false,
+            advance_at: None,
+            playback_started_at: None,
+            // Entries for files that were moved or deleted are dropped
+            favorites: state.favorites.into_iter().filter(|path| path.exists()).collect(),
+            favorites_only: false,
+            history: state.history.into_iter().take(HISTORY_LEN).collect(),
+            track_gains: state.track_gains.into_iter().filter(|(path, _)| path.exists()).collect(),
+            crossfade_overrides: state
+                .crossfade_overrides
+                .into_iter()
+                .filter(|(path, _)| path.exists())
+                .collect(),
+            crossfade_next: None,
+            paused_by_volume: false,
+            rebuffering: false,
+            system_theme_rx: follow_system_theme.then(theme::watch_system),
+            base_theme: theme,
+        };
+        app.set_status(status_message);
+        if scan {
+            app.start_scan();
+        } else if let Some(hint) = app.resume_hint() {
+            app.set_status(hint);
+        }
+        app.send_volume();
+        for warning in &startup_warnings {
+            log::warn!("Config: {}", warning);
+        }
+        if !startup_warnings.is_empty() {
+            app.set_status(format!("Problems in config.toml: {}", startup_warnings.join("; ")));
+        }
+        Ok(app)
+    }
+
+    pub fn next(&mut self) {
+        if !self.visible.is_empty() {
+            let next = match self.visible_position() {
+                Some(pos) => (pos + 1) % self.visible.len(),
+                None => 0,
+            };
+            self.selected_index = self.visible[next];
+            self.sync_list_state();
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if !self.visible.is_empty() {
+            let previous = match self.visible_position() {
+                Some(0) | None => self.visible.len() - 1,
+                Some(pos) => pos - 1,
+            };
+            self.selected_index = self.visible[previous];
+            self.sync_list_state();
+        }
+    }
+
+    /// Moves the selection down a page and scrolls so it sits on the first row.
+    pub fn page_down(&mut self) {
+        let page = self.page_size();
+        let Some(pos) = self.visible_position() else {
+            return self.go_first();
+        };
+        let target = (pos + page).min(self.visible.len() - 1);
+        let offset = (self.list_state.offset() + page).min(target);
+        self.select_visible(target, offset);
+    }
+
+    /// Moves the selection up a page, scrolling the list along with it.
+    pub fn page_up(&mut self) {
+        let page = self.page_size();
+        let Some(pos) = self.visible_position() else {
+            return self.go_first();
+        };
+        let target = pos.saturating_sub(page);
+        let offset = self.list_state.offset().saturating_sub(page).min(target);
+        self.select_visible(target, offset);
+    }
+
+    pub fn go_first(&mut self) {
+        self.select_visible(0, 0);
+    }
+
+    pub fn go_last(&mut self) {
+        let last = self.visible.len().saturating_sub(1);
+        self.select_visible(last, (last + 1).saturating_sub(self.page_size()));
+    }
+
+    /// Selects the playing track, found by path so a file with the same name
+    /// in another folder doesn't match. Clears a search that hides it, and
+    /// in the folder view opens the folder it is in.
+    pub fn jump_to_playing(&mut self) {
+        let Some(path) = self.current_playing.clone() else {
+            self.set_status("Nothing is playing");
+            return;
+        };
+        if let Some(ref mut browser) = self.browser {
+            if let Some(dir) = path.parent().filter(|dir| dir.starts_with(&browser.root)) {
+                browser.enter(dir, &self.config);
+                browser.select_path(&path);
+            }
+            return;
+        }
+        let Some(index) = self.music_files.iter().position(|f| f.path == path) else {
+            self.set_status("The playing track is not in the list");
+            return;
+        };
+        if !self.visible.contains(&index) {
+            self.clear_search();
+        }
+        self.selected_index = index;
+        self.sync_list_state();
+    }
+
+    /// Rows per page, falling back to one row before the first draw.
+    fn page_size(&self) -> usize {
+        self.list_height.get().max(1)
+    }
+
+    fn select_visible(&mut self, pos: usize, offset: usize) {
+        let Some(&index) = self.visible.get(pos) else {
+            return;
+        };
+        self.selected_index = index;
+        *self.list_state.offset_mut() = offset;
+        self.sync_list_state();
+    }
+
+    /// Selects the entry in list row `row` (0 is the first row inside the
+    /// border) at screen line `y`; a second click on the same line plays it.
+    pub fn click_list_row(&mut self, row: usize, y: u16) -> Result<()> {
+        // Compared by screen line: the list re-centres on the new selection,
+        // so the second click of a double click lands on a different entry
+        let double = self
+            .last_click
+            .is_some_and(|(at, line)| line == y && at.elapsed() < DOUBLE_CLICK);
+        if double {
+            self.last_click = None;
+            return match self.browser {
+                Some(_) => self.browser_activate(),
+                None => self.play_selected(),
+            };
+        }
+        self.last_click = Some((Instant::now(), y));
+
+        let index = self.hit_areas.get().list_offset + row;
+        if let Some(ref mut browser) = self.browser {
+            if index < browser.entries.len() {
+                browser.selected = index;
+            }
+        } else if let Some(&file) = self.visible.get(index) {
+            self.selected_index = file;
+            self.sync_list_state();
+        }
+        Ok(())
+    }
+
+    /// Moves the progress bar drag to `fraction` of the current track.
+    pub fn drag_seek(&mut self, fraction: f64) {
+        if self.current_playing.is_none() {
+            return;
+        }
+        if let Some(total) = self.current_duration {
+            self.seek_drag = Some(total.mul_f64(fraction.clamp(0.0, 1.0)));
+        }
+    }
+
+    /// Seeks to where the progress bar was dragged, if it was.
+    pub fn finish_seek_drag(&mut self) {
+        if let Some(target) = self.seek_drag.take() {
+            self.seek_to(target);
+        }
+    }
+
+    /// Position of the selected track within the displayed list.
+    pub fn visible_position(&self) -> Option<usize> {
+        self.visible.iter().position(|&i| i == self.selected_index)
+    }
+
+    fn sync_list_state(&mut self) {
+        self.list_state.select(self.visible_position());
+    }
+
+    /// Recomputes `visible` from the search query, moving the selection onto
+    /// the first match if the selected track was filtered out.
+    pub fn update_visible(&mut self) {
+        let query = self.search_query.to_lowercase();
+        self.visible = self
+            .music_files
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| query.is_empty() || file.matches(&query))
+            .filter(|(_, file)| !self.favorites_only || self.favorites.contains(&file.path))
+            .map(|(i, _)| i)
+            .collect();
+        if self.visible_position().is_none() {
+            if let Some(&first) = self.visible.first() {
+                self.selected_index = first;
+            }
+        }
+        self.sync_list_state();
+    }
+
+    pub fn start_search(&mut self) {
+        // Search filters the flat list, so leave the folder view for it
+        self.browser = None;
+        self.mode = Mode::Search;
+    }
+
+    pub fn search_push(&mut self, c: char) {
+        self.search_query.push(c);
+        self.update_visible();
+    }
+
+    pub fn search_pop(&mut self) {
+        self.search_query.pop();
+        self.update_visible();
+    }
+
+    pub fn clear_search(&mut self) {
+        self.mode = Mode::Normal;
+        self.search_query.clear();
+        self.update_visible();
+    }
+
+    /// Handles Enter while typing a search: plays the highlighted match, or
+    /// applies `on_empty_search_enter` when nothing matches.
+    pub fn submit_search(&mut self) -> Result<()> {
+        if self.visible.is_empty() {
+            match self.config.on_empty_search_enter {
+                EmptySearchEnter::Clear => self.clear_search(),
+                EmptySearchEnter::Ignore => {}
+            }
+            return Ok(());
+        }
+        self.mode = Mode::Normal;
+        self.play_selected()
+    }
+
+    /// Plays the selected track. Does nothing while a filter hides every
+    /// track, since the selection isn't on screen then.
+    pub fn play_selected(&mut self) -> Result<()> {
+        if self.visible.is_empty() && !self.music_files.is_empty() {
+            return Ok(());
+        }
+        self.play_index(self.selected_index)
+    }
+
+    /// Plays the track at `index` without touching the selection.
+    pub fn play_index(&mut self, index: usize) -> Result<()> {
+        if self.music_files.is_empty() {
+            self.set_status("No music files available to play");
+            return Ok(());
+        }
+
+        self.advance_at = None;
+        let crossfade = self.crossfade_next.take();
+        if let Some(file) = self.music_files.get(index) {
+            // Set for the new track only; a fading-out track keeps its level
+            self.audio_player.set_next_volume(self.output_volume());
+            self.audio_player.set_next_track_gain(self.gain_for(Some(file)));
+            let started = if let Some(fade) = crossfade {
+                self.audio_player.crossfade_to(&file.path, fade)
+            } else {
+                self.audio_player.play_with_fade(&file.path)
+            };
+            match started {
+                Ok(_) => self.track_started(index),
+                Err(e) => {
+                    // Every decoder backend rejected the file
+                    log::warn!("Could not play {}: {}", file.path.display(), e);
+                    self.bad_files.insert(file.path.clone());
+                    self.set_status(format!("Error playing file: {}", e));
+                }
+            }
+        } else {
+            self.set_status("No file selected");
+        }
+        Ok(())
+    }
+
+    /// Bookkeeping once the track at `index` has started playing, whether
+    /// opened by [`App::play_index`] or reached through a preload.
+    fn track_started(&mut self, index: usize) {
+        let file = self.music_files[index].clone();
+        log::info!("Playing {}", file.path.display());
+        self.last_played = Some(file.path.clone());
+        self.resume_position = None;
+        self.loop_a = None;
+        self.loop_b = None;
+        self.bad_files.remove(&file.path);
+        self.rebuffering = false;
+        self.current_playing = Some(file.path.clone());
+        self.playback_started_at = Some(Instant::now());
+        self.current_duration = self.audio_player.duration().or(file.duration);
+        self.audible_end = None;
+        self.silence_rx = None;
+        if self.skip_silence {
+            self.find_audible_end(file.path.clone());
+        }
+        if self.history.front() != Some(&file.path) {
+            self.history.push_front(file.path.clone());
+            self.history.truncate(HISTORY_LEN);
+        }
+        if self.shuffle {
+            self.shuffle_played.insert(file.path.clone());
+            if self.shuffle_history.last() != Some(&file.path) {
+                self.shuffle_history.push(file.path.clone());
+            }
+        }
+        self.is_paused = false;
+        if self.show_album_art {
+            self.load_album_art(file.path.clone());
+        }
+        self.set_status(format!("♪ Playing: {}", file.name));
+        self.events.emit(PlayerEvent::TrackStarted(file));
+    }
+
+    /// The track auto-advance would play after the current one, or `None`
+    /// when it would stop, wait, or has to handle a flagged file first.
+    fn upcoming_index(&self) -> Option<usize> {
+        let playing = self.playing_index()?;
+        if self.config.inter_track_delay_secs > 0
+            || self.loop_b.is_some()
+            || self.stop_after.as_ref() == Some(&self.music_files[playing].path)
+        {
+            return None;
+        }
+        let index = if self.repeat == RepeatMode::One {
+            playing
+        } else if let Some(queued) = self.queued_files().next() {
+            self.music_files.iter().position(|f| f.path == queued.path)?
+        } else if self.repeat == RepeatMode::Off && self.at_end() {
+            return None;
+        } else if self.shuffle {
+            // Keep an earlier pick while it is still a valid one, since picks are random
+            let candidates = self.shuffle_candidates();
+            let preloaded = self
+                .audio_player
+                .preloaded()
+                .and_then(|path| candidates.iter().copied().find(|&i| self.music_files[i].path == path));
+            preloaded.or_else(|| self.choose_shuffled(&candidates))?
+        } else {
+            step_index(self.advance_origin(), self.music_files.len(), self.repeat, true)?
+        };
+        let flagged = self.bad_files.contains(&self.music_files[index].path);
+        (!flagged || self.config.on_bad_file == BadFileAction::Play).then_some(index)
+    }
+
+    /// Queues the upcoming track behind the current one for a gapless
+    /// transition once the current one is about to end, replacing a preload
+    /// that no longer matches (the queue, selection or modes changed).
+    fn preload_upcoming(&mut self) {
+        if self.audible_end.is_some() {
+            // The track is cut short, so nothing may wait behind its silence
+            self.audio_player.discard_preload();
+            return;
+        }
+        if self.crossfades() {
+            return;
+        }
+        // Tracks of unknown length are preloaded straight away
+        let near_end = self
+            .current_duration
+            .is_none_or(|total| self.audio_player.position() + PRELOAD_AHEAD >= total);
+        if !near_end {
+            return;
+        }
+        let upcoming = self.upcoming_index().map(|index| &self.music_files[index]);
+        if upcoming.map(|file| file.path.as_path()) == self.audio_player.preloaded() {
+            return;
+        }
+        match upcoming.map(|file| (file.path.clone(), self.gain_for(Some(file)))) {
+            Some((path, gain)) => {
+                if let Err(e) = self.audio_player.preload_next(&path, gain) {
+                    log::warn!("Could not preload {}: {}", path.display(), e);
+                }
+            }
+            None => self.audio_player.discard_preload(),
+        }
+    }
+
+    /// Whether the current track will be crossfaded into the next rather
+    /// than followed by a gapless preload; it has to be long enough to fade
+    /// out and in again.
+    fn crossfades(&self) -> bool {
+        let fade = self.crossfade_out();
+        !fade.is_zero() && self.track_end().is_some_and(|total| total >= fade * 2)
+    }
+
+    /// How long the playing track crossfades into the next: its own length
+    /// from `crossfade_overrides` if it has one, else `crossfade_ms`.
+    fn crossfade_out(&self) -> Duration {
+        let override_ms = self.current_playing.as_ref().and_then(|path| self.crossfade_overrides.get(path));
+        Duration::from_millis(override_ms.copied().unwrap_or(self.config.crossfade_ms))
+    }
+
+    /// Cycles the playing track's crossfade length through
+    /// [`TRACK_CROSSFADE_SECS`] and back to the `crossfade_ms` default,
+    /// remembering it for every later play of the file.
+    pub fn cycle_track_crossfade(&mut self) {
+        let Some(path) = self.current_playing.clone() else {
+            self.set_status("Nothing playing");
+            return;
+        };
+        let next = match self.crossfade_overrides.get(&path) {
+            None => Some(TRACK_CROSSFADE_SECS[0]),
+            Some(&ms) => TRACK_CROSSFADE_SECS.iter().copied().find(|&secs| secs * 1000 > ms),
+        };
+        match next {
+            Some(secs) => self.crossfade_overrides.insert(path, secs * 1000),
+            None => self.crossfade_overrides.remove(&path),
+        };
+        self.set_status(match next {
+            Some(0) => String::from("Crossfade out of this track: off (hard cut)"),
+            Some(secs) => format!("Crossfade out of this track: {}s", secs),
+            None => format!("Crossfade out of this track: default ({} ms)", self.config.crossfade_ms),
+        });
+        if let Err(e) = self.save_state() {
+            log::warn!("Could not save crossfade overrides: {}", e);
+        }
+    }
+
+    /// Auto-advances `crossfade_ms` before the current track ends, so the
+    /// next one fades in over it. Leaves the track to end by itself when
+    /// auto-advance wouldn't carry on straight away.
+    fn start_crossfade(&mut self) -> Result<()> {
+        if !self.crossfades() || self.is_paused {
+            return Ok(());
+        }
+        let fade = self.crossfade_out();
+        let Some(total) = self.track_end() else {
+            return Ok(());
+        };
+        if self.audio_player.position() + fade < total || self.upcoming_index().is_none() {
+            return Ok(());
+        }
+        log::debug!("Crossfading into the next track");
+        self.crossfade_next = Some(fade);
+        let advanced = self.auto_advance();
+        self.crossfade_next = None;
+        advanced
+    }
+
+    /// Picks up a gapless switch to the preloaded track.
+    fn follow_preloaded(&mut self) {
+        if !self.audio_player.advance_preloaded() {
+            return;
+        }
+        let Some(path) = self.audio_player.current_path().map(Path::to_path_buf) else {
+            return;
+        };
+        let Some(index) = self.music_files.iter().position(|f| f.path == path) else {
+            return;
+        };
+        if self.queued_files().next().is_some_and(|queued| queued.path == path) {
+            self.pop_queue();
+        }
+        if self.repeat != RepeatMode::One && self.config.follow_play {
+            self.selected_index = index;
+            self.sync_list_state();
+        }
+        self.track_started(index);
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(ref path) = self.current_playing {
+            log::info!("Stopped {}", path.display());
+        }
+        self.resume_position = self.position_to_remember();
+        self.advance_at = None;
+        self.audio_player.stop_with_fade();
+        self.current_playing = None;
+        self.current_duration = None;
+        self.is_paused = false;
+        self.rebuffering = false;
+        self.set_status("Stopped");
+        self.events.emit(PlayerEvent::Stopped);
+    }
+
+    pub fn pause(&mut self) {
+        if self.current_playing.is_some() && !self.is_paused {
+            self.audio_player.pause_with_fade();
+            self.is_paused = true;
+            self.set_status("Paused");
+            self.events.emit(PlayerEvent::Paused(self.audio_player.position()));
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.paused_by_volume = false;
+        self.rebuffering = false;
+        if self.current_playing.is_some() && self.is_paused {
+            let position = self.audio_player.position();
+            if self.config.resume_rewind_secs > 0 && !position.is_zero() {
+                let rewind = Duration::from_secs(self.config.resume_rewind_secs);
+                // Only worth it when cheap; a decoder that can't seek resumes where it was
+                match self.audio_player.seek_in_place(position.saturating_sub(rewind)) {
+                    Ok(true) => {}
+                    Ok(false) => log::debug!("Skipped the resume rewind, the decoder can't seek"),
+                    Err(e) => log::warn!("Resume rewind failed: {}", e),
+                }
+            }
+            self.audio_player.resume_with_fade();
+            self.is_paused = false;
+            self.events.emit(PlayerEvent::Resumed(self.audio_player.position()));
+            if let Some(name) = self.playing_name() {
+                self.set_status(format!("♪ Playing: {}", name));
+            }
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.current_playing.is_some() {
+            if self.is_paused {
+                self.resume();
+            } else {
+                self.pause();
+            }
+        }
+    }
+
+    /// Starts the current track over, staying paused if it was paused.
+    pub fn restart_current(&mut self) {
+        let Some(name) = self.playing_name() else {
+            return;
+        };
+        self.advance_at = None;
+        match self.audio_player.seek(Duration::ZERO) {
+            Ok(()) => {
+                self.set_status(format!("Restarted: {}", name));
+                self.events.emit(PlayerEvent::Seeked(Duration::ZERO));
+            }
+            Err(e) => self.set_status(format!("Could not restart {}: {}", name, e)),
+        }
+    }
+
+    pub fn seek_forward(&mut self) {
+        let target = self.audio_player.position() + SEEK_STEP;
+        self.seek_to(target);
+    }
+
+    pub fn seek_backward(&mut self) {
+        let target = self.audio_player.position().saturating_sub(SEEK_STEP);
+        self.seek_to(target);
+    }
+
+    pub fn start_seek_entry(&mut self) {
+        if self.current_playing.is_none() {
+            self.set_status("Nothing is playing");
+            return;
+        }
+        self.seek_input.clear();
+        self.mode = Mode::SeekEntry;
+    }
+
+    /// Takes a typed character: digits and colons, or a leading `+`/`-`
+    /// for a jump relative to the current position.
+    pub fn seek_entry_push(&mut self, c: char) {
+        let sign = (c == '+' || c == '-') && self.seek_input.is_empty();
+        if (c.is_ascii_digit() || c == ':' || sign) && self.seek_input.len() < 12 {
+            self.seek_input.push(c);
+        }
+    }
+
+    pub fn seek_entry_pop(&mut self) {
+        self.seek_input.pop();
+    }
+
+    /// Seeks to the typed time and leaves the entry. A time that doesn't
+    /// parse is reported and playback stays where it is; nothing typed
+    /// does nothing.
+    pub fn submit_seek_entry(&mut self) {
+        self.mode = Mode::Normal;
+        let input = std::mem::take(&mut self.seek_input);
+        if input.is_empty() {
+            return;
+        }
+        let (sign, time) = match input.strip_prefix(['+', '-']) {
+            Some(time) => (input.chars().next(), time),
+            None => (None, input.as_str()),
+        };
+        let Some(offset) = template::parse_timestamp(time) else {
+            self.set_status(format!("Invalid time '{}' - type seconds, m:ss or h:mm:ss", input));
+            return;
+        };
+        let position = self.audio_player.position();
+        let target = match sign {
+            Some('+') => position + offset,
+            Some(_) => position.saturating_sub(offset),
+            None => offset,
+        };
+        self.seek_to(target);
+    }
+
+    pub fn cancel_seek_entry(&mut self) {
+        self.mode = Mode::Normal;
+        self.seek_input.clear();
+    }
+
+    /// Jumps within the current track, clamped to its length when known.
+    pub fn seek_to(&mut self, target: Duration) {
+        if self.current_playing.is_none() {
+            return;
+        }
+        let target = match self.current_duration {
+            Some(total) => target.min(total),
+            None => target,
+        };
+        match self.audio_player.seek(target) {
+            Ok(()) => {
+                self.set_status(format!(
+                    "Seeked to {}",
+                    template::format_duration(target, self.config.time_format)
+                ));
+                self.events.emit(PlayerEvent::Seeked(target));
+            }
+            Err(e) => {
+                log::warn!("Seek failed: {}", e);
+                self.set_status(format!("Seeking isn't supported for this file: {}", e));
+            }
+        }
+    }
+
+    pub fn cycle_repeat(&mut self) {
+        self.repeat = self.repeat.next();
+        self.set_status(format!("Repeat: {}", self.repeat.label()));
+    }
+
+    /// Switches to the next sort key, keeping the direction.
+    pub fn cycle_sort(&mut self) {
+        self.sort_key = self.sort_key.next();
+        self.resort();
+    }
+
+    pub fn toggle_sort_direction(&mut self) {
+        self.sort_descending = !self.sort_descending;
+        self.resort();
+    }
+
+    /// Re-sorts the list in place, keeping the selected and playing tracks.
+    fn resort(&mut self) {
+        let selected = self.music_files.get(self.selected_index).map(|f| f.path.clone());
+        scan::sort_files_by(&mut self.music_files, self.sort_key, self.sort_descending);
+        self.reconcile_selection(selected);
+        self.set_status(format!(
+            "Sorted by {} ({})",
+            self.sort_key.label(),
+            if self.sort_descending { "descending" } else { "ascending" }
+        ));
+    }
+
+    /// Adds the selected track to the end of the queue.
+    pub fn enqueue_selected(&mut self) {
+        let Some(file) = self.music_files.get(self.selected_index) else {
+            return;
+        };
+        self.queue.push_back(file.path.clone());
+        self.set_status(format!("Queued {} ({} in queue)", file.display_name(), self.queue.len()));
+    }
+
+    /// Queues every track on screen in display order: the open folder in the
+    /// folder view, otherwise the list as filtered and sorted. The playing
+    /// track is left out so it doesn't come round again.
+    pub fn enqueue_visible(&mut self) {
+        let (files, what): (Vec<&MusicFile>, &str) = match self.browser {
+            Some(ref browser) => (
+                self.music_files.iter().filter(|file| file.path.starts_with(&browser.dir)).collect(),
+                "in this folder",
+            ),
+            None => (
+                self.visible.iter().map(|&i| &self.music_files[i]).collect(),
+                if self.search_query.is_empty() && !self.favorites_only { "in the list" } else { "match the filter" },
+            ),
+        };
+        let paths: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|file| self.current_playing.as_ref() != Some(&file.path))
+            .map(|file| file.path.clone())
+            .collect();
+        if paths.is_empty() {
+            self.set_status(format!("No tracks {} to queue", what));
+            return;
+        }
+        let count = paths.len();
+        self.queue.extend(paths);
+        self.set_status(format!("Queued {} tracks ({} in queue)", count, self.queue.len()));
+    }
+
+    /// Writes the queue, or the whole list if the queue is empty, to
+    /// `playlist.m3u` in the music directory.
+    pub fn save_playlist(&mut self) {
+        let path = self.music_directory().join("playlist.m3u");
+        let (files, what): (Vec<MusicFile>, &str) = if self.queue.is_empty() {
+            (self.music_files.clone(), "list")
+        } else {
+            (self.queued_files().cloned().collect(), "queue")
+        };
+        self.set_status(match playlist::save_playlist(&path, &files) {
+            Ok(()) => format!("Saved {} ({} tracks) to {}", what, files.len(), path.display()),
+            Err(e) => format!("Could not save playlist: {:#}", e),
+        });
+    }
+
+    /// Reads `playlist.m3u` from the music directory, as saved by
+    /// [`App::save_playlist`], to replace the list. Asks first when the list
+    /// isn't empty and `confirm_playlist_replace` is on.
+    pub fn load_saved_playlist(&mut self) {
+        let path = self.music_directory().join("playlist.m3u");
+        let files = match playlist::load_playlist(&path) {
+            Ok(files) => files,
+            Err(e) => {
+                self.set_status(format!("Could not load playlist: {:#}", e));
+                return;
+            }
+        };
+        self.pending_playlist = Some((path, files));
+        if self.config.confirm_playlist_replace && !self.music_files.is_empty() {
+            self.mode = Mode::ConfirmPlaylist;
+        } else {
+            self.apply_pending_playlist(false);
+        }
+    }
+
+    /// Puts the pending playlist in place of the list, or after it with
+    /// `append`, leaving out tracks already listed. A replaced list then
+    /// follows the playlist on refresh.
+    pub fn apply_pending_playlist(&mut self, append: bool) {
+        self.mode = Mode::Normal;
+        let Some((path, files)) = self.pending_playlist.take() else {
+            return;
+        };
+        let selected = self.music_files.get(self.selected_index).map(|f| f.path.clone());
+        if append {
+            let listed: HashSet<PathBuf> = self.music_files.iter().map(|f| f.path.clone()).collect();
+            let before = self.music_files.len();
+            self.music_files.extend(files.into_iter().filter(|f| !listed.contains(&f.path)));
+            self.set_status(format!("Appended {} tracks from {}", self.music_files.len() - before, path.display()));
+        } else {
+            // A scan still running would put the library back when it finishes
+            self.scan_rx = None;
+            self.scanning = false;
+            self.range = None;
+            self.music_files = files;
+            self.set_status(format!("Loaded playlist {} - {} tracks", path.display(), self.music_files.len()));
+            self.playlist = Some(path);
+        }
+        self.reconcile_selection(selected);
+    }
+
+    pub fn cancel_pending_playlist(&mut self) {
+        self.mode = Mode::Normal;
+        self.pending_playlist = None;
+    }
+
+    pub fn clear_queue(&mut self) {
+        self.queue.clear();
+        self.set_status("Queue cleared");
+    }
+
+    /// Queued tracks that are still in the list, in play order.
+    pub fn queued_files(&self) -> impl Iterator<Item = &MusicFile> {
+        self.queue
+            .iter()
+            .filter_map(|path| self.music_files.iter().find(|f| &f.path == path))
+    }
+
+    /// Pops queue entries until one is still in the list.
+    fn pop_queue(&mut self) -> Option<usize> {
+        while let Some(path) = self.queue.pop_front() {
+            if let Some(index) = self.music_files.iter().position(|f| f.path == path) {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    pub fn toggle_shuffle(&mut self) {
+        self.shuffle = !self.shuffle;
+        self.shuffle_history.clear();
+        self.shuffle_played.clear();
+        if self.shuffle {
+            if let Some(path) = self.playing_file().map(|f| f.path.clone()) {
+                self.shuffle_played.insert(path.clone());
+                self.shuffle_history.push(path);
+            }
+        }
+        self.set_status(format!("Shuffle: {}", if self.shuffle { "On" } else { "Off" }));
+    }
+
+    /// Whether next would run past the last track, or while shuffling, whether
+    /// every track has been played in this pass.
+    fn at_end(&self) -> bool {
+        if self.shuffle {
+            self.music_files.iter().all(|f| self.shuffle_played.contains(&f.path))
+        } else {
+            self.advance_origin() + 1 >= self.music_files.len()
+        }
+    }
+
+    /// A random track not yet played in this pass, starting a new pass once
+    /// all have been. Never the playing track unless it is the only one.
+    fn shuffle_pick(&mut self) -> Option<usize> {
+        let candidates = self.shuffle_candidates();
+        if self.at_end() {
+            self.shuffle_played.clear();
+        }
+        self.choose_shuffled(&candidates).or(self.playing_index())
+    }
+
+    /// A random pick from `candidates`, weighted by `shuffle_weighting`.
+    fn choose_shuffled(&self, candidates: &[usize]) -> Option<usize> {
+        let weighting = self.config.shuffle_weighting;
+        if weighting == ShuffleWeighting::Uniform {
+            return candidates.choose(&mut rand::thread_rng()).copied();
+        }
+        let recent: HashMap<&PathBuf, usize> = self.history.iter().enumerate().map(|(i, path)| (path, i)).collect();
+        candidates
+            .choose_weighted(&mut rand::thread_rng(), |&i| {
+                let path = &self.music_files[i].path;
+                shuffle_weight(weighting, self.favorites.contains(path), recent.get(path).copied())
+            })
+            .ok()
+            .copied()
+    }
+
+    /// Tracks shuffle may pick next: those not yet played in this pass, or
+    /// all of them once the pass is complete. Never the playing track.
+    fn shuffle_candidates(&self) -> Vec<usize> {
+        let new_pass = self.at_end();
+        let playing = self.playing_index();
+        (0..self.music_files.len())
+            .filter(|&i| Some(i) != playing)
+            .filter(|&i| new_pass || !self.shuffle_played.contains(&self.music_files[i].path))
+            .collect()
+    }
+
+    fn shuffle_next(&mut self) -> Result<()> {
+        match self.shuffle_pick() {
+            Some(index) => self.advance_to(index, true),
+            None => Ok(()),
+        }
+    }
+
+    /// Steps back through the shuffle history to the track played before this one.
+    fn shuffle_previous(&mut self) -> Result<()> {
+        if self.shuffle_history.len() < 2 {
+            self.set_status("No earlier track in the shuffle history");
+            return Ok(());
+        }
+        self.shuffle_history.pop();
+        let previous = self.shuffle_history.pop().unwrap_or_default();
+        match self.music_files.iter().position(|f| f.path == previous) {
+            // play_index pushes it back onto the history
+            Some(index) => self.advance_to(index, false),
+            None => self.shuffle_previous(),
+        }
+    }
+
+    /// Plays the next track. Past the end of the list this only wraps around
+    /// with [`RepeatMode::All`].
+    pub fn play_next(&mut self) -> Result<()> {
+        if let Some(index) = self.pop_queue() {
+            return self.advance_to(index, true);
+        }
+        if !self.music_files.is_empty() {
+            let current = self.advance_origin();
+            let was_at_end = self.at_end();
+            if self.shuffle {
+                if was_at_end && self.repeat != RepeatMode::All {
+                    self.set_status("Every track has been played");
+                    return Ok(());
+                }
+                return self.shuffle_next();
+            }
+            let Some(index) = step_index(current, self.music_files.len(), self.repeat, true) else {
+                self.set_status("Already at the last track");
+                return Ok(());
+            };
+            self.advance_to(index, true)?;
+
+            // Show special message when looping back to start
+            if was_at_end {
+                if let Some(name) = self.playing_name() {
+                    self.set_status(format!("♪ Looped to beginning - Playing: {}", name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Plays the previous track. Before the start of the list this only
+    /// wraps around to the last track with [`RepeatMode::All`], like
+    /// [`App::play_next`] at the end; moving the selection always wraps.
+    pub fn play_previous(&mut self) -> Result<()> {
+        if self.shuffle {
+            return self.shuffle_previous();
+        }
+        if !self.music_files.is_empty() {
+            let current = self.advance_origin();
+            let Some(index) = step_index(current, self.music_files.len(), self.repeat, false) else {
+                self.set_status("Already at the first track");
+                return Ok(());
+            };
+            self.advance_to(index, false)?;
+        }
+        Ok(())
+    }
+
+    /// Index that next/previous are relative to: the selection when playback
+    /// follows it, otherwise the playing track so browsing doesn't affect it.
+    fn advance_origin(&self) -> usize {
+        if self.config.follow_play {
+            self.selected_index
+        } else {
+            self.playing_index().unwrap_or(self.selected_index)
+        }
+    }
+
+    /// Plays `index`, applying `on_bad_file` if that track is flagged as
+    /// unplayable or fails to decode now. `forward` is the direction to keep
+    /// going when skipping, which stops at the ends of the list like
+    /// [`App::play_next`] unless repeat is All.
+    fn advance_to(&mut self, index: usize, forward: bool) -> Result<()> {
+        let paths: Vec<PathBuf> = self.music_files.iter().map(|f| f.path.clone()).collect();
+        let flagged = self.bad_files.clone();
+        // Shuffle picks and queued tracks aren't in list order, so the ends mean nothing there
+        let repeat = if self.shuffle { RepeatMode::All } else { self.repeat };
+        let mut failure = None;
+        let landing = find_playable(&paths, index, forward, repeat, &flagged, self.config.on_bad_file, |path| {
+            let Some(index) = self.music_files.iter().position(|f| f.path == path) else {
+                anyhow::bail!("no longer in the list");
+            };
+            if self.config.follow_play {
+                self.selected_index = index;
+                self.sync_list_state();
+            }
+            self.play_index(index)?;
+            // play_index flags the file when no decoder takes it
+            if self.bad_files.contains(path) {
+                failure = Some(std::mem::take(&mut self.status_message));
+                anyhow::bail!("no decoder takes it");
+            }
+            Ok(())
+        });
+
+        match landing {
+            Landing::Played { index, skipped } if skipped > 0 => {
+                self.set_status(format!(
+                    "Skipped {} unplayable file{} - ♪ Playing: {}",
+                    skipped,
+                    if skipped == 1 { "" } else { "s" },
+                    self.music_files[index].name
+                ));
+            }
+            Landing::Played { .. } => {}
+            Landing::Stopped(index) => {
+                self.stop();
+                self.set_status(match failure {
+                    Some(error) if !flagged.contains(&paths[index]) => error,
+                    _ => {
+                        let name = self.music_files[index].name.clone();
+                        log::info!("Stopping at flagged file {}", name);
+                        format!("Stopped at unplayable file: {}", name)
+                    }
+                });
+            }
+            Landing::End { skipped } => {
+                self.stop();
+                self.set_status(format!(
+                    "Skipped {} unplayable file{} - reached the {} of the list",
+                    skipped,
+                    if skipped == 1 { "" } else { "s" },
+                    if forward { "end" } else { "start" }
+                ));
+            }
+            Landing::Exhausted => {
+                self.stop();
+                self.set_status("All tracks are flagged as unplayable");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn volume_up(&mut self) {
+        self.muted = false;
+        self.volume = round_volume(self.volume + 0.1).min(self.config.max_volume());
+        self.apply_volume();
+        if self.paused_by_volume {
+            self.resume();
+        }
+        self.report_volume();
+    }
+
+    pub fn volume_down(&mut self) {
+        self.muted = false;
+        self.volume = round_volume(self.volume - 0.1).max(0.0);
+        self.apply_volume();
+        self.report_volume();
+        if self.volume == 0.0
+            && self.config.pause_at_zero_volume
+            && self.current_playing.is_some()
+            && !self.is_paused
+        {
+            self.pause();
+            self.paused_by_volume = true;
+            self.set_status("Volume: 0% - paused, press + to resume");
+        }
+    }
+
+    /// Sets the volume to `percent`, capped at `max_volume_percent`.
+    pub fn set_volume_percent(&mut self, percent: u16) {
+        self.muted = false;
+        self.volume = (percent as f32 / 100.0).min(self.config.max_volume());
+        self.apply_volume();
+        if self.paused_by_volume && self.volume > 0.0 {
+            self.resume();
+        }
+        self.report_volume();
+    }
+
+    pub fn start_volume_entry(&mut self) {
+        self.volume_input.clear();
+        self.mode = Mode::VolumeEntry;
+    }
+
+    /// Takes a typed character; anything but a digit, or a fourth digit,
+    /// is ignored.
+    pub fn volume_entry_push(&mut self, c: char) {
+        if c.is_ascii_digit() && self.volume_input.len() < 3 {
+            self.volume_input.push(c);
+        }
+    }
+
+    pub fn volume_entry_pop(&mut self) {
+        self.volume_input.pop();
+    }
+
+    /// Sets the typed volume, capped at the configured maximum, and leaves
+    /// the entry. Nothing typed leaves the volume as it is.
+    pub fn submit_volume_entry(&mut self) {
+        self.mode = Mode::Normal;
+        if let Ok(percent) = std::mem::take(&mut self.volume_input).parse() {
+            self.set_volume_percent(percent);
+        }
+    }
+
+    pub fn cancel_volume_entry(&mut self) {
+        self.mode = Mode::Normal;
+        self.volume_input.clear();
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.send_volume();
+        if self.muted {
+            self.set_status("Muted");
+        } else {
+            self.report_volume();
+        }
+    }
+
+    /// What the sink should play at: the volume above 100%, if any, or
+    /// nothing while muted. The rest is applied per track by [`App::gain_for`].
+    fn output_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume.max(1.0)
+        }
+    }
+
+    /// The factor `file` plays at on top of [`App::output_volume`]: the
+    /// volume scaled by the track's normalization and per-track gains.
+    /// Those gains never take it past 100%, or past the volume if that is
+    /// set higher, so it never exceeds 1.0.
+    fn gain_for(&self, file: Option<&MusicFile>) -> f32 {
+        let gain = file.map_or(1.0, |file| self.normalization_gain(file) * self.track_gain(file));
+        let headroom = self.volume.max(1.0);
+        (self.volume * gain).min(headroom) / headroom
+    }
+
+    /// Sends the volume to the sink and the gains of the playing and
+    /// preloaded tracks to their sources.
+    fn send_volume(&mut self) {
+        self.audio_player.set_volume(self.output_volume());
+        self.audio_player.set_track_gain(self.gain_for(self.playing_file()));
+        let preloaded = self
+            .audio_player
+            .preloaded()
+            .and_then(|path| self.music_files.iter().find(|f| f.path == path));
+        self.audio_player.set_preload_gain(self.gain_for(preloaded));
+    }
+
+    /// The factor the remembered per-track gain applies to `file`.
+    pub fn track_gain(&self, file: &MusicFile) -> f32 {
+        self.track_gains.get(&file.path).map_or(1.0, |db| 10f32.powf(db / 20.0))
+    }
+
+    /// Raises (or, with `steps` below zero, lowers) the playing track's own
+    /// gain, which is remembered for every later play of the file.
+    pub fn adjust_track_gain(&mut self, steps: f32) {
+        let Some(path) = self.current_playing.clone() else {
+            self.set_status("Nothing playing");
+            return;
+        };
+        let db = self.track_gains.get(&path).copied().unwrap_or(0.0) + steps * TRACK_GAIN_STEP_DB;
+        let db = db.clamp(-MAX_TRACK_GAIN_DB, MAX_TRACK_GAIN_DB);
+        if db == 0.0 {
+            self.track_gains.remove(&path);
+        } else {
+            self.track_gains.insert(path, db);
+        }
+        self.set_status(format!("Track gain: {:+} dB", db));
+        self.track_gains_changed();
+    }
+
+    pub fn clear_track_gain(&mut self) {
+        let Some(ref path) = self.current_playing else {
+            self.set_status("Nothing playing");
+            return;
+        };
+        self.track_gains.remove(path);
+        self.set_status("Track gain cleared");
+        self.track_gains_changed();
+    }
+
+    fn track_gains_changed(&mut self) {
+        self.send_volume();
+        if let Err(e) = self.save_state() {
+            log::warn!("Could not save track gains: {}", e);
+        }
+    }
+
+    /// The factor normalization applies to `file`; 1.0 when it is off or
+    /// the file has no ReplayGain tags.
+    pub fn normalization_gain(&self, file: &MusicFile) -> f32 {
+        if self.normalize {
+            file.replay_gain.unwrap_or(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    pub fn toggle_normalize(&mut self) {
+        self.normalize = !self.normalize;
+        self.send_volume();
+        self.set_status(format!("Loudness normalization: {}", if self.normalize { "On" } else { "Off" }));
+    }
+
+    pub fn toggle_limiter(&mut self) {
+        let limiter = self.audio_player.limiter();
+        limiter.set_enabled(!limiter.is_enabled());
+        self.set_status(format!("Limiter: {}", if limiter.is_enabled() { "On" } else { "Off" }));
+    }
+
+    /// Whether the limiter bent any peaks within the last [`LIMITER_HOLD`].
+    pub fn limiter_engaged(&self) -> bool {
+        self.limiter_engaged_at.is_some_and(|at| at.elapsed() < LIMITER_HOLD)
+    }
+
+    fn check_limiter(&mut self) {
+        let limited = self.audio_player.limiter().limited();
+        if limited > self.limited_seen {
+            self.limited_seen = limited;
+            self.limiter_engaged_at = Some(Instant::now());
+        }
+    }
+
+    /// Whether the quit key should quit now. With `confirm_quit` on and a
+    /// track playing it asks first instead, and the answer comes back
+    /// through [`Mode::ConfirmQuit`].
+    pub fn request_quit(&mut self) -> bool {
+        if !self.config.confirm_quit || self.current_playing.is_none() {
+            return true;
+        }
+        self.mode = Mode::ConfirmQuit;
+        false
+    }
+
+    /// Shows the status log in place of the help panel, newest first, or
+    /// hides it.
+    pub fn toggle_status_log(&mut self) {
+        self.mode = match self.mode {
+            Mode::StatusLog { .. } => Mode::Normal,
+            _ => Mode::StatusLog { selected: 0 },
+        };
+    }
+
+    /// Shows the queue in place of the help panel, or hides it.
+    pub fn toggle_queue_panel(&mut self) {
+        self.mode = match self.mode {
+            Mode::Queue { .. } => Mode::Normal,
+            _ => Mode::Queue { selected: 0 },
+        };
+    }
+
+    /// Plays queue entry `selected` now and takes it out of the queue. The
+    /// entries before it stay queued.
+    pub fn play_from_queue(&mut self, selected: usize) -> Result<()> {
+        let Some(path) = self.queue.get(selected).cloned() else {
+            return Ok(());
+        };
+        let Some(index) = self.music_files.iter().position(|f| f.path == path) else {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.set_status(format!("{} is not in the current list", name));
+            return Ok(());
+        };
+        self.queue.remove(selected);
+        self.mode = Mode::Queue { selected: selected.min(self.queue.len().saturating_sub(1)) };
+        self.play_index(index)
+    }
+
+    /// Takes queue entry `selected` out of the queue.
+    pub fn remove_from_queue(&mut self, selected: usize) {
+        let Some(path) = self.queue.remove(selected) else {
+            return;
+        };
+        self.mode = Mode::Queue { selected: selected.min(self.queue.len().saturating_sub(1)) };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        self.set_status(format!("Removed {} from the queue ({} left)", name, self.queue.len()));
+    }
+
+    /// Shows `message` in the status bar and adds it to the status log,
+    /// unless it repeats the last entry.
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status_message = message.into();
+        if self.status_message.is_empty()
+            || self.status_log.back().is_some_and(|(_, message)| *message == self.status_message)
+        {
+            return;
+        }
+        self.status_log.push_back((Instant::now(), self.status_message.clone()));
+        if self.status_log.len() > STATUS_LOG_LEN {
+            self.status_log.pop_front();
+        }
+    }
+
+    /// Shows a message that changes as work goes on, like a countdown or a
+    /// progress count, without filling the status log with every step.
+    fn set_progress(&mut self, message: String) {
+        self.status_message = message;
+    }
+
+    pub fn toggle_skip_silence(&mut self) {
+        self.skip_silence = !self.skip_silence;
+        self.audible_end = None;
+        self.silence_rx = None;
+        if self.skip_silence {
+            if let Some(path) = self.current_playing.clone() {
+                self.find_audible_end(path);
+            }
+        }
+        self.set_status(format!("Skip trailing silence: {}", if self.skip_silence { "On" } else { "Off" }));
+    }
+
+    /// Looks for trailing silence in `path` on a background thread; see
+    /// [`App::drain_audible_end`].
+    fn find_audible_end(&mut self, path: PathBuf) {
+        let threshold = self.config.silence_threshold_db;
+        let min_silence = Duration::from_millis(self.config.min_silence_ms);
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let end = audio::audible_end(&path, threshold, min_silence).unwrap_or_else(|e| {
+                log::debug!("Could not look for silence in {}: {}", path.display(), e);
+                None
+            });
+            let _ = tx.send((path, end));
+        });
+        self.silence_rx = Some(rx);
+    }
+
+    fn drain_audible_end(&mut self) {
+        let Some(ref rx) = self.silence_rx else {
+            return;
+        };
+        if let Ok((path, end)) = rx.try_recv() {
+            self.silence_rx = None;
+            if self.current_playing.as_ref() == Some(&path) {
+                if let Some(end) = end {
+                    log::debug!("{} falls silent at {:?}", path.display(), end);
+                }
+                self.audible_end = end;
+            }
+        }
+    }
+
+    /// Where the playing track ends for auto-advance: its length, or where
+    /// it falls silent when skipping silence.
+    fn track_end(&self) -> Option<Duration> {
+        self.audible_end.or(self.current_duration)
+    }
+
+    pub fn speed_up(&mut self) {
+        self.set_speed(round_volume(self.speed + SPEED_STEP));
+    }
+
+    pub fn speed_down(&mut self) {
+        self.set_speed(round_volume(self.speed - SPEED_STEP));
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        self.audio_player.set_speed(self.speed);
+        self.set_status(if self.speed == 1.0 {
+            String::from("Speed: 1.0x")
+        } else {
+            format!("Speed: {:.1}x (pitch shifts with speed)", self.speed)
+        });
+    }
+
+    /// One line describing playback for scripts, e.g.
+    /// `playing | Artist - Title | 1:23 / 4:56 | volume 80%`.
+    pub fn status_line(&self) -> String {
+        let state = match (&self.current_playing, self.is_paused) {
+            (None, _) => "stopped",
+            (Some(_), true) => "paused",
+            (Some(_), false) => "playing",
+        };
+        let mut parts = vec![state.to_string()];
+        if let Some(name) = self.playing_name() {
+            parts.push(self.playing_file().map_or(name, MusicFile::display_name));
+            let position = template::format_duration(self.audio_player.position(), self.config.time_format);
+            parts.push(match self.current_duration {
+                Some(total) => format!("{} / {}", position, template::format_duration(total, self.config.time_format)),
+                None => position,
+            });
+        }
+        parts.push(format!("volume {}%{}", self.volume_percent(), if self.muted { " (muted)" } else { "" }));
+        parts.join(" | ")
+    }
+
+    pub fn volume_percent(&self) -> u32 {
+        (self.volume * 100.0).round() as u32
+    }
+
+    fn report_volume(&mut self) {
+        self.set_status(if self.volume > 1.0 {
+            format!("Volume: {}% (above 100% may clip)", self.volume_percent())
+        } else {
+            format!("Volume: {}%", self.volume_percent())
+        });
+    }
+
+    /// Sends the volume to the sink and remembers it for the current output device.
+    fn apply_volume(&mut self) {
+        self.send_volume();
+        if let Some(name) = self.audio_player.device_name() {
+            self.device_volumes.insert(name.to_string(), self.volume);
+        }
+    }
+
+    pub fn tick_rate_up(&mut self) {
+        let fps = self.tick_rate_fps();
+        let next = TICK_RATES_FPS.iter().copied().find(|&rate| rate > fps);
+        self.set_tick_rate_fps(next.unwrap_or(TICK_RATES_FPS[TICK_RATES_FPS.len() - 1]));
+    }
+
+    pub fn tick_rate_down(&mut self) {
+        let fps = self.tick_rate_fps();
+        let next = TICK_RATES_FPS.iter().rev().copied().find(|&rate| rate < fps);
+        self.set_tick_rate_fps(next.unwrap_or(TICK_RATES_FPS[0]));
+    }
+
+    /// How long the event loop should wait for input before redrawing.
+    pub fn poll_timeout(&self) -> Duration {
+        if self.stress_mode {
+            Duration::ZERO
+        } else {
+            self.tick_rate
+        }
+    }
+
+    /// Shows or hides the cover art, loading it for the playing track.
+    pub fn toggle_album_art(&mut self) {
+        self.show_album_art = !self.show_album_art;
+        if self.show_album_art {
+            if let Some(path) = self.playing_file().map(|f| f.path.clone()) {
+                self.load_album_art(path);
+            }
+        } else {
+            self.album_art = None;
+            self.art_rx = None;
+        }
+        self.set_status(format!("Cover art: {}", if self.show_album_art { "On" } else { "Off" }));
+    }
+
+    /// Decodes the cover of `path` in the background; the old cover goes
+    /// away straight away so it is never shown against the wrong track.
+    fn load_album_art(&mut self, path: PathBuf) {
+        if self.album_art.as_ref().is_some_and(|art| art.path == path) {
+            return;
+        }
+        self.album_art = None;
+        self.art_rx = Some(art::spawn_load(path));
+    }
+
+    fn drain_album_art(&mut self) {
+        let Some(ref rx) = self.art_rx else {
+            return;
+        };
+        if let Ok(art) = rx.try_recv() {
+            self.art_rx = None;
+            self.album_art = Some(art);
+        }
+    }
+
+    /// Shows or hides the spectrum; sample collection stops while hidden.
+    pub fn toggle_visualizer(&mut self) {
+        self.show_visualizer = !self.show_visualizer;
+        self.audio_player.tap().set_enabled(self.show_visualizer);
+        self.set_status(format!("Visualizer: {}", if self.show_visualizer { "On" } else { "Off" }));
+    }
+
+    pub fn toggle_stress_mode(&mut self) {
+        self.stress_mode = !self.stress_mode;
+        self.frame_stats = FrameStats::default();
+    }
+
+    pub fn tick_rate_fps(&self) -> u64 {
+        1000 / (self.tick_rate.as_millis() as u64).max(1)
+    }
+
+    fn set_tick_rate_fps(&mut self, fps: u64) {
+        self.tick_rate = Duration::from_millis(1000 / fps);
+        self.set_status(format!("Refresh rate: {} fps", fps));
+    }
+
+    pub fn save_state(&self) -> Result<()> {
+        let state = State {
+            tick_rate_ms: Some(self.tick_rate.as_millis() as u64),
+            volume: Some(self.volume),
+            music_directory: Some(canonical(self.music_directory())),
+            music_roots: if self.music_roots.len() > 1 {
+                self.music_roots.iter().map(|root| canonical(root)).collect()
+            } else {
+                Vec::new()
+            },
+            last_played: self.last_played.clone(),
+            last_position_ms: self
+                .position_to_remember()
+                .or(self.resume_position)
+                .map(|position| position.as_millis() as u64),
+            device_volumes: self.device_volumes.clone(),
+            eq_gains: self.audio_player.eq_gains().to_vec(),
+            favorites: self.favorites.clone(),
+            history: self.history.clone(),
+            track_gains: self.track_gains.clone(),
+            crossfade_overrides: self.crossfade_overrides.clone(),
+        };
+        state.save()
+    }
+
+    /// Where the playing track has got to, if it is long enough for
+    /// `remember_position_min_minutes` and not at either end.
+    fn position_to_remember(&self) -> Option<Duration> {
+        let minutes = self.config.remember_position_min_minutes;
+        let total = self.current_duration?;
+        if minutes == 0 || self.current_playing.is_none() || total < Duration::from_secs(minutes * 60) {
+            return None;
+        }
+        let position = self.audio_player.position();
+        (position > Duration::ZERO && position < total).then_some(position)
+    }
+
+    /// Status text offering to pick up the last session, when there is one.
+    fn resume_hint(&self) -> Option<String> {
+        let position = self.resume_position?;
+        let file = self.last_played.as_ref()?.file_name()?.to_string_lossy();
+        Some(format!(
+            "'c' resumes {} at {}",
+            file,
+            template::format_duration(position, self.config.time_format)
+        ))
+    }
+
+    /// Plays the last session's track from where it was left.
+    pub fn resume_last_session(&mut self) -> Result<()> {
+        let (Some(path), Some(position)) = (self.last_played.clone(), self.resume_position) else {
+            self.set_status("No position saved to resume");
+            return Ok(());
+        };
+        if !path.exists() {
+            self.resume_position = None;
+            self.set_status(format!("{} no longer exists", path.display()));
+            return Ok(());
+        }
+        let Some(index) = self.music_files.iter().position(|f| f.path == path) else {
+            self.set_status(format!("{} is not in the current list", path.display()));
+            return Ok(());
+        };
+        self.selected_index = index;
+        self.sync_list_state();
+        self.play_index(index)?;
+        if self.playing_index() == Some(index) {
+            self.seek_to(position);
+            self.set_status(format!(
+                "Resumed {} at {}",
+                self.music_files[index].name,
+                template::format_duration(position, self.config.time_format)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Opens the identity card for the selected track. The hash is computed
+    /// here rather than during the scan to keep scanning fast.
+    pub fn identify_selected(&mut self) {
+        let Some(file) = self.music_files.get(self.selected_index) else {
+            self.set_status("No file selected");
+            return;
+        };
+        match identify::identify(&file.path, self.config.time_format) {
+            Ok(identity) => {
+                self.identity = Some(identity);
+                self.mode = Mode::Identify;
+            }
+            Err(e) => self.set_status(format!("Could not identify {}: {}", file.name, e)),
+        }
+    }
+
+    pub fn open_pinned_dirs(&mut self) {
+        if self.config.pinned_dirs.is_empty() {
+            self.set_status("No pinned directories - add pinned_dirs to config.toml");
+        } else {
+            self.mode = Mode::PinnedDirs { selected: 0 };
+        }
+    }
+
+    /// Switches the file list between the flat track list and the folder
+    /// view, which opens at the selected track's folder.
+    pub fn toggle_tree_view(&mut self) {
+        if self.browser.take().is_some() {
+            self.set_status("List view");
+            return;
+        }
+        let selected = self.music_files.get(self.selected_index).map(|file| file.path.clone());
+        let dir = selected.as_deref().and_then(Path::parent).unwrap_or(self.music_directory());
+        let root = self.root_of(dir).to_path_buf();
+        let dir = if dir.starts_with(&root) { dir.to_path_buf() } else { root.clone() };
+        let mut browser = Browser::open(&root, &dir, &self.config);
+        if let Some(ref path) = selected {
+            browser.select_path(path);
+        }
+        self.browser = Some(browser);
+        self.set_status("Folder view - Enter opens, Backspace goes up, 'a' queues a folder");
+    }
+
+    pub fn browser_move(&mut self, delta: isize) {
+        if let Some(ref mut browser) = self.browser {
+            browser.move_by(delta);
+        }
+    }
+
+    pub fn browser_page_down(&mut self) {
+        self.browser_move(self.page_size() as isize);
+    }
+
+    pub fn browser_page_up(&mut self) {
+        self.browser_move(-(self.page_size() as isize));
+    }
+
+    /// Opens the selected folder, or plays the selected track and selects it
+    /// in the flat list too.
+    pub fn browser_activate(&mut self) -> Result<()> {
+        let Some(ref mut browser) = self.browser else {
+            return Ok(());
+        };
+        match browser.selected_entry().cloned() {
+            Some(ref entry @ BrowserEntry::Dir(_)) if browser.is_parent(entry) => browser.up(&self.config),
+            Some(BrowserEntry::Dir(dir)) => match self.config.folder_enter {
+                FolderEnter::Open => browser.enter(&dir, &self.config),
+                FolderEnter::Play => self.play_folder(&dir)?,
+            },
+            Some(BrowserEntry::Track(file)) => {
+                match self.music_files.iter().position(|f| f.path == file.path) {
+                    Some(index) => {
+                        self.selected_index = index;
+                        self.sync_list_state();
+                        self.play_index(index)?;
+                    }
+                    None => {
+                        self.set_status(format!("{} is not in the current list", file.display_name()));
+                    }
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Plays the folder selected in the folder view, or the folder of the
+    /// selected track in the list; see [`App::play_folder`].
+    pub fn play_selected_folder(&mut self) -> Result<()> {
+        let folder = match self.browser {
+            Some(ref browser) => match browser.selected_entry() {
+                Some(entry @ BrowserEntry::Dir(_)) if browser.is_parent(entry) => None,
+                Some(BrowserEntry::Dir(dir)) => Some(dir.clone()),
+                Some(BrowserEntry::Track(file)) => file.path.parent().map(Path::to_path_buf),
+                None => None,
+            },
+            None => self
+                .music_files
+                .get(self.selected_index)
+                .and_then(|f| f.path.parent())
+                .map(Path::to_path_buf),
+        };
+        match folder {
+            Some(folder) => self.play_folder(&folder),
+            None => Ok(()),
+        }
+    }
+
+    /// Plays the first listed track under `dir` and queues the rest ahead
+    /// of anything already queued, in list order.
+    fn play_folder(&mut self, dir: &Path) -> Result<()> {
+        let indices: Vec<usize> = (0..self.music_files.len())
+            .filter(|&i| self.music_files[i].path.starts_with(dir))
+            .collect();
+        let Some((&first, rest)) = indices.split_first() else {
+            self.set_status(format!("No tracks in {}", dir.display()));
+            return Ok(());
+        };
+        for &i in rest.iter().rev() {
+            self.queue.push_front(self.music_files[i].path.clone());
+        }
+        self.selected_index = first;
+        self.sync_list_state();
+        self.play_index(first)
+    }
+
+    pub fn browser_up(&mut self) {
+        if let Some(ref mut browser) = self.browser {
+            browser.up(&self.config);
+        }
+    }
+
+    /// Queues the selected track, or every listed track under the selected
+    /// folder in list order.
+    pub fn browser_enqueue(&mut self) {
+        let Some(ref browser) = self.browser else {
+            return;
+        };
+        let Some(entry) = browser.selected_entry() else {
+            return;
+        };
+        let (paths, what): (Vec<PathBuf>, String) = match entry {
+            BrowserEntry::Dir(_) if browser.is_parent(entry) => return,
+            BrowserEntry::Dir(dir) => (
+                self.music_files
+                    .iter()
+                    .filter(|file| file.path.starts_with(dir))
+                    .map(|file| file.path.clone())
+                    .collect(),
+                dir.file_name().map_or_else(|| dir.display().to_string(), |name| name.to_string_lossy().to_string()),
+            ),
+            BrowserEntry::Track(file) => (vec![file.path.clone()], file.display_name()),
+        };
+        if paths.is_empty() {
+            self.set_status(format!("No tracks in {}", what));
+            return;
+        }
+        let count = paths.len();
+        self.queue.extend(paths);
+        self.set_status(if count == 1 {
+            format!("Queued {} ({} in queue)", what, self.queue.len())
+        } else {
+            format!("Queued {} tracks from {} ({} in queue)", count, what, self.queue.len())
+        });
+    }
+
+    pub fn open_history(&mut self) {
+        self.mode = Mode::History { selected: 0 };
+        if self.history.is_empty() {
+            self.set_status("Nothing played yet");
+        }
+    }
+
+    /// Plays entry `selected` of the history, which moves it to the top.
+    pub fn play_from_history(&mut self, selected: usize) -> Result<()> {
+        let Some(path) = self.history.get(selected).cloned() else {
+            return Ok(());
+        };
+        let Some(index) = self.music_files.iter().position(|f| f.path == path) else {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.set_status(format!("{} is not in the current list", name));
+            return Ok(());
+        };
+        self.play_index(index)?;
+        self.mode = Mode::History { selected: 0 };
+        Ok(())
+    }
+
+    pub fn open_equalizer(&mut self) {
+        self.mode = Mode::Equalizer { selected: 0 };
+    }
+
+    /// Raises or lowers equalizer band `band` by `delta` dB.
+    pub fn adjust_eq_band(&mut self, band: usize, delta: f32) {
+        let Some(gain) = self.audio_player.eq_gains().get(band).copied() else {
+            return;
+        };
+        self.audio_player.set_eq_band(band, gain + delta);
+        self.set_status(format!(
+            "EQ {} Hz: {:+} dB",
+            eq::BAND_LABELS[band],
+            self.audio_player.eq_gains()[band]
+        ));
+    }
+
+    pub fn set_eq_preset(&mut self, preset: Preset) {
+        self.audio_player.set_eq_gains(&preset.gains());
+        self.set_status(format!("EQ: {}", preset.label()));
+    }
+
+    /// Switches to the preset after the current one, or to Flat when the
+    /// bands have been set by hand.
+    pub fn cycle_eq_preset(&mut self) {
+        let next = Preset::matching(&self.audio_player.eq_gains()).map_or(Preset::Flat, Preset::next);
+        self.set_eq_preset(next);
+    }
+
+    /// Name of the preset the bands match, or "Custom".
+    pub fn eq_preset_label(&self) -> &'static str {
+        Preset::matching(&self.audio_player.eq_gains()).map_or("Custom", Preset::label)
+    }
+
+    /// Lists the output devices, highlighting the one in use.
+    pub fn open_device_picker(&mut self) {
+        self.output_devices = audio::list_output_devices();
+        if self.output_devices.is_empty() {
+            self.set_status("No output devices found");
+            return;
+        }
+        let selected = self
+            .audio_player
+            .device_name()
+            .and_then(|current| self.output_devices.iter().position(|name| name == current))
+            .unwrap_or(0);
+        self.mode = Mode::Devices { selected };
+    }
+
+    /// Moves playback to the output device called `name` without interrupting
+    /// the current track, and applies the volume remembered for that device.
+    pub fn set_output_device(&mut self, name: &str) {
+        self.mode = Mode::Normal;
+        match self.audio_player.set_device(name) {
+            Ok(true) => {
+                if let Some(volume) = self.device_volumes.get(name).copied() {
+                    self.volume = volume.min(self.config.max_volume());
+                }
+                self.send_volume();
+                self.set_status(format!("Output: {}", name));
+            }
+            Ok(false) => {
+                self.set_status(format!(
+                    "Output device '{}' not found - still using {}",
+                    name,
+                    self.audio_player.device_name().unwrap_or("the default device")
+                ));
+            }
+            Err(e) => {
+                log::error!("Could not switch to output device {}: {}", name, e);
+                self.set_status(format!("Could not switch to '{}': {}", name, e));
+            }
+        }
+    }
+
+    /// The first music directory. The folder view, the directory picker and
+    /// saved playlists start here.
+    pub fn music_directory(&self) -> &Path {
+        &self.music_roots[0]
+    }
+
+    /// The music directory `path` lies under, or the first one.
+    fn root_of(&self, path: &Path) -> &Path {
+        self.music_roots
+            .iter()
+            .find(|root| path.starts_with(root))
+            .unwrap_or(&self.music_roots[0])
+    }
+
+    /// The music directory, or how many there are when there are several.
+    pub fn roots_label(&self) -> String {
+        match self.music_roots.as_slice() {
+            [root] => root.display().to_string(),
+            roots => format!("{} folders", roots.len()),
+        }
+    }
+
+    /// Opens the directory browser at the current music directory.
+    pub fn open_dir_picker(&mut self) {
+        self.dir_picker = Some(DirPicker::open(self.music_directory()));
+        self.mode = Mode::DirPicker { selected: 0 };
+        self.set_status("Choose a music directory");
+    }
+
+    pub fn close_dir_picker(&mut self) {
+        self.dir_picker = None;
+        self.mode = Mode::Normal;
+        if self.music_files.is_empty() {
+            self.set_status("No directory chosen - Press 'r' to scan the current one or 'q' to quit");
+        }
+    }
+
+    /// Switches the library to `dir`. Playback carries on with the current track.
+    pub fn change_directory(&mut self, dir: PathBuf) -> Result<()> {
+        self.mode = Mode::Normal;
+        self.dir_picker = None;
+        if !dir.is_dir() {
+            self.set_status(format!("Directory '{}' does not exist", dir.display()));
+            return Ok(());
+        }
+
+        self.music_roots = vec![dir];
+        self.playlist = None;
+        // A range picked for the old library means nothing in the new one
+        self.range = None;
+        self.selected_index = 0;
+        self.sync_list_state();
+        if self.browser.is_some() {
+            self.browser = Some(Browser::open(self.music_directory(), self.music_directory(), &self.config));
+        }
+        self.start_scan();
+        if self.library_watcher.is_some() {
+            self.watch_library();
+        }
+        Ok(())
+    }
+
+    pub fn refresh_files(&mut self) -> Result<()> {
+        match self.playlist.clone() {
+            Some(path) => self.reload_playlist(&path),
+            None => self.start_scan(),
+        }
+        Ok(())
+    }
+
+    /// Rereads the playlist the list came from, keeping its order. The
+    /// selection carries over as with a rescan.
+    fn reload_playlist(&mut self, path: &Path) {
+        let music_files = match playlist::load_playlist(path) {
+            Ok(files) => files,
+            Err(e) => {
+                log::warn!("Could not reload {}: {:#}", path.display(), e);
+                self.set_status(format!("Could not reload playlist: {:#}", e));
+                return;
+            }
+        };
+        let selected = self.music_files.get(self.selected_index).map(|f| f.path.clone());
+        self.music_files = music_files;
+        self.reconcile_selection(selected);
+        self.set_status(format!("Reloaded playlist {} - {} tracks", path.display(), self.music_files.len()));
+    }
+
+    /// Rescans the library by itself whenever files under the music
+    /// directory are added, removed or renamed.
+    pub fn watch_library(&mut self) {
+        self.library_watcher = match watch::spawn(&self.music_roots) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                log::warn!("Could not watch {}: {}", self.roots_label(), e);
+                self.set_status(format!("Not watching for new files: {}", e));
+                None
+            }
+        };
+    }
+
+    /// Starts a rescan after the watcher saw a change, once any running scan
+    /// is done. The selection and playing track carry over as with `r`.
+    fn drain_library_changes(&mut self) {
+        let Some(ref watcher) = self.library_watcher else {
+            return;
+        };
+        if watcher.changed() {
+            self.rescan_pending = true;
+        }
+        if !self.rescan_pending || self.scanning {
+            return;
+        }
+        self.rescan_pending = false;
+        log::info!("Files changed in {}, rescanning", self.roots_label());
+        if let Some(ref mut browser) = self.browser {
+            browser.reload(&self.config);
+        }
+        self.start_scan();
+    }
+
+    /// Scans the music directory on a background thread. The current list
+    /// stays up until the scan finishes; see [`App::drain_scan_updates`].
+    fn start_scan(&mut self) {
+        self.scan_rx = Some(scan::spawn(self.music_roots.clone(), self.config.clone()));
+        self.scanning = true;
+        self.scan_found = 0;
+        self.set_status(format!("Scanning {}...", self.roots_label()));
+    }
+
+    fn drain_scan_updates(&mut self) {
+        let Some(ref rx) = self.scan_rx else {
+            return;
+        };
+        let mut finished = None;
+        while let Ok(update) = rx.try_recv() {
+            match update {
+                ScanUpdate::Found(count) => self.scan_found = count,
+                ScanUpdate::Finished(files, stats) => finished = Some((files, stats)),
+            }
+        }
+        let Some((music_files, scan_stats)) = finished else {
+            return;
+        };
+
+        self.scan_rx = None;
+        self.scanning = false;
+        // On the first scan there is no selection yet, so land on the last played track
+        let selected = self
+            .music_files
+            .get(self.selected_index)
+            .map(|f| f.path.clone())
+            .or_else(|| self.last_played.clone());
+        self.music_files = music_files;
+        scan::sort_files_by(&mut self.music_files, self.sort_key, self.sort_descending);
+        self.reconcile_selection(selected);
+
+        self.set_status(if self.music_files.is_empty() {
+            format!("No music files found{} - Press 'r' to refresh or 'q' to quit", scan_stats.summary())
+        } else {
+            let hint = self.resume_hint().unwrap_or_else(|| String::from("Enter to play"));
+            format!(
+                "Found {} music files in {}{} - {}",
+                self.music_files.len(),
+                self.roots_label(),
+                scan_stats.summary(),
+                hint
+            )
+        });
+        if let Some((first, last)) = self.range {
+            self.apply_range(first, last);
+        }
+        if std::mem::take(&mut self.play_when_loaded) {
+            if let Err(e) = self.start_playback() {
+                log::warn!("Could not start playback: {}", e);
+            }
+        }
+    }
+
+    /// Starts playback from the top of the list, or a random track while
+    /// shuffling, waiting for a running scan to finish first.
+    pub fn play_when_loaded(&mut self) -> Result<()> {
+        if self.scanning {
+            self.play_when_loaded = true;
+            return Ok(());
+        }
+        self.start_playback()
+    }
+
+    fn start_playback(&mut self) -> Result<()> {
+        if self.shuffle {
+            self.shuffle_next()
+        } else {
+            self.go_first();
+            self.play_selected()
+        }
+    }
+
+    /// Keeps only tracks `first..=last` (1-based) of the sorted list, now and
+    /// after every rescan of the library. Bounds outside the list are clamped
+    /// and reported in the status bar. While the library is still being
+    /// scanned this waits for the scan to finish.
+    pub fn restrict_to_range(&mut self, first: usize, last: usize) {
+        self.range = Some((first, last));
+        if !self.scanning {
+            self.apply_range(first, last);
+        }
+    }
+
+    fn apply_range(&mut self, first: usize, last: usize) {
+        let len = self.music_files.len();
+        if len == 0 {
+            return;
+        }
+        let (first, last) = if first <= last { (first, last) } else { (last, first) };
+        let start = first.clamp(1, len);
+        let end = last.clamp(start, len);
+
+        let selected = self.music_files.get(self.selected_index).map(|f| f.path.clone());
+        self.music_files = self.music_files.drain(start - 1..end).collect();
+        self.selected_index = 0;
+        self.reconcile_selection(selected);
+        self.set_status(if (start, end) != (first, last) {
+            format!(
+                "Range {}-{} clamped to {}-{} ({} tracks available)",
+                first, last, start, end, len
+            )
+        } else {
+            format!("Playing tracks {}-{} of {}", start, end, len)
+        });
+    }
+
+    /// Rescans only the selected track's folder and merges the result into
+    /// the list, which is much cheaper than a full refresh on big libraries.
+    pub fn refresh_selected_folder(&mut self) {
+        let folder = self
+            .music_files
+            .get(self.selected_index)
+            .and_then(|f| f.path.parent())
+            .map(|p| p.to_path_buf());
+        match folder {
+            Some(folder) => self.refresh_folder(&folder),
+            None => self.set_status("No file selected"),
+        }
+    }
+
+    pub fn refresh_folder(&mut self, folder: &Path) {
+        if self.range.is_some() {
+            // The range is taken from the whole library, so rescan all of it
+            return self.start_scan();
+        }
+        let selected = self.music_files.get(self.selected_index).map(|f| f.path.clone());
+        let (found, scan_stats) = scan::scan_folder(self.root_of(folder), folder, &self.config);
+
+        let before = self.music_files.len();
+        self.music_files.retain(|f| !f.path.starts_with(folder));
+        let removed = before - self.music_files.len();
+        let added = found.len();
+        self.music_files.extend(found);
+        scan::sort_files_by(&mut self.music_files, self.sort_key, self.sort_descending);
+        self.reconcile_selection(selected);
+
+        self.set_status(format!(
+            "Rescanned {} - Found {} music files (was {}){}",
+            folder.display(),
+            added,
+            removed,
+            scan_stats.summary()
+        ));
+    }
+
+    /// The list entry for the track that is currently playing, if it is listed.
+    pub fn playing_file(&self) -> Option<&MusicFile> {
+        self.playing_index().map(|i| &self.music_files[i])
+    }
+
+    pub fn playing_index(&self) -> Option<usize> {
+        self.music_files.iter().position(|f| self.is_playing(f))
+    }
+
+    /// Whether `file` is the playing track. Matched by path, since the same
+    /// file name can turn up in several folders.
+    pub fn is_playing(&self, file: &MusicFile) -> bool {
+        self.current_playing.as_ref() == Some(&file.path)
+    }
+
+    /// File name of the playing track.
+    pub fn playing_name(&self) -> Option<String> {
+        let path = self.current_playing.as_ref()?;
+        Some(path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string()))
+    }
+
+    /// Re-points `selected_index` and `list_state` at the given path after
+    /// `music_files` has been replaced. A selected track that disappeared
+    /// falls back to the nearest valid index. Also recounts `library_total`.
+    pub fn reconcile_selection(&mut self, selected: Option<PathBuf>) {
+        self.library_total = LibraryTotal::of(&self.music_files);
+        if self.music_files.is_empty() {
+            self.selected_index = 0;
+            self.update_visible();
+            return;
+        }
+
+        let found = selected.and_then(|path| self.music_files.iter().position(|f| f.path == path));
+        self.selected_index = found.unwrap_or(self.selected_index.min(self.music_files.len() - 1));
+        self.update_visible();
+    }
+
+    /// Starts checking every listed file's header on a background thread and
+    /// drops the unplayable ones from the list once it finishes.
+    pub fn trim_to_playable(&mut self) {
+        if self.trim_rx.is_some() {
+            self.set_status("Already checking files");
+            return;
+        }
+        let paths = self.music_files.iter().map(|f| f.path.clone()).collect();
+        self.trim_unplayable.clear();
+        self.trim_rx = Some(trim::spawn(paths));
+        self.set_status("Checking files for playability...");
+    }
+
+    fn drain_trim_updates(&mut self) {
+        let Some(ref rx) = self.trim_rx else {
+            return;
+        };
+        let mut finished = false;
+        let mut progress = None;
+        loop {
+            match rx.try_recv() {
+                Ok(TrimUpdate::Checked { done, total }) => progress = Some((done, total)),
+                Ok(TrimUpdate::Unplayable(path)) => self.trim_unplayable.push(path),
+                Ok(TrimUpdate::Finished) => finished = true,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // The worker died without finishing; keep the list as it is
+                    log::warn!("Playability check stopped before finishing");
+                    self.trim_rx = None;
+                    self.trim_unplayable.clear();
+                    self.set_status("Checking files stopped unexpectedly");
+                    return;
+                }
+            }
+            if finished {
+                break;
+            }
+        }
+        if let Some((done, total)) = progress {
+            self.set_progress(format!("Checking files for playability... {}/{}", done, total));
+        }
+        if !finished {
+            return;
+        }
+
+        self.trim_rx = None;
+        let unplayable: HashSet<PathBuf> = self.trim_unplayable.drain(..).collect();
+        let selected = self.music_files.get(self.selected_index).map(|f| f.path.clone());
+        self.music_files.retain(|f| !unplayable.contains(&f.path));
+        self.reconcile_selection(selected);
+        self.set_status(format!("Removed {} unplayable files", unplayable.len()));
+    }
+
+    /// Gathers sizes and durations for every track in the background and
+    /// opens the stats dashboard once done.
+    pub fn show_library_stats(&mut self) {
+        if self.stats_rx.is_some() {
+            return;
+        }
+        self.set_status("Gathering library stats...");
+        self.stats_rx = Some(stats::spawn(self.music_files.clone()));
+    }
+
+    fn drain_stats_updates(&mut self) {
+        let Some(ref rx) = self.stats_rx else {
+            return;
+        };
+        let mut finished = None;
+        let mut progress = None;
+        while let Ok(update) = rx.try_recv() {
+            match update {
+                StatsUpdate::Checked { done, total } => progress = Some((done, total)),
+                StatsUpdate::Finished(stats) => finished = Some(stats),
+            }
+        }
+        if let Some((done, total)) = progress {
+            self.set_progress(format!("Gathering library stats... {}/{}", done, total));
+        }
+        let Some(stats) = finished else {
+            return;
+        };
+
+        self.stats_rx = None;
+        self.library_stats = Some(stats);
+        self.set_status("Library stats ready");
+        if self.mode == Mode::Normal {
+            self.mode = Mode::Stats;
+        }
+    }
+
+    /// Switches between following the system dark/light preference and the
+    /// theme fixed in the config.
+    pub fn toggle_follow_system_theme(&mut self) {
+        self.follow_system_theme = !self.follow_system_theme;
+        if self.follow_system_theme {
+            self.system_theme_rx = Some(theme::watch_system());
+            self.set_status("Theme follows the system dark/light setting");
+        } else {
+            self.system_theme_rx = None;
+            self.theme = self.base_theme;
+            self.set_status("Theme fixed to the configured colors");
+        }
+    }
+
+    fn drain_system_theme(&mut self) {
+        let Some(ref rx) = self.system_theme_rx else {
+            return;
+        };
+        if let Some(detected) = rx.try_iter().last() {
+            self.theme = detected.map_or(self.base_theme, Theme::named);
+        }
+    }
+
+    /// Switches to `theme`, which also becomes what turning off
+    /// system-following returns to.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.base_theme = theme;
+        self.monochrome = !theme::colors_supported() || theme.monochrome;
+    }
+
+    /// A handle for sending [`Command`]s to the player from other threads.
+    pub fn command_sender(&self) -> Sender<Command> {
+        self.command_tx.clone()
+    }
+
+    pub fn apply_command(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Play if self.current_playing.is_some() => self.resume(),
+            Command::Play => self.play_selected()?,
+            Command::Pause => self.pause(),
+            Command::Resume => self.resume(),
+            Command::TogglePause => self.toggle_pause(),
+            Command::Stop => self.stop(),
+            Command::Next => self.play_next()?,
+            Command::Previous => self.play_previous()?,
+            Command::Seek(position) => self.seek_to(position),
+            Command::SetVolume(percent) => self.set_volume_percent(percent),
+            Command::Status(reply) => {
+                let _ = reply.send(self.status_line());
+            }
+            Command::Suspend => {
+                if self.current_playing.is_some() && !self.is_paused {
+                    self.pause();
+                    self.suspended_playback = true;
+                    self.set_status("Paused for suspend/lock");
+                }
+            }
+            Command::Wake => {
+                if std::mem::take(&mut self.suspended_playback) && self.config.resume_on_wake {
+                    self.resume();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Pins the selected track so playback stops after it, or unpins it.
+    /// Marks the selected track as a favorite, or unmarks it, and saves the
+    /// change straight away. Works on the folder view's selection too.
+    pub fn toggle_favorite_selected(&mut self) {
+        let file = match self.browser {
+            Some(ref browser) => match browser.selected_entry() {
+                Some(BrowserEntry::Track(file)) => Some(file),
+                _ => None,
+            },
+            None => self.music_files.get(self.selected_index),
+        };
+        let Some((path, name)) = file.map(|file| (file.path.clone(), file.name.clone())) else {
+            return;
+        };
+        if self.favorites.remove(&path) {
+            self.set_status(format!("Removed {} from favorites", name));
+        } else {
+            self.favorites.insert(path);
+            self.set_status(format!("★ Added {} to favorites", name));
+        }
+        if self.favorites_only {
+            self.update_visible();
+        }
+        if let Err(e) = self.save_state() {
+            log::warn!("Could not save favorites: {}", e);
+        }
+    }
+
+    /// Asks for confirmation before [`App::trash_selected`].
+    pub fn request_trash_selected(&mut self) {
+        if self.browser.is_some() {
+            self.set_status("Close the folder browser to move a track to the trash");
+            return;
+        }
+        if !self.music_files.is_empty() {
+            self.mode = Mode::ConfirmTrash;
+        }
+    }
+
+    /// Moves the selected track to the system trash and drops it from the
+    /// list and the queue, stopping it first if it is playing.
+    pub fn trash_selected(&mut self) {
+        self.mode = Mode::Normal;
+        let Some(file) = self.music_files.get(self.selected_index) else {
+            return;
+        };
+        let (path, name) = (file.path.clone(), file.name.clone());
+        // Let go of the file before it moves
+        if self.current_playing.as_ref() == Some(&path) {
+            self.stop();
+        }
+        if self.audio_player.preloaded() == Some(path.as_path()) {
+            self.audio_player.discard_preload();
+        }
+        if let Err(e) = trash::delete(&path) {
+            log::warn!("Could not move {} to the trash: {}", path.display(), e);
+            self.set_status(format!("Could not move {} to the trash: {}", name, e));
+            return;
+        }
+        log::info!("Moved {} to the trash", path.display());
+
+        self.music_files.remove(self.selected_index);
+        self.queue.retain(|queued| *queued != path);
+        if self.stop_after.as_ref() == Some(&path) {
+            self.stop_after = None;
+        }
+        if self.favorites.remove(&path) {
+            if let Err(e) = self.save_state() {
+                log::warn!("Could not save favorites: {}", e);
+            }
+        }
+        // The same index now holds the track after the removed one
+        self.reconcile_selection(None);
+        self.set_status(format!("Moved {} to the trash", name));
+    }
+
+    /// Switches the list between every track and favorites only. The filter
+    /// works on the flat list, so the folder view is closed.
+    pub fn toggle_favorites_only(&mut self) {
+        self.favorites_only = !self.favorites_only;
+        self.browser = None;
+        self.update_visible();
+        self.set_status(if !self.favorites_only {
+            String::from("Showing all tracks")
+        } else if self.favorites.is_empty() {
+            String::from("No favorites yet - Press '*' on a track to add it")
+        } else {
+            format!("Showing {} favorite{}", self.visible.len(), if self.visible.len() == 1 { "" } else { "s" })
+        });
+    }
+
+    pub fn toggle_stop_after_selected(&mut self) {
+        let Some(file) = self.music_files.get(self.selected_index) else {
+            return;
+        };
+        if self.stop_after.as_ref() == Some(&file.path) {
+            self.stop_after = None;
+            self.set_status(format!("Unpinned {}", file.name));
+        } else {
+            self.stop_after = Some(file.path.clone());
+            self.set_status(format!("Will stop after {}", file.name));
+        }
+    }
+
+    /// Pins the playing track like [`App::toggle_stop_after_selected`], so
+    /// playback stops once it ends whatever the repeat and shuffle modes.
+    /// The pin clears itself when it fires; pressing again cancels it.
+    pub fn toggle_stop_after_current(&mut self) {
+        let Some(file) = self.playing_file() else {
+            self.set_status("Nothing is playing");
+            return;
+        };
+        let (path, name) = (file.path.clone(), file.name.clone());
+        if self.stop_after.as_ref() == Some(&path) {
+            self.stop_after = None;
+            self.set_status(format!("Will carry on after {}", name));
+        } else {
+            self.stop_after = Some(path);
+            self.set_status(format!("Will stop after {}", name));
+        }
+    }
+
+    /// Steps the sleep timer through 15, 30 and 60 minutes and then off,
+    /// each time counting from now.
+    pub fn cycle_sleep_timer(&mut self) {
+        let next = match self.sleep_timer_minutes {
+            None => Some(SLEEP_TIMER_MINUTES[0]),
+            Some(minutes) => SLEEP_TIMER_MINUTES.iter().copied().find(|&preset| preset > minutes),
+        };
+        self.sleep_timer_minutes = next;
+        self.sleep_timer = next.map(|minutes| Instant::now() + Duration::from_secs(minutes * 60));
+        self.set_status(match next {
+            Some(minutes) => format!("Sleep timer: stopping in {} min", minutes),
+            None => String::from("Sleep timer off"),
+        });
+    }
+
+    /// Time left on the sleep timer.
+    pub fn sleep_remaining(&self) -> Option<Duration> {
+        self.sleep_timer.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Stops playback (fading out if fades are on) once the sleep timer runs
+    /// out, and asks to quit when `sleep_timer_quit` is set.
+    /// With `pause_on_underrun`, holds playback while the decoder has
+    /// fallen behind and lets it go again once `rebuffer_ms` is ready. A
+    /// pause made in the meantime is kept.
+    fn check_underrun(&mut self) {
+        if self.rebuffering {
+            if self.audio_player.is_buffered(Duration::from_millis(self.config.rebuffer_ms)) {
+                self.rebuffering = false;
+                if !self.is_paused {
+                    log::info!("Rebuffered, resuming playback");
+                    self.audio_player.resume();
+                    if let Some(name) = self.playing_name() {
+                        self.set_status(format!("♪ Playing: {}", name));
+                    }
+                }
+            }
+        } else if self.current_playing.is_some() && !self.is_paused && self.audio_player.take_underrun() {
+            log::warn!("Playback ran dry, pausing to rebuffer");
+            self.audio_player.pause();
+            self.rebuffering = true;
+            self.set_status("Playback ran dry - buffering...");
+        }
+    }
+
+    fn check_sleep_timer(&mut self) {
+        if self.sleep_timer.is_none_or(|deadline| Instant::now() < deadline) {
+            return;
+        }
+        self.sleep_timer = None;
+        self.sleep_timer_minutes = None;
+        log::info!("Sleep timer ran out");
+        self.stop();
+        self.set_status("Sleep timer: playback stopped");
+        if self.config.sleep_timer_quit {
+            self.quit_requested = true;
+        }
+    }
+
+    /// Marks the current position as the start of the A-B loop.
+    pub fn set_loop_a(&mut self) {
+        self.set_loop_mark(true);
+    }
+
+    /// Marks the current position as the end of the A-B loop.
+    pub fn set_loop_b(&mut self) {
+        self.set_loop_mark(false);
+    }
+
+    fn set_loop_mark(&mut self, start: bool) {
+        if self.current_playing.is_none() {
+            self.set_status("Nothing playing to loop");
+            return;
+        }
+        let position = self.audio_player.position();
+        let (mut a, mut b) = if start {
+            (Some(position), self.loop_b)
+        } else {
+            (self.loop_a, Some(position))
+        };
+        if let (Some(start), Some(end)) = (a, b) {
+            // Marks set the wrong way round still make a loop
+            if end < start {
+                std::mem::swap(&mut a, &mut b);
+            }
+            if start.abs_diff(end) < MIN_LOOP {
+                self.set_status("Loop marks need to be at least a second apart");
+                return;
+            }
+        }
+        self.loop_a = a;
+        self.loop_b = b;
+        let format = |mark: Option<Duration>| {
+            mark.map_or_else(|| String::from("?"), |mark| template::format_duration(mark, self.config.time_format))
+        };
+        self.set_status(format!("Loop: A {} - B {}", format(self.loop_a), format(self.loop_b)));
+    }
+
+    pub fn clear_loop(&mut self) {
+        if self.loop_a.take().is_some() | self.loop_b.take().is_some() {
+            self.set_status("Loop cleared");
+        }
+    }
+
+    /// Jumps back to A once playback passes B. Without an A mark the loop
+    /// runs from the start of the track.
+    fn repeat_loop(&mut self) {
+        let Some(end) = self.loop_b else {
+            return;
+        };
+        if self.audio_player.position() < end {
+            return;
+        }
+        let start = self.loop_a.unwrap_or_default();
+        match self.audio_player.seek(start) {
+            Ok(()) => self.events.emit(PlayerEvent::Seeked(start)),
+            Err(e) => {
+                log::warn!("A-B loop seek failed: {}", e);
+                self.loop_a = None;
+                self.loop_b = None;
+                self.set_status(format!("Can't loop this file: {}", e));
+            }
+        }
+    }
+
+    /// The track auto-advance stops after, if it is still in the list.
+    pub fn stop_after_file(&self) -> Option<&MusicFile> {
+        let pinned = self.stop_after.as_ref()?;
+        self.music_files.iter().find(|f| &f.path == pinned)
+    }
+
+    /// Per-frame housekeeping a host loop should call between draws: applies
+    /// queued commands and advances to the next track once the current one
+    /// has finished.
+    pub fn tick(&mut self) -> Result<()> {
+        while let Ok(command) = self.command_rx.try_recv() {
+            self.apply_command(command)?;
+        }
+        self.drain_trim_updates();
+        self.drain_scan_updates();
+        self.drain_library_changes();
+        self.drain_stats_updates();
+        self.drain_album_art();
+        self.drain_audible_end();
+        if let Some(warning) = self.audio_player.take_output_warning() {
+            self.set_status(warning);
+        }
+        self.check_limiter();
+        self.audio_player.release_idle_output();
+        self.drain_system_theme();
+        self.check_sleep_timer();
+        self.check_underrun();
+
+        if self.current_playing.is_some() {
+            if !self.is_paused {
+                self.repeat_loop();
+            }
+            self.follow_preloaded();
+            self.preload_upcoming();
+            self.start_crossfade()?;
+        }
+        let fell_silent = self.audible_end.is_some_and(|end| self.audio_player.position() >= end);
+        if self.current_playing.is_some()
+            && !self.is_paused
+            && !self.rebuffering
+            && track_ended(self.playback_started_at, Instant::now(), self.audio_player.is_empty(), fell_silent)
+        {
+            let finished = self.playing_file().map(|f| f.path.clone());
+            if finished.is_some() && finished == self.stop_after {
+                let name = self.playing_name().unwrap_or_default();
+                log::info!("Finished pinned track {}, stopping", name);
+                self.stop_after = None;
+                self.stop();
+                self.set_status(format!("Stopped after pinned track {}", name));
+            } else {
+                self.auto_advance()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves on after a track ends, waiting out `inter_track_delay_secs` first.
+    fn auto_advance(&mut self) -> Result<()> {
+        let delay = Duration::from_secs(self.config.inter_track_delay_secs);
+        let deadline = *self.advance_at.get_or_insert_with(|| Instant::now() + delay);
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !remaining.is_zero() {
+            self.set_progress(format!("Next in {}s", remaining.as_secs() + 1));
+            return Ok(());
+        }
+
+        match self.repeat {
+            RepeatMode::One => {
+                log::info!("Track finished, repeating it");
+                match self.playing_index() {
+                    Some(index) => self.play_index(index),
+                    None => self.play_next(),
+                }
+            }
+            RepeatMode::Off if self.at_end() && self.queue.is_empty() => {
+                log::info!("Reached the end of the list, stopping");
+                self.stop();
+                self.set_status("Reached the end of the list");
+                Ok(())
+            }
+            RepeatMode::Off | RepeatMode::All => {
+                log::info!("Track finished, advancing");
+                self.set_status("Auto-advancing to next song...");
+                self.play_next()
+            }
+        }
+    }
+}
+
+/// Where [`find_playable`] landed.
+#[derive(Debug, PartialEq, Eq)]
+enum Landing {
+    /// `open` took the track at `index` after `skipped` others.
+    Played { index: usize, skipped: usize },
+    /// Halted at `index` by `on_bad_file = "stop"`, on a flagged track or
+    /// one that failed to open.
+    Stopped(usize),
+    /// Ran off the end of the list after `skipped` unplayable tracks.
+    End { skipped: usize },
+    /// No track could be played.
+    Exhausted,
+}
+
+/// Calls `open` on `paths[start]`, then on each track after it (before it
+/// when not `forward`) until one opens, passing over `flagged` tracks as
+/// `on_bad_file` says. Steps past either end as [`step_index`] does for
+/// `repeat`.
+fn find_playable(
+    paths: &[PathBuf],
+    start: usize,
+    forward: bool,
+    repeat: RepeatMode,
+    flagged: &HashSet<PathBuf>,
+    on_bad_file: BadFileAction,
+    mut open: impl FnMut(&Path) -> Result<()>,
+) -> Landing {
+    let len = paths.len();
+    let mut index = start;
+    // Bounded by the list length so an all-bad list can't loop forever
+    for skipped in 0..len {
+        let path = &paths[index];
+        let is_flagged = flagged.contains(path);
+        if is_flagged && on_bad_file == BadFileAction::Stop {
+            return Landing::Stopped(index);
+        }
+        if !is_flagged || on_bad_file == BadFileAction::Play {
+            match open(path) {
+                Ok(()) => return Landing::Played { index, skipped },
+                Err(_) if on_bad_file == BadFileAction::Stop => return Landing::Stopped(index),
+                Err(e) => log::debug!("Skipping {}: {}", path.display(), e),
+            }
+        } else {
+            log::debug!("Skipping flagged file {}", path.display());
+        }
+        match step_index(index, len, repeat, forward) {
+            Some(next) => index = next,
+            None => return Landing::End { skipped: skipped + 1 },
+        }
+    }
+    Landing::Exhausted
+}
+
+/// Whether the playing track is over: it reached its trailing silence, or
+/// the sink ran dry after the [`START_GRACE`] since `started_at`.
+fn track_ended(started_at: Option<Instant>, now: Instant, is_empty: bool, fell_silent: bool) -> bool {
+    let settled = started_at.is_none_or(|at| now.saturating_duration_since(at) >= START_GRACE);
+    fell_silent || (settled && is_empty)
+}
+
+/// How likely shuffle is to pick a track, relative to others. `recent` is
+/// the track's place in the play history, 0 for the last one played.
+fn shuffle_weight(weighting: ShuffleWeighting, favorite: bool, recent: Option<usize>) -> f64 {
+    // From near 0 for the track just played up to 1 for one not in the history
+    let staleness = recent.map_or(1.0, |i| (i + 1) as f64 / (HISTORY_LEN + 1) as f64);
+    match weighting {
+        ShuffleWeighting::Uniform => 1.0,
+        ShuffleWeighting::Balanced => staleness.sqrt() * if favorite { 2.0 } else { 1.0 },
+        ShuffleWeighting::Favorites => staleness.sqrt() * if favorite { 8.0 } else { 1.0 },
+        ShuffleWeighting::Discover => staleness * staleness * if favorite { 0.25 } else { 1.0 },
+    }
+}
+
+/// The track one step from `current` in a list of `len`, or `None` past
+/// either end. Only [`RepeatMode::All`] wraps around; a manual step moves on
+/// under [`RepeatMode::One`] like under Off.
+fn step_index(current: usize, len: usize, repeat: RepeatMode, forward: bool) -> Option<usize> {
+    let last = len.checked_sub(1)?;
+    match (forward, repeat) {
+        (true, _) if current < last => Some(current + 1),
+        (true, RepeatMode::All) => Some(0),
+        (false, _) if current > 0 => Some(current - 1),
+        (false, RepeatMode::All) => Some(last),
+        _ => None,
+    }
+}
+
+/// `path` resolved to an absolute path where possible.
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Snaps a volume (or speed) to whole percent so repeated steps don't drift.
+fn round_volume(volume: f32) -> f32 {
+    (volume * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(path: &str) -> MusicFile {
+        let path = PathBuf::from(path);
+        MusicFile {
+            name: path.file_name().unwrap().to_string_lossy().to_string(),
+            path,
+            artist: None,
+            album: None,
+            title: None,
+            track_number: None,
+            duration: None,
+            replay_gain: None,
+        }
+    }
+
+    /// An app over `files` that scans nothing, starts no integrations and
+    /// ignores the saved state.
+    fn app_with(files: Vec<MusicFile>) -> App {
+        app_from(Library::Files(files), State::default())
+    }
+
+    fn app_from(library: Library, state: State) -> App {
+        let config = Config {
+            mpris: false,
+            pause_on_suspend: false,
+            prewarm_audio: false,
+            ..Config::default()
+        };
+        App::build_with_state(vec![PathBuf::from("/music")], library, config, state).unwrap()
+    }
+
+    #[test]
+    fn playing_track_is_matched_by_path_not_name() {
+        let mut app = app_with(vec![track("/music/a/track.mp3"), track("/music/b/track.mp3")]);
+        app.current_playing = Some(PathBuf::from("/music/b/track.mp3"));
+        assert_eq!(app.playing_index(), Some(1));
+        assert!(!app.is_playing(&app.music_files[0]));
+        assert!(app.is_playing(&app.music_files[1]));
+    }
+
+    #[test]
+    fn range_is_applied_again_after_a_rescan() {
+        let files: Vec<MusicFile> = (1..=5).map(|n| track(&format!("/music/{}.mp3", n))).collect();
+        let mut app = app_with(files.clone());
+        app.restrict_to_range(2, 3);
+        let names = |app: &App| app.music_files.iter().map(|f| f.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&app), ["2.mp3", "3.mp3"]);
+
+        let (tx, rx) = mpsc::channel();
+        tx.send(ScanUpdate::Finished(files, ScanStats::default())).unwrap();
+        app.scan_rx = Some(rx);
+        app.scanning = true;
+        app.drain_scan_updates();
+        assert_eq!(names(&app), ["2.mp3", "3.mp3"]);
+    }
+
+    #[test]
+    fn enter_plays_nothing_when_the_filter_matches_nothing() {
+        let mut app = app_with(vec![track("/music/a.mp3"), track("/music/b.mp3")]);
+        app.search_query = String::from("no such track");
+        app.update_visible();
+        assert!(app.visible.is_empty());
+        app.mode = Mode::Normal;
+        app.play_selected().unwrap();
+        // An attempt would have flagged the missing file as unplayable
+        assert!(app.bad_files.is_empty());
+        assert_eq!(app.current_playing, None);
+    }
+
+    #[test]
+    fn refresh_rereads_the_playlist_instead_of_scanning() {
+        let dir = std::env::temp_dir().join(format!("oxiplayer-playlist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a.wav", "b.wav", "c.wav"] {
+            std::fs::write(dir.join(name), audio::tests::wav()).unwrap();
+        }
+        let m3u = dir.join("list.m3u");
+        std::fs::write(&m3u, "b.wav\na.wav\n").unwrap();
+        let mut app = app_from(Library::Playlist(m3u.clone()), State::default());
+        assert_eq!(app.music_files.len(), 2);
+
+        std::fs::write(&m3u, "b.wav\na.wav\nb.wav\n").unwrap();
+        app.refresh_files().unwrap();
+        assert!(app.scan_rx.is_none());
+        let names: Vec<_> = app.music_files.iter().map(|f| f.path.file_name().unwrap()).collect();
+        assert_eq!(names, ["b.wav", "a.wav", "b.wav"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn status_log_keeps_every_message_but_not_progress() {
+        let mut app = app_with(vec![track("/music/a.mp3")]);
+        let before = app.status_log.len();
+        app.set_status("first");
+        app.set_status("second");
+        app.set_status("second");
+        for done in 1..=50 {
+            app.set_progress(format!("Checking files for playability... {}/50", done));
+        }
+        let logged: Vec<_> = app.status_log.iter().skip(before).map(|(_, message)| message.as_str()).collect();
+        assert_eq!(logged, ["first", "second"]);
+        assert_eq!(app.status_message, "Checking files for playability... 50/50");
+    }
+
+    #[test]
+    fn removing_the_last_queue_entry_moves_the_selection_up() {
+        let mut app = app_with(vec![track("/music/a.mp3"), track("/music/b.mp3")]);
+        app.queue.extend([PathBuf::from("/music/a.mp3"), PathBuf::from("/music/b.mp3")]);
+        app.toggle_queue_panel();
+        app.remove_from_queue(1);
+        assert_eq!(app.queue, [PathBuf::from("/music/a.mp3")]);
+        assert_eq!(app.mode, Mode::Queue { selected: 0 });
+        app.remove_from_queue(0);
+        assert!(app.queue.is_empty());
+        app.remove_from_queue(0);
+        assert_eq!(app.mode, Mode::Queue { selected: 0 });
+    }
+
+    #[test]
+    fn progress_bar_drag_previews_and_needs_a_known_length() {
+        let mut app = app_with(vec![track("/music/a.mp3")]);
+        app.drag_seek(0.5);
+        assert_eq!(app.seek_drag, None);
+
+        app.current_playing = Some(PathBuf::from("/music/a.mp3"));
+        app.drag_seek(0.5);
+        assert_eq!(app.seek_drag, None);
+
+        app.current_duration = Some(Duration::from_secs(100));
+        app.drag_seek(0.25);
+        assert_eq!(app.seek_drag, Some(Duration::from_secs(25)));
+        app.drag_seek(1.5);
+        assert_eq!(app.seek_drag, Some(Duration::from_secs(100)));
+        app.finish_seek_drag();
+        assert_eq!(app.seek_drag, None);
+    }
+
+    #[test]
+    fn shuffle_weights_follow_the_preset() {
+        for weighting in [ShuffleWeighting::Balanced, ShuffleWeighting::Favorites, ShuffleWeighting::Discover] {
+            // Lately played tracks are always less likely
+            assert!(shuffle_weight(weighting, false, Some(0)) < shuffle_weight(weighting, false, Some(20)));
+            assert!(shuffle_weight(weighting, false, Some(20)) < shuffle_weight(weighting, false, None));
+            assert!(shuffle_weight(weighting, false, Some(0)) > 0.0);
+        }
+        assert!(shuffle_weight(ShuffleWeighting::Favorites, true, None) > shuffle_weight(ShuffleWeighting::Favorites, false, None));
+        assert!(shuffle_weight(ShuffleWeighting::Discover, true, None) < shuffle_weight(ShuffleWeighting::Discover, false, None));
+        assert_eq!(shuffle_weight(ShuffleWeighting::Uniform, true, Some(0)), shuffle_weight(ShuffleWeighting::Uniform, false, None));
+    }
+
+    #[test]
+    fn loading_the_saved_playlist_asks_then_replaces_or_appends() {
+        let dir = std::env::temp_dir().join(format!("oxiplayer-load-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a.wav", "b.wav"] {
+            std::fs::write(dir.join(name), audio::tests::wav()).unwrap();
+        }
+        std::fs::write(dir.join("playlist.m3u"), "b.wav\na.wav\n").unwrap();
+        let mut app = app_with(vec![MusicFile::from_path(dir.join("a.wav")), track("/music/c.mp3")]);
+        app.music_roots = vec![dir.clone()];
+
+        app.load_saved_playlist();
+        assert_eq!(app.mode, Mode::ConfirmPlaylist);
+        app.cancel_pending_playlist();
+        assert_eq!(app.music_files.len(), 2);
+
+        app.load_saved_playlist();
+        app.apply_pending_playlist(true);
+        let names: Vec<_> = app.music_files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a.wav", "c.mp3", "b.wav"]);
+        assert_eq!(app.playlist, None);
+
+        app.load_saved_playlist();
+        app.apply_pending_playlist(false);
+        let names: Vec<_> = app.music_files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["b.wav", "a.wav"]);
+        assert_eq!(app.playlist, Some(dir.join("playlist.m3u")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_track_crossfade_override_replaces_the_default() {
+        let mut app = app_with(vec![track("/music/a.mp3"), track("/music/b.mp3")]);
+        app.config.crossfade_ms = 3000;
+        app.current_playing = Some(PathBuf::from("/music/a.mp3"));
+        assert_eq!(app.crossfade_out(), Duration::from_secs(3));
+
+        app.crossfade_overrides.insert(PathBuf::from("/music/a.mp3"), 0);
+        assert_eq!(app.crossfade_out(), Duration::ZERO);
+        app.current_duration = Some(Duration::from_secs(60));
+        assert!(!app.crossfades());
+
+        app.current_playing = Some(PathBuf::from("/music/b.mp3"));
+        assert!(app.crossfades());
+    }
+
+    #[test]
+    fn rebuffering_keeps_a_pause_made_in_the_meantime() {
+        let mut app = app_with(vec![track("/music/a.mp3")]);
+        app.current_playing = Some(PathBuf::from("/music/a.mp3"));
+        app.rebuffering = true;
+        app.is_paused = true;
+        // Nothing decoded ahead counts as buffered, so the hold is let go
+        app.check_underrun();
+        assert!(!app.rebuffering);
+        assert!(app.is_paused);
+        assert!(!app.audio_player.take_underrun());
+    }
+
+    #[test]
+    fn a_dead_trim_worker_is_let_go() {
+        let mut app = app_with(vec![track("/music/a.mp3")]);
+        let (tx, rx) = mpsc::channel();
+        tx.send(TrimUpdate::Checked { done: 1, total: 2 }).unwrap();
+        drop(tx);
+        app.trim_rx = Some(rx);
+        app.drain_trim_updates();
+        assert!(app.trim_rx.is_none());
+        assert_eq!(app.music_files.len(), 1);
+    }
+
+    #[test]
+    fn saved_tick_rate_is_clamped() {
+        let build = |tick_rate_ms| app_from(Library::Empty, State { tick_rate_ms, ..State::default() });
+        assert_eq!(build(Some(0)).tick_rate, MIN_TICK_RATE);
+        assert_eq!(build(Some(60_000)).tick_rate, MAX_TICK_RATE);
+        assert_eq!(build(Some(100)).tick_rate, Duration::from_millis(100));
+        assert_eq!(build(None).tick_rate, DEFAULT_TICK_RATE);
+    }
+
+    #[test]
+    fn gauge_length_falls_back_to_the_tagged_duration() {
+        let mut file = track("/music/a/track.mp3");
+        file.duration = Some(Duration::from_secs(200));
+        let mut app = app_with(vec![file]);
+        // No track is open, so the player knows no length, like most decoders
+        assert_eq!(app.audio_player.duration(), None);
+        app.track_started(0);
+        assert_eq!(app.current_duration, Some(Duration::from_secs(200)));
+    }
+
+    #[test]
+    fn track_gains_stay_in_the_track_and_within_the_volume() {
+        let mut loud = track("/music/loud.mp3");
+        loud.replay_gain = Some(0.5);
+        let mut quiet = track("/music/quiet.mp3");
+        quiet.replay_gain = Some(2.0);
+        let mut app = app_with(vec![loud, quiet]);
+        app.normalize = true;
+        app.volume = 0.8;
+        assert_eq!(app.output_volume(), 1.0);
+        assert_eq!(app.gain_for(Some(&app.music_files[0])), 0.4);
+        assert_eq!(app.gain_for(Some(&app.music_files[1])), 1.0);
+        // Above 100% the sink carries the extra and the gains stay below it
+        app.volume = 1.5;
+        assert_eq!(app.output_volume(), 1.5);
+        assert_eq!(app.gain_for(Some(&app.music_files[0])), 0.5);
+        assert_eq!(app.gain_for(Some(&app.music_files[1])), 1.0);
+        app.muted = true;
+        assert_eq!(app.output_volume(), 0.0);
+    }
+
+    /// Opens `path` with a decoder, as playing it would.
+    fn decode(path: &Path) -> Result<()> {
+        rodio::Decoder::new(std::io::BufReader::new(std::fs::File::open(path)?))?;
+        Ok(())
+    }
+
+    #[test]
+    fn find_playable_skips_a_file_no_decoder_takes() {
+        let dir = std::env::temp_dir().join(format!("oxiplayer-playable-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = vec![dir.join("a.wav"), dir.join("b.mp3"), dir.join("c.wav")];
+        std::fs::write(&paths[0], audio::tests::wav()).unwrap();
+        std::fs::write(&paths[1], "not audio at all").unwrap();
+        std::fs::write(&paths[2], audio::tests::wav()).unwrap();
+        let none = HashSet::new();
+
+        let landing = find_playable(&paths, 1, true, RepeatMode::All, &none, BadFileAction::Skip, decode);
+        assert_eq!(landing, Landing::Played { index: 2, skipped: 1 });
+        let landing = find_playable(&paths, 1, false, RepeatMode::All, &none, BadFileAction::Skip, decode);
+        assert_eq!(landing, Landing::Played { index: 0, skipped: 1 });
+        let landing = find_playable(&paths, 1, true, RepeatMode::All, &none, BadFileAction::Stop, decode);
+        assert_eq!(landing, Landing::Stopped(1));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn find_playable_stops_at_an_unplayable_last_track_without_repeat() {
+        let dir = std::env::temp_dir().join(format!("oxiplayer-last-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = vec![dir.join("a.wav"), dir.join("b.wav"), dir.join("c.mp3")];
+        std::fs::write(&paths[0], audio::tests::wav()).unwrap();
+        std::fs::write(&paths[1], audio::tests::wav()).unwrap();
+        std::fs::write(&paths[2], "not audio at all").unwrap();
+        let none = HashSet::new();
+
+        for repeat in [RepeatMode::Off, RepeatMode::One] {
+            let landing = find_playable(&paths, 2, true, repeat, &none, BadFileAction::Skip, decode);
+            assert_eq!(landing, Landing::End { skipped: 1 });
+        }
+        let landing = find_playable(&paths, 2, true, RepeatMode::All, &none, BadFileAction::Skip, decode);
+        assert_eq!(landing, Landing::Played { index: 0, skipped: 1 });
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn find_playable_follows_on_bad_file_for_flagged_tracks() {
+        let paths: Vec<PathBuf> = ["a.mp3", "b.mp3", "c.mp3"].iter().map(PathBuf::from).collect();
+        let flagged: HashSet<PathBuf> = [paths[0].clone()].into();
+        let open = |_: &Path| Ok(());
+        let landing = find_playable(&paths, 0, true, RepeatMode::All, &flagged, BadFileAction::Skip, open);
+        assert_eq!(landing, Landing::Played { index: 1, skipped: 1 });
+        let landing = find_playable(&paths, 0, true, RepeatMode::All, &flagged, BadFileAction::Stop, open);
+        assert_eq!(landing, Landing::Stopped(0));
+        let landing = find_playable(&paths, 0, true, RepeatMode::All, &flagged, BadFileAction::Play, open);
+        assert_eq!(landing, Landing::Played { index: 0, skipped: 0 });
+        // A flagged track at either end doesn't lead round to the other without repeat All
+        let landing = find_playable(&paths, 0, false, RepeatMode::Off, &flagged, BadFileAction::Skip, open);
+        assert_eq!(landing, Landing::End { skipped: 1 });
+        let landing = find_playable(&paths, 0, false, RepeatMode::All, &flagged, BadFileAction::Skip, open);
+        assert_eq!(landing, Landing::Played { index: 2, skipped: 1 });
+        let last: HashSet<PathBuf> = [paths[2].clone()].into();
+        let landing = find_playable(&paths, 2, true, RepeatMode::Off, &last, BadFileAction::Skip, open);
+        assert_eq!(landing, Landing::End { skipped: 1 });
+        let fail = |_: &Path| Err(anyhow::anyhow!("no decoder"));
+        assert_eq!(find_playable(&paths, 2, false, RepeatMode::All, &flagged, BadFileAction::Skip, fail), Landing::Exhausted);
+    }
+
+    #[test]
+    fn empty_sink_only_ends_the_track_after_the_grace_period() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        assert!(!track_ended(Some(start), at(0), true, false));
+        assert!(!track_ended(Some(start), at(299), true, false));
+        assert!(track_ended(Some(start), at(300), true, false));
+        assert!(!track_ended(Some(start), at(300), false, false));
+        assert!(track_ended(None, at(0), true, false));
+    }
+
+    #[test]
+    fn trailing_silence_ends_the_track_at_once() {
+        let start = Instant::now();
+        assert!(track_ended(Some(start), start, false, true));
+    }
+
+    #[test]
+    fn step_index_stops_at_the_ends_without_repeat_all() {
+        for repeat in [RepeatMode::Off, RepeatMode::One] {
+            assert_eq!(step_index(0, 3, repeat, true), Some(1));
+            assert_eq!(step_index(2, 3, repeat, true), None);
+            assert_eq!(step_index(0, 3, repeat, false), None);
+            assert_eq!(step_index(2, 3, repeat, false), Some(1));
+        }
+    }
+
+    #[test]
+    fn step_index_wraps_with_repeat_all() {
+        assert_eq!(step_index(0, 3, RepeatMode::All, true), Some(1));
+        assert_eq!(step_index(2, 3, RepeatMode::All, true), Some(0));
+        assert_eq!(step_index(0, 3, RepeatMode::All, false), Some(2));
+        assert_eq!(step_index(2, 3, RepeatMode::All, false), Some(1));
+    }
+
+    #[test]
+    fn step_index_handles_tiny_lists() {
+        assert_eq!(step_index(0, 0, RepeatMode::All, true), None);
+        assert_eq!(step_index(0, 1, RepeatMode::Off, true), None);
+        assert_eq!(step_index(0, 1, RepeatMode::All, true), Some(0));
+        assert_eq!(step_index(0, 1, RepeatMode::All, false), Some(0));
+    }
+}
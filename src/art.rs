@@ -0,0 +1,106 @@
+//! Embedded cover art, drawn with half-block characters so each cell shows
+//! two pixels.
+
+use image::imageops::{self, FilterType};
+use image::RgbImage;
+use lofty::picture::PictureType;
+use lofty::prelude::*;
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Covers are shrunk to this many pixels on the longer side when loaded;
+/// the panel never needs more.
+const MAX_SIDE: u32 = 128;
+
+/// Cells drawn for a `(width, height)` area.
+type Rendered = ((u16, u16), Vec<Line<'static>>);
+
+/// The cover of one track, with the cells last drawn from it.
+pub struct AlbumArt {
+    pub path: PathBuf,
+    /// `None` when the file has no usable picture.
+    pub image: Option<RgbImage>,
+    /// Lines for the last size asked for, so a frame of the same size
+    /// doesn't scale the image again.
+    rendered: RefCell<Option<Rendered>>,
+}
+
+impl AlbumArt {
+    /// The cover scaled to fit `width` x `height` cells, centred
+    /// horizontally. Empty when there is no cover.
+    pub fn lines(&self, width: u16, height: u16) -> Vec<Line<'static>> {
+        let Some(ref image) = self.image else {
+            return Vec::new();
+        };
+        let mut rendered = self.rendered.borrow_mut();
+        match *rendered {
+            Some((size, ref lines)) if size == (width, height) => lines.clone(),
+            _ => {
+                let lines = render(image, width, height);
+                *rendered = Some(((width, height), lines.clone()));
+                lines
+            }
+        }
+    }
+}
+
+/// Reads and decodes the cover of `path` on a background thread.
+pub fn spawn_load(path: PathBuf) -> Receiver<AlbumArt> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let image = load(&path);
+        let _ = tx.send(AlbumArt { path, image, rendered: RefCell::new(None) });
+    });
+    rx
+}
+
+/// The front cover, or failing that the first picture in any tag.
+fn load(path: &Path) -> Option<RgbImage> {
+    let tagged = lofty::read_from_path(path)
+        .inspect_err(|e| log::debug!("No tags read from {}: {}", path.display(), e))
+        .ok()?;
+    let pictures = || tagged.tags().iter().flat_map(|tag| tag.pictures());
+    let picture = pictures()
+        .find(|picture| picture.pic_type() == PictureType::CoverFront)
+        .or_else(|| pictures().next())?;
+    let image = image::load_from_memory(picture.data())
+        .inspect_err(|e| log::debug!("Could not decode the cover of {}: {}", path.display(), e))
+        .ok()?;
+    Some(image.thumbnail(MAX_SIDE, MAX_SIDE).to_rgb8())
+}
+
+/// Each cell is an upper half block: the foreground is the top pixel and
+/// the background the one below it.
+fn render(image: &RgbImage, width: u16, height: u16) -> Vec<Line<'static>> {
+    let (columns, pixel_rows) = (width as u32, height as u32 * 2);
+    if columns == 0 || pixel_rows == 0 || image.width() == 0 || image.height() == 0 {
+        return Vec::new();
+    }
+    let scale = (columns as f32 / image.width() as f32).min(pixel_rows as f32 / image.height() as f32);
+    let scaled_width = ((image.width() as f32 * scale) as u32).clamp(1, columns);
+    let scaled_height = ((image.height() as f32 * scale) as u32).clamp(1, pixel_rows);
+    let scaled = imageops::resize(image, scaled_width, scaled_height, FilterType::Triangle);
+    let indent = " ".repeat(((columns - scaled_width) / 2) as usize);
+
+    let color = |x: u32, y: u32| {
+        let [r, g, b] = scaled.get_pixel(x, y).0;
+        Color::Rgb(r, g, b)
+    };
+    (0..scaled_height.div_ceil(2))
+        .map(|row| {
+            let mut spans = vec![Span::raw(indent.clone())];
+            spans.extend((0..scaled_width).map(|x| {
+                let mut style = Style::default().fg(color(x, row * 2));
+                if row * 2 + 1 < scaled_height {
+                    style = style.bg(color(x, row * 2 + 1));
+                }
+                Span::styled("▀", style)
+            }));
+            Line::from(spans)
+        })
+        .collect()
+}
@@ -1,41 +1,651 @@
-use anyhow::Result;
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use crate::eq::{EqSettings, Equalizer, Gains};
+use crate::limiter::{Limiter, LimiterSettings};
+use crate::sources::{Buffer, Gain, Prefetch, Progress, SampleTap, Scaled, Tapped, Tracked};
+use anyhow::{anyhow, Result};
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::cpal::{Device, SampleRate, SupportedStreamConfig};
+use rodio::decoder::{DecoderError, Mp4Type};
+use rodio::source::{SeekError, Zero};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
 use std::fs::File;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type FileDecoder = Decoder<BufReader<File>>;
+
+/// A decoder backend that can be forced when automatic format detection fails.
+struct DecoderBackend {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    open: fn(BufReader<File>) -> Result<FileDecoder, DecoderError>,
+}
+
+// Tried in order after `Decoder::new`; backends matching the file's
+// extension are moved to the front of the chain.
+const FALLBACK_DECODERS: &[DecoderBackend] = &[
+    DecoderBackend { name: "symphonia-mp3", extensions: &["mp3"], open: Decoder::new_mp3 },
+    DecoderBackend { name: "hound", extensions: &["wav"], open: Decoder::new_wav },
+    DecoderBackend { name: "symphonia-flac", extensions: &["flac"], open: Decoder::new_flac },
+    DecoderBackend { name: "symphonia-vorbis", extensions: &["ogg"], open: Decoder::new_vorbis },
+    DecoderBackend { name: "symphonia-aac", extensions: &["aac"], open: Decoder::new_aac },
+    DecoderBackend {
+        name: "symphonia-isomp4",
+        extensions: &["m4a", "mp4", "m4v", "mov"],
+        open: |data| Decoder::new_mp4(data, Mp4Type::M4a),
+    },
+];
+
+/// Opens `path` with the first decoder backend that accepts it, returning the
+/// source along with the name of the backend that succeeded.
+fn open_source(path: &Path) -> Result<(FileDecoder, &'static str)> {
+    let mut last_error = match Decoder::new(BufReader::new(File::open(path)?)) {
+        Ok(source) => return Ok((source, "auto")),
+        Err(e) => e,
+    };
+
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default();
+    let mut backends: Vec<&DecoderBackend> = FALLBACK_DECODERS.iter().collect();
+    backends.sort_by_key(|backend| !backend.extensions.contains(&extension.as_str()));
+
+    for backend in backends {
+        match (backend.open)(BufReader::new(File::open(path)?)) {
+            Ok(source) => return Ok((source, backend.name)),
+            Err(e) => {
+                log::debug!("{} failed for {}: {}", backend.name, path.display(), e);
+                last_error = e;
+            }
+        }
+    }
+
+    Err(last_error.into())
+}
+
+// Silence played when pre-warming the output so the device is running
+// before the first real track.
+const PREWARM_DURATION: Duration = Duration::from_millis(100);
+
+// How long the output stays open after a stop before the device is given
+// back; long enough that the next play rarely has to reopen it.
+const OUTPUT_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Volume steps per fade; 20 keeps a 300ms fade free of audible zipper noise.
+const FADE_STEPS: u32 = 20;
+
+/// What a volume ramp does to the sink once it reaches its target.
+#[derive(Debug, Clone, Copy)]
+enum RampEnd {
+    Nothing,
+    /// Pause, leaving the volume at `restore` for a later resume.
+    Pause { restore: f32 },
+    Stop,
+}
+
+/// Steps the volume of the sink in `sink` from `from` to `to` over `duration`
+/// on a background thread. The ramp gives up as soon as `generation` moves
+/// on, so any later volume, pause, resume or play change wins over it.
+fn spawn_ramp(
+    sink: Arc<Mutex<Option<Sink>>>,
+    generation: Arc<AtomicU64>,
+    from: f32,
+    to: f32,
+    duration: Duration,
+    end: RampEnd,
+) {
+    let started = generation.load(Ordering::SeqCst);
+    thread::spawn(move || {
+        for step in 1..=FADE_STEPS {
+            thread::sleep(duration / FADE_STEPS);
+            // Checked under the lock, so a change made after bumping the
+            // generation always lands after the last step
+            let Ok(sink_guard) = sink.lock() else {
+                return;
+            };
+            if generation.load(Ordering::SeqCst) != started {
+                return;
+            }
+            let Some(sink) = sink_guard.as_ref() else {
+                return;
+            };
+            sink.set_volume(from + (to - from) * step as f32 / FADE_STEPS as f32);
+            if step == FADE_STEPS {
+                match end {
+                    RampEnd::Nothing => {}
+                    RampEnd::Pause { restore } => {
+                        sink.pause();
+                        sink.set_volume(restore);
+                    }
+                    RampEnd::Stop => sink.stop(),
+                }
+            }
+        }
+    });
+}
+
+/// Fades `incoming` up from silence to `to` while fading `outgoing` down
+/// from its volume to silence, both over `duration` on one background
+/// thread, then stops `outgoing`. The incoming ramp gives up when
+/// `generation` moves on (see [`spawn_ramp`]); the outgoing one when
+/// `outgoing_generation` does, which means its sink was cut or replaced.
+fn spawn_crossfade(
+    incoming: Arc<Mutex<Option<Sink>>>,
+    generation: Arc<AtomicU64>,
+    to: f32,
+    outgoing: Arc<Mutex<Option<Sink>>>,
+    outgoing_generation: Arc<AtomicU64>,
+    duration: Duration,
+) {
+    let started = generation.load(Ordering::SeqCst);
+    let outgoing_started = outgoing_generation.load(Ordering::SeqCst);
+    let from = outgoing
+        .lock()
+        .ok()
+        .and_then(|sink_guard| sink_guard.as_ref().map(Sink::volume))
+        .unwrap_or(0.0);
+    thread::spawn(move || {
+        for step in 1..=FADE_STEPS {
+            thread::sleep(duration / FADE_STEPS);
+            let progress = step as f32 / FADE_STEPS as f32;
+            let mut active = false;
+            if let Ok(sink_guard) = incoming.lock() {
+                if generation.load(Ordering::SeqCst) == started {
+                    if let Some(sink) = sink_guard.as_ref() {
+                        sink.set_volume(to * progress);
+                        active = true;
+                    }
+                }
+            }
+            if let Ok(mut sink_guard) = outgoing.lock() {
+                if outgoing_generation.load(Ordering::SeqCst) == outgoing_started {
+                    if step == FADE_STEPS {
+                        if let Some(sink) = sink_guard.take() {
+                            sink.stop();
+                        }
+                    } else if let Some(sink) = sink_guard.as_ref() {
+                        sink.set_volume(from * (1.0 - progress));
+                        active = true;
+                    }
+                }
+            }
+            if !active {
+                return;
+            }
+        }
+    });
+}
+
+/// The file loaded into the sink and how to turn progress into a position.
+struct Track {
+    path: PathBuf,
+    backend: &'static str,
+    progress: Arc<Progress>,
+    /// Its own volume factor, applied in its source chain.
+    gain: Arc<Gain>,
+    /// Set when the track is decoded ahead; see [`AudioPlayer::set_prefetch`].
+    buffer: Option<Arc<Buffer>>,
+    samples_per_second: u64,
+    total_duration: Option<Duration>,
+}
+
+/// Stream parameters reported by the decoder that accepted a file.
+#[derive(Debug, Clone)]
+pub struct ProbeInfo {
+    pub backend: &'static str,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub duration: Option<Duration>,
+}
+
+/// Opens `path` with the decoder chain without playing it.
+pub fn probe(path: &Path) -> Result<ProbeInfo> {
+    let (source, backend) = open_source(path)?;
+    Ok(ProbeInfo {
+        backend,
+        sample_rate: source.sample_rate(),
+        channels: source.channels(),
+        duration: source.total_duration(),
+    })
+}
+
+/// Where the audible part of `path` ends: just after the last sample louder
+/// than `threshold_db` (dBFS). `None` when the quiet stretch after it is
+/// shorter than `min_silence`, so quiet endings and short gaps are left
+/// alone, or when the whole file is quiet. Decodes the entire file.
+pub fn audible_end(path: &Path, threshold_db: f32, min_silence: Duration) -> Result<Option<Duration>> {
+    let (source, _) = open_source(path)?;
+    let channels = source.channels().max(1) as u64;
+    let sample_rate = source.sample_rate().max(1) as u64;
+    let threshold = (10f32.powf(threshold_db / 20.0) * i16::MAX as f32) as i32;
+    // Counted in samples of all channels, up to and including the last loud one
+    let mut samples = 0u64;
+    let mut last_loud = 0u64;
+    for sample in source {
+        samples += 1;
+        if (sample as i32).abs() > threshold {
+            last_loud = samples;
+        }
+    }
+    let to_time = |samples: u64| Duration::from_secs_f64(samples.div_ceil(channels) as f64 / sample_rate as f64);
+    let (end, total) = (to_time(last_loud), to_time(samples));
+    Ok((last_loud > 0 && total.saturating_sub(end) >= min_silence).then_some(end))
+}
+
+/// Names of the output devices on the default host.
+pub fn list_output_devices() -> Vec<String> {
+    match rodio::cpal::default_host().output_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(e) => {
+            log::warn!("Could not list output devices: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn find_device(name: &str) -> Option<Device> {
+    rodio::cpal::default_host()
+        .output_devices()
+        .ok()
+        .and_then(|mut devices| devices.find(|device| device.name().is_ok_and(|n| n == name)))
+}
+
+/// A stream config for `device` running at `rate` Hz, keeping the channel
+/// count and sample format of its default config where it can so only the
+/// rate changes.
+fn config_at_rate(device: &Device, rate: u32) -> Option<SupportedStreamConfig> {
+    let default = device.default_output_config().ok();
+    device
+        .supported_output_configs()
+        .ok()?
+        .filter(|range| range.min_sample_rate().0 <= rate && rate <= range.max_sample_rate().0)
+        .min_by_key(|range| {
+            default.as_ref().map_or((false, false), |default| {
+                (range.channels() != default.channels(), range.sample_format() != default.sample_format())
+            })
+        })
+        .map(|range| range.with_sample_rate(SampleRate(rate)))
+}
 
 pub struct AudioPlayer {
-    _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
+    /// `None` uses the host's default device.
+    device: Option<Device>,
+    /// Rate to open the output stream at instead of the device default, so
+    /// the system mixer doesn't resample.
+    sample_rate: Option<u32>,
+    /// Why the stream fell back to the device default, until the app takes it.
+    output_warning: Option<String>,
+    /// Opened on first use unless pre-warmed, so the device stays free until
+    /// something is played.
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    /// Pre-warmed players keep the stream open for good.
+    keep_output: bool,
+    /// When playback last stopped, so an idle stream can be closed.
+    stopped_at: Option<Instant>,
     sink: Arc<Mutex<Option<Sink>>>,
+    /// The previous track while a crossfade fades it out under `sink`.
+    outgoing: Arc<Mutex<Option<Sink>>>,
+    /// Bumped whenever `outgoing` is cut or replaced, so the crossfade that
+    /// was driving it leaves the slot alone.
+    outgoing_generation: Arc<AtomicU64>,
+    backend: Option<&'static str>,
+    track: Option<Track>,
+    /// Queued behind `track` in the same sink so it starts without a gap.
+    next: Option<Track>,
+    device_name: Option<String>,
+    /// Applied to every new sink, so it holds across tracks and while stopped.
+    current_volume: f32,
+    /// The current track's own factor (see [`AudioPlayer::set_track_gain`]),
+    /// and what the next one opened starts at.
+    track_gain: f32,
+    /// Playback speed factor, kept across tracks like the volume.
+    current_speed: f32,
+    tap: Arc<SampleTap>,
+    /// Equalizer bands applied to every track.
+    eq: Arc<EqSettings>,
+    /// Soft limiter after the equalizer, kept told of `current_volume`.
+    limiter: Arc<LimiterSettings>,
+    /// Length of the `*_with_fade` volume ramps; zero turns them into the
+    /// plain calls.
+    fade: Duration,
+    /// Bumped by every change to the current sink so a running ramp stops.
+    fade_generation: Arc<AtomicU64>,
+    /// How far ahead tracks are decoded on their own thread, if they are.
+    prefetch: Option<Duration>,
 }
 
 impl AudioPlayer {
-    pub fn new() -> Result<Self> {
-        let (stream, stream_handle) = OutputStream::try_default()?;
+    /// Creates the player. With `prewarm` the output stream is opened now and
+    /// fed a moment of silence, so the first play starts without a delay or pop.
+    /// With `sample_rate` the stream runs at that rate when the device
+    /// supports it, and at the device default otherwise.
+    pub fn new(prewarm: bool, sample_rate: Option<u32>) -> Result<Self> {
+        Self::open(None, prewarm, sample_rate)
+    }
 
-        Ok(AudioPlayer {
-            _stream: stream,
-            stream_handle,
+    /// Like [`AudioPlayer::new`], but plays on the output device called `name`.
+    /// Falls back to the default device with a warning if there is none.
+    pub fn with_device(name: &str, prewarm: bool, sample_rate: Option<u32>) -> Result<Self> {
+        let device = find_device(name);
+        if device.is_none() {
+            log::warn!("Output device {:?} not found, using the default", name);
+        }
+        Self::open(device, prewarm, sample_rate)
+    }
+
+    /// Moves output to the device called `name`, carrying on with the current
+    /// track from the same position. Returns `false`, leaving the current
+    /// device in place, when there is no such device.
+    pub fn set_device(&mut self, name: &str) -> Result<bool> {
+        let Some(device) = find_device(name) else {
+            log::warn!("Output device {:?} not found", name);
+            return Ok(false);
+        };
+        let resume = self.track.as_ref().map(|track| (track.path.clone(), self.position()));
+        let paused = self.is_paused();
+        // Drop the sinks before the stream they play on; reopen keeps the preload
+        self.cut_outgoing();
+        if let Ok(mut sink_guard) = self.sink.lock() {
+            if let Some(sink) = sink_guard.take() {
+                sink.stop();
+            }
+        }
+        self.output = None;
+        self.device_name = device.name().ok();
+        self.device = Some(device);
+        match resume {
+            Some((path, position)) => self.reopen(&path, position, paused)?,
+            None => self.stop(),
+        }
+        Ok(true)
+    }
+
+    fn open(device: Option<Device>, prewarm: bool, sample_rate: Option<u32>) -> Result<Self> {
+        let device_name = match device {
+            Some(ref device) => device.name().ok(),
+            None => rodio::cpal::default_host()
+                .default_output_device()
+                .and_then(|device| device.name().ok()),
+        };
+
+        let mut player = AudioPlayer {
+            device,
+            sample_rate,
+            output_warning: None,
+            output: None,
+            keep_output: prewarm,
+            stopped_at: None,
             sink: Arc::new(Mutex::new(None)),
-        })
+            outgoing: Arc::new(Mutex::new(None)),
+            outgoing_generation: Arc::new(AtomicU64::new(0)),
+            backend: None,
+            track: None,
+            next: None,
+            device_name,
+            current_volume: 1.0,
+            track_gain: 1.0,
+            current_speed: 1.0,
+            tap: Arc::new(SampleTap::default()),
+            eq: Arc::new(EqSettings::default()),
+            limiter: Arc::new(LimiterSettings::default()),
+            fade: Duration::ZERO,
+            prefetch: None,
+            fade_generation: Arc::new(AtomicU64::new(0)),
+        };
+        if prewarm {
+            let sink = Sink::try_new(player.output()?)?;
+            sink.append(Zero::<f32>::new(2, 44_100).take_duration(PREWARM_DURATION));
+            sink.detach();
+        }
+        Ok(player)
+    }
+
+    /// Handle to the output stream, opening it if this is the first use.
+    fn output(&mut self) -> Result<&OutputStreamHandle> {
+        if self.output.is_none() {
+            let output = match self.open_at_sample_rate() {
+                Some(output) => output,
+                None => match self.device {
+                    Some(ref device) => OutputStream::try_from_device(device),
+                    None => OutputStream::try_default(),
+                }
+                .inspect_err(|e| log::error!("Could not open the output device: {}", e))?,
+            };
+            log::info!("Opened output device {}", self.device_name().unwrap_or("(unknown)"));
+            self.output = Some(output);
+        }
+        Ok(&self.output.as_ref().unwrap().1)
+    }
+
+    /// Opens the stream at the configured sample rate. `None` when no rate
+    /// is set, or when the device can't run at it, in which case the reason
+    /// is left in `output_warning`.
+    fn open_at_sample_rate(&mut self) -> Option<(OutputStream, OutputStreamHandle)> {
+        let rate = self.sample_rate?;
+        let default_device;
+        let device = match self.device {
+            Some(ref device) => device,
+            None => {
+                default_device = rodio::cpal::default_host().default_output_device()?;
+                &default_device
+            }
+        };
+        let opened = match config_at_rate(device, rate) {
+            Some(config) => OutputStream::try_from_device_config(device, config).map_err(|e| e.to_string()),
+            None => Err("not supported by the device".to_string()),
+        };
+        match opened {
+            Ok(output) => {
+                log::info!("Output stream running at {} Hz", rate);
+                Some(output)
+            }
+            Err(reason) => {
+                let warning = format!("Could not output at {} Hz ({}), using the device default", rate, reason);
+                log::warn!("{}", warning);
+                self.output_warning = Some(warning);
+                None
+            }
+        }
+    }
+
+    /// Why the output stream didn't open at the requested sample rate, once
+    /// per time it happened.
+    pub fn take_output_warning(&mut self) -> Option<String> {
+        self.output_warning.take()
+    }
+
+    /// Name of the output device the stream was opened on, if it could be read.
+    pub fn device_name(&self) -> Option<&str> {
+        self.device_name.as_deref()
     }
 
     pub fn play<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.play_from(path.as_ref(), Duration::ZERO, false)
+    }
+
+    /// Sets how long fades take; `Duration::ZERO` disables them.
+    pub fn set_fade(&mut self, fade: Duration) {
+        self.fade = fade;
+    }
+
+    /// Decodes tracks opened from now on up to `ahead` in front of the
+    /// output on a thread of their own, so that a slow read shows up as an
+    /// underrun (see [`AudioPlayer::take_underrun`]) instead of stalling
+    /// the device. `None` decodes in the output callback as usual.
+    pub fn set_prefetch(&mut self, ahead: Option<Duration>) {
+        self.prefetch = ahead;
+    }
+
+    /// Like [`AudioPlayer::play`], but fades out whatever is playing and
+    /// ramps the new track up from silence.
+    pub fn play_with_fade<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        if self.fade.is_zero() {
+            return self.play(path);
+        }
+        self.stop_with_fade();
+        self.play(path)?;
+        self.ramp_current(0.0, self.current_volume, RampEnd::Nothing);
+        Ok(())
+    }
+
+    /// Like [`AudioPlayer::stop`], but lets the track fade out first. The
+    /// fading sink is detached from the player, so a play right after starts
+    /// a fresh sink at the normal volume.
+    pub fn stop_with_fade(&mut self) {
+        let fading = self.sink.lock().ok().and_then(|mut sink_guard| sink_guard.take());
+        self.stop();
+        if let Some(sink) = fading {
+            if self.fade.is_zero() || sink.is_paused() {
+                sink.stop();
+            } else {
+                let volume = sink.volume();
+                let sink = Arc::new(Mutex::new(Some(sink)));
+                spawn_ramp(sink, Arc::new(AtomicU64::new(0)), volume, 0.0, self.fade, RampEnd::Stop);
+            }
+        }
+    }
+
+    /// Starts `path` at silence and fades it up over `duration` while the
+    /// current track fades out and is dropped. The two play at once until
+    /// the fade is done; skipping, stopping or pausing in the meantime cuts
+    /// the outgoing track off. A paused track isn't faded but stopped.
+    pub fn crossfade_to(&mut self, path: &Path, duration: Duration) -> Result<()> {
+        if duration.is_zero() {
+            return self.play(path);
+        }
+        // A crossfade that is still running loses its outgoing track now
+        self.cut_outgoing();
+        let outgoing = self
+            .sink
+            .lock()
+            .ok()
+            .and_then(|mut sink_guard| sink_guard.take())
+            .filter(|sink| !sink.is_paused() && !sink.empty());
+        let started = self.play(path);
+        if let Ok(mut outgoing_guard) = self.outgoing.lock() {
+            *outgoing_guard = outgoing;
+        }
+        if let Err(e) = started {
+            // Nothing to fade in, so just let the old track fade away
+            spawn_crossfade(
+                self.sink.clone(),
+                self.fade_generation.clone(),
+                0.0,
+                self.outgoing.clone(),
+                self.outgoing_generation.clone(),
+                duration,
+            );
+            return Err(e);
+        }
+        self.fade_generation.fetch_add(1, Ordering::SeqCst);
+        if let Ok(sink_guard) = self.sink.lock() {
+            if let Some(sink) = sink_guard.as_ref() {
+                sink.set_volume(0.0);
+            }
+        }
+        spawn_crossfade(
+            self.sink.clone(),
+            self.fade_generation.clone(),
+            self.current_volume,
+            self.outgoing.clone(),
+            self.outgoing_generation.clone(),
+            duration,
+        );
+        Ok(())
+    }
+
+    /// Stops the outgoing track of a crossfade straight away.
+    fn cut_outgoing(&self) {
+        self.outgoing_generation.fetch_add(1, Ordering::SeqCst);
+        if let Ok(mut sink_guard) = self.outgoing.lock() {
+            if let Some(sink) = sink_guard.take() {
+                sink.stop();
+            }
+        }
+    }
+
+    /// Like [`AudioPlayer::pause`], but fades out before pausing.
+    pub fn pause_with_fade(&mut self) {
+        self.cut_outgoing();
+        if self.fade.is_zero() || self.is_paused() {
+            return self.pause();
+        }
+        let volume = self.current_volume;
+        self.ramp_current(volume, 0.0, RampEnd::Pause { restore: volume });
+    }
+
+    /// Like [`AudioPlayer::resume`], but ramps up from silence.
+    pub fn resume_with_fade(&mut self) {
+        self.resume();
+        if !self.fade.is_zero() {
+            self.ramp_current(0.0, self.current_volume, RampEnd::Nothing);
+        }
+    }
+
+    /// Starts a ramp on the current sink, replacing any running one.
+    fn ramp_current(&self, from: f32, to: f32, end: RampEnd) {
+        self.fade_generation.fetch_add(1, Ordering::SeqCst);
+        if let Ok(sink_guard) = self.sink.lock() {
+            if let Some(sink) = sink_guard.as_ref() {
+                sink.set_volume(from);
+            }
+        }
+        spawn_ramp(self.sink.clone(), self.fade_generation.clone(), from, to, self.fade, end);
+    }
+
+    fn play_from(&mut self, path: &Path, offset: Duration, paused: bool) -> Result<()> {
         // Stop any currently playing audio
         self.stop();
 
         // Create a new sink
-        let sink = Sink::try_new(&self.stream_handle)?;
+        let sink = Sink::try_new(self.output()?)?;
+        sink.set_volume(self.current_volume);
+        sink.set_speed(self.current_speed);
 
-        // Open the audio file
-        let file = File::open(path.as_ref())?;
-        let source = Decoder::new(BufReader::new(file))?;
+        // Open the audio file, falling back through the decoder backends
+        let (mut source, backend) = open_source(path)?;
+        log::debug!("Decoded {} with {}", path.display(), backend);
+        self.backend = Some(backend);
+
+        // Decoders that can't seek have to decode their way to the offset
+        let skip = if offset.is_zero() {
+            Duration::ZERO
+        } else {
+            match source.try_seek(offset) {
+                Ok(()) => Duration::ZERO,
+                Err(SeekError::NotSupported { .. }) => offset,
+                Err(e) => return Err(anyhow!("Could not seek to {:?}: {}", offset, e)),
+            }
+        };
+        let progress = Arc::new(Progress::default());
+        progress.set_position(offset, source.sample_rate(), source.channels());
+        let gain = Arc::new(Gain::new(self.track_gain));
+        let (source, buffer) = self.prefetched(source.skip_duration(skip));
+        self.track = Some(Track {
+            path: path.to_path_buf(),
+            backend,
+            progress: progress.clone(),
+            gain: gain.clone(),
+            buffer,
+            samples_per_second: source.sample_rate() as u64 * source.channels() as u64,
+            total_duration: source.total_duration(),
+        });
 
         // Add the source to the sink and play
-        sink.append(source);
-        sink.play();
+        let source = Scaled::new(Tracked::new(source, progress), gain);
+        let source = Equalizer::new(source, self.eq.clone());
+        let source = Limiter::new(source, self.limiter.clone());
+        sink.append(Tapped::new(source, self.tap.clone()));
+        if paused {
+            sink.pause();
+        } else {
+            sink.play();
+        }
 
         // Store the sink
         *self.sink.lock().unwrap() = Some(sink);
@@ -43,7 +653,189 @@ impl AudioPlayer {
         Ok(())
     }
 
+    /// Decodes `path` and queues it right behind the current track, so it
+    /// starts without a gap, at its own `gain` (see
+    /// [`AudioPlayer::set_track_gain`]). Replaces an earlier preload; does
+    /// nothing when no track is loaded.
+    pub fn preload_next(&mut self, path: &Path, gain: f32) -> Result<()> {
+        self.discard_preload();
+        let sink_guard = self.sink.lock().unwrap();
+        let Some(sink) = sink_guard.as_ref() else {
+            return Ok(());
+        };
+
+        let (source, backend) = open_source(path)?;
+        log::debug!("Preloaded {} with {}", path.display(), backend);
+        let progress = Arc::new(Progress::default());
+        let gain = Arc::new(Gain::new(gain));
+        let (source, buffer) = self.prefetched(source);
+        let track = Track {
+            path: path.to_path_buf(),
+            backend,
+            progress: progress.clone(),
+            gain: gain.clone(),
+            buffer,
+            samples_per_second: source.sample_rate() as u64 * source.channels() as u64,
+            total_duration: source.total_duration(),
+        };
+        let source = Equalizer::new(Scaled::new(Tracked::new(source, progress), gain), self.eq.clone());
+        let source = Limiter::new(source, self.limiter.clone());
+        sink.append(Tapped::new(source, self.tap.clone()));
+        drop(sink_guard);
+        self.next = Some(track);
+        Ok(())
+    }
+
+    /// Puts `source` behind a [`Prefetch`] when tracks are decoded ahead.
+    fn prefetched<S>(&self, source: S) -> (Box<dyn Source<Item = i16> + Send>, Option<Arc<Buffer>>)
+    where
+        S: Source<Item = i16> + Send + 'static,
+    {
+        match self.prefetch {
+            Some(ahead) => {
+                let buffer = Arc::new(Buffer::default());
+                (Box::new(Prefetch::new(source, ahead, buffer.clone())), Some(buffer))
+            }
+            None => (Box::new(source), None),
+        }
+    }
+
+    /// Whether the current track's output ran dry since the last call while
+    /// its decoder was still behind, as opposed to the track ending. Only
+    /// happens with [`AudioPlayer::set_prefetch`].
+    pub fn take_underrun(&self) -> bool {
+        self.track.as_ref().and_then(|track| track.buffer.as_ref()).is_some_and(|buffer| buffer.take_starved())
+    }
+
+    /// Whether at least `ahead` of the current track is decoded and waiting,
+    /// or all of the rest of it is.
+    pub fn is_buffered(&self, ahead: Duration) -> bool {
+        let Some(track) = self.track.as_ref() else {
+            return true;
+        };
+        let Some(ref buffer) = track.buffer else {
+            return true;
+        };
+        buffer.is_decoded() || buffer.buffered() as f64 >= ahead.as_secs_f64() * track.samples_per_second as f64
+    }
+
+    /// Drops the preloaded track; it is skipped if the sink reaches it.
+    pub fn discard_preload(&mut self) {
+        if let Some(next) = self.next.take() {
+            next.progress.cancel();
+        }
+    }
+
+    /// The track queued by [`AudioPlayer::preload_next`], if any.
+    pub fn preloaded(&self) -> Option<&Path> {
+        self.next.as_ref().map(|next| next.path.as_path())
+    }
+
+    /// Checks whether the current track has ended and the preloaded one has
+    /// taken over, making it the current track. Returns `true` once per switch.
+    pub fn advance_preloaded(&mut self) -> bool {
+        let finished = self.track.as_ref().is_some_and(|track| track.progress.is_exhausted());
+        if !finished || self.next.is_none() {
+            return false;
+        }
+        let next = self.next.take();
+        self.backend = next.as_ref().map(|next| next.backend);
+        if let Some(ref next) = next {
+            self.track_gain = next.gain.get();
+        }
+        self.track = next;
+        true
+    }
+
+    /// Path of the current track.
+    pub fn current_path(&self) -> Option<&Path> {
+        self.track.as_ref().map(|track| track.path.as_path())
+    }
+
+    /// How far into the current track the output has read.
+    pub fn position(&self) -> Duration {
+        match self.track {
+            Some(ref track) if track.samples_per_second > 0 => {
+                // Silence filled in during an underrun isn't part of the track
+                let padding = track.buffer.as_ref().map_or(0, |buffer| buffer.padding());
+                let samples = track.progress.samples().saturating_sub(padding);
+                Duration::from_secs_f64(samples as f64 / track.samples_per_second as f64)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Length of the current track, when the decoder knows it.
+    pub fn duration(&self) -> Option<Duration> {
+        self.track.as_ref().and_then(|track| track.total_duration)
+    }
+
+    /// Moves the current track to `position`, keeping it paused or playing.
+    /// Seeks in place on the audio thread, so the preload stays queued; only
+    /// a decoder that can't seek makes it reopen the file.
+    pub fn seek(&mut self, position: Duration) -> Result<()> {
+        if self.seek_in_place(position)? {
+            return Ok(());
+        }
+        let Some(ref track) = self.track else {
+            return Ok(());
+        };
+        let path = track.path.clone();
+        log::debug!("Decoder can't seek, reopening {}", path.display());
+        let paused = self.is_paused();
+        self.reopen(&path, self.clamp_to_track(position), paused)
+    }
+
+    /// Like [`AudioPlayer::seek`], but returns `false` and leaves playback
+    /// alone when the decoder can't seek, rather than decoding its way there.
+    pub fn seek_in_place(&self, position: Duration) -> Result<bool> {
+        if self.track.is_none() {
+            return Ok(true);
+        }
+        let position = self.clamp_to_track(position);
+        let seeked = match self.sink.lock() {
+            Ok(sink_guard) => sink_guard.as_ref().map(|sink| sink.try_seek(position)),
+            Err(_) => None,
+        };
+        match seeked {
+            Some(Ok(())) | None => Ok(true),
+            Some(Err(SeekError::NotSupported { .. })) => Ok(false),
+            Some(Err(e)) => Err(anyhow!("{}", e)),
+        }
+    }
+
+    fn clamp_to_track(&self, position: Duration) -> Duration {
+        match self.duration() {
+            Some(total) => position.min(total),
+            None => position,
+        }
+    }
+
+    /// Like `play_from`, but keeps the preloaded next track queued.
+    fn reopen(&mut self, path: &Path, offset: Duration, paused: bool) -> Result<()> {
+        let next = self.next.as_ref().map(|next| (next.path.clone(), next.gain.get()));
+        self.play_from(path, offset, paused)?;
+        if let Some((next, gain)) = next {
+            if let Err(e) = self.preload_next(&next, gain) {
+                log::warn!("Could not preload {}: {}", next.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of the decoder backend used for the current track.
+    pub fn backend(&self) -> Option<&'static str> {
+        self.backend
+    }
+
     pub fn stop(&mut self) {
+        self.stopped_at = Some(Instant::now());
+        self.cut_outgoing();
+        self.backend = None;
+        self.track = None;
+        self.next = None;
+        self.tap.clear();
+        self.fade_generation.fetch_add(1, Ordering::SeqCst);
         if let Ok(mut sink_guard) = self.sink.lock() {
             if let Some(sink) = sink_guard.take() {
                 sink.stop();
@@ -51,7 +843,25 @@ impl AudioPlayer {
         }
     }
 
+    /// Closes the output stream once nothing has played on it for a while,
+    /// so other programs can have the device. The next play reopens it.
+    /// Does nothing for a pre-warmed player.
+    pub fn release_idle_output(&mut self) {
+        if self.keep_output || self.output.is_none() || self.track.is_some() {
+            return;
+        }
+        if self.stopped_at.is_none_or(|at| at.elapsed() < OUTPUT_IDLE_TIMEOUT) {
+            return;
+        }
+        self.cut_outgoing();
+        self.output = None;
+        self.stopped_at = None;
+        log::info!("Closed the idle output device");
+    }
+
     pub fn pause(&mut self) {
+        self.cut_outgoing();
+        self.fade_generation.fetch_add(1, Ordering::SeqCst);
         if let Ok(sink_guard) = self.sink.lock() {
             if let Some(sink) = sink_guard.as_ref() {
                 sink.pause();
@@ -59,9 +869,12 @@ impl AudioPlayer {
         }
     }
 
+    /// Resumes at the normal volume, cutting short any fade in progress.
     pub fn resume(&mut self) {
+        self.fade_generation.fetch_add(1, Ordering::SeqCst);
         if let Ok(sink_guard) = self.sink.lock() {
             if let Some(sink) = sink_guard.as_ref() {
+                sink.set_volume(self.current_volume);
                 sink.play();
             }
         }
@@ -85,11 +898,161 @@ impl AudioPlayer {
         true
     }
 
+    /// Sets the volume of the current sink and of every later one. Above 1.0
+    /// amplifies; the caller enforces the configured cap.
     pub fn set_volume(&mut self, volume: f32) {
+        self.current_volume = volume.max(0.0);
+        self.limiter.set_gain(self.current_volume);
+        self.fade_generation.fetch_add(1, Ordering::SeqCst);
+        if let Ok(sink_guard) = self.sink.lock() {
+            if let Some(sink) = sink_guard.as_ref() {
+                sink.set_volume(self.current_volume);
+            }
+        }
+    }
+
+    /// Sets the volume later sinks start at without touching the current
+    /// one, e.g. ahead of a play that fades the current track out.
+    pub fn set_next_volume(&mut self, volume: f32) {
+        self.current_volume = volume.max(0.0);
+        self.limiter.set_gain(self.current_volume);
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.current_volume
+    }
+
+    /// Sets the current track's own volume factor, on top of the sink
+    /// volume. It sits in the track's source chain, so a preloaded track
+    /// queued behind it on the same sink keeps its own.
+    pub fn set_track_gain(&mut self, gain: f32) {
+        self.track_gain = gain.max(0.0);
+        if let Some(ref track) = self.track {
+            track.gain.set(self.track_gain);
+        }
+    }
+
+    /// Sets the factor the next track opened starts at without touching the
+    /// current one, like [`AudioPlayer::set_next_volume`].
+    pub fn set_next_track_gain(&mut self, gain: f32) {
+        self.track_gain = gain.max(0.0);
+    }
+
+    /// Changes the factor of the preloaded track, if there is one.
+    pub fn set_preload_gain(&self, gain: f32) {
+        if let Some(ref next) = self.next {
+            next.gain.set(gain.max(0.0));
+        }
+    }
+
+    /// Changes the playback speed, taking effect immediately. Like a tape,
+    /// this shifts the pitch along with the tempo.
+    pub fn set_speed(&mut self, factor: f32) {
+        self.current_speed = factor;
         if let Ok(sink_guard) = self.sink.lock() {
             if let Some(sink) = sink_guard.as_ref() {
-                sink.set_volume(volume.clamp(0.0, 1.0));
+                sink.set_speed(factor);
             }
         }
     }
+
+    pub fn speed(&self) -> f32 {
+        self.current_speed
+    }
+
+    /// Sets equalizer band `index` (see [`crate::eq::BANDS`]) to `gain_db`,
+    /// clamped to ±12 dB. Takes effect within a frame, including on the
+    /// preloaded track.
+    pub fn set_eq_band(&self, index: usize, gain_db: f32) {
+        self.eq.set_band(index, gain_db);
+    }
+
+    /// Sets the equalizer bands from the lowest up; missing bands are left
+    /// as they are.
+    pub fn set_eq_gains(&self, gains: &[f32]) {
+        for (index, &gain) in gains.iter().enumerate() {
+            self.eq.set_band(index, gain);
+        }
+    }
+
+    pub fn eq_gains(&self) -> Gains {
+        self.eq.gains()
+    }
+
+    /// The soft limiter, for turning it on and off and seeing whether it
+    /// engages.
+    pub fn limiter(&self) -> &LimiterSettings {
+        &self.limiter
+    }
+
+    /// Recent output for the visualizer; see [`SampleTap::set_enabled`].
+    pub fn tap(&self) -> &SampleTap {
+        &self.tap
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A one-second silent mono wav.
+    pub(crate) fn wav() -> Vec<u8> {
+        let rate: u32 = 8000;
+        let data = rate * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&rate.to_le_bytes());
+        bytes.extend_from_slice(&(rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data.to_le_bytes());
+        bytes.resize(bytes.len() + data as usize, 0);
+        bytes
+    }
+
+    #[test]
+    fn listing_devices_never_panics() {
+        // Empty on a machine without audio, which is fine
+        let _ = list_output_devices();
+    }
+
+    #[test]
+    fn unknown_device_falls_back_to_the_default() {
+        assert!(find_device("no such device").is_none());
+        let mut player = AudioPlayer::with_device("no such device", false, None).unwrap();
+        assert!(!player.set_device("no such device").unwrap());
+    }
+
+    #[test]
+    fn volume_set_before_any_sink_is_kept() {
+        // Output opens lazily, so this needs no device
+        let mut player = AudioPlayer::new(false, None).unwrap();
+        player.set_volume(0.4);
+        assert_eq!(player.volume(), 0.4);
+        assert_eq!(player.limiter.gain(), 0.4);
+        player.set_next_volume(1.5);
+        assert_eq!(player.volume(), 1.5);
+        assert_eq!(player.limiter.gain(), 1.5);
+        player.set_volume(-1.0);
+        assert_eq!(player.volume(), 0.0);
+    }
+
+    #[test]
+    #[ignore = "needs an audio output device"]
+    fn first_sink_starts_at_the_volume_set_before_it() {
+        let path = std::env::temp_dir().join(format!("oxiplayer-volume-{}.wav", std::process::id()));
+        std::fs::write(&path, wav()).unwrap();
+        let mut player = AudioPlayer::new(false, None).unwrap();
+        player.set_volume(0.4);
+        player.play(&path).unwrap();
+        let volume = player.sink.lock().unwrap().as_ref().map(Sink::volume);
+        assert_eq!(volume, Some(0.4));
+        std::fs::remove_file(path).unwrap();
+    }
 }
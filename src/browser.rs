@@ -0,0 +1,102 @@
+use crate::config::Config;
+use crate::scan;
+use crate::MusicFile;
+use std::path::{Path, PathBuf};
+
+/// One row of the folder view.
+#[derive(Debug, Clone)]
+pub enum BrowserEntry {
+    Dir(PathBuf),
+    Track(MusicFile),
+}
+
+/// The music directory shown one folder at a time. Each folder is read
+/// from disk when it is entered rather than walked up front.
+#[derive(Debug, Clone)]
+pub struct Browser {
+    /// The music directory; the view never goes above it.
+    pub root: PathBuf,
+    /// The folder being shown.
+    pub dir: PathBuf,
+    /// A `..` entry for the parent (except at the root), then subfolders,
+    /// then tracks.
+    pub entries: Vec<BrowserEntry>,
+    pub selected: usize,
+}
+
+impl Browser {
+    pub fn open(root: &Path, dir: &Path, config: &Config) -> Self {
+        let (dirs, files) = scan::list_dir(root, dir, config);
+        let mut entries = Vec::with_capacity(dirs.len() + files.len() + 1);
+        if let Some(parent) = dir.parent().filter(|_| dir != root) {
+            entries.push(BrowserEntry::Dir(parent.to_path_buf()));
+        }
+        entries.extend(dirs.into_iter().map(BrowserEntry::Dir));
+        entries.extend(files.into_iter().map(|path| BrowserEntry::Track(MusicFile::from_path(path))));
+        Browser {
+            root: root.to_path_buf(),
+            dir: dir.to_path_buf(),
+            entries,
+            selected: 0,
+        }
+    }
+
+    /// Whether `entry` is the `..` entry leading up a level.
+    pub fn is_parent(&self, entry: &BrowserEntry) -> bool {
+        matches!(entry, BrowserEntry::Dir(path) if Some(path.as_path()) == self.dir.parent())
+    }
+
+    pub fn selected_entry(&self) -> Option<&BrowserEntry> {
+        self.entries.get(self.selected)
+    }
+
+    /// Moves the selection by `delta` rows, stopping at either end.
+    pub fn move_by(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Selects the entry for `path`, if it is listed.
+    pub fn select_path(&mut self, path: &Path) {
+        let found = self.entries.iter().position(|entry| match entry {
+            BrowserEntry::Dir(dir) => dir == path,
+            BrowserEntry::Track(file) => file.path == path,
+        });
+        if let Some(index) = found {
+            self.selected = index;
+        }
+    }
+
+    /// Shows the folder `dir`.
+    pub fn enter(&mut self, dir: &Path, config: &Config) {
+        *self = Browser::open(&self.root, dir, config);
+    }
+
+    /// Reads the folder again, keeping the selected entry if it is still
+    /// there. Shows the root if the folder itself is gone.
+    pub fn reload(&mut self, config: &Config) {
+        let selected = self.selected_entry().map(|entry| match entry {
+            BrowserEntry::Dir(dir) => dir.clone(),
+            BrowserEntry::Track(file) => file.path.clone(),
+        });
+        let dir = if self.dir.is_dir() { self.dir.clone() } else { self.root.clone() };
+        self.enter(&dir, config);
+        if let Some(path) = selected {
+            self.select_path(&path);
+        }
+    }
+
+    /// Shows the parent folder with the folder just left selected. Does
+    /// nothing at the root.
+    pub fn up(&mut self, config: &Config) {
+        if self.dir == self.root {
+            return;
+        }
+        let Some(parent) = self.dir.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        let left = self.dir.clone();
+        self.enter(&parent, config);
+        self.select_path(&left);
+    }
+}
@@ -0,0 +1,30 @@
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+/// Requests sent to the player from outside the key handler, e.g. by
+/// background integrations. They are applied on the next [`App::tick`].
+///
+/// [`App::tick`]: crate::App::tick
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Resume when paused, otherwise play the selected track.
+    Play,
+    Pause,
+    Resume,
+    TogglePause,
+    Stop,
+    Next,
+    Previous,
+    /// Jump to this position in the current track.
+    Seek(Duration),
+    /// Set the volume in percent, capped at `max_volume_percent`.
+    SetVolume(u16),
+    /// Reply with [`App::status_line`].
+    ///
+    /// [`App::status_line`]: crate::App::status_line
+    Status(Sender<String>),
+    /// The system is about to sleep or the session was locked.
+    Suspend,
+    /// The system woke up or the session was unlocked.
+    Wake,
+}
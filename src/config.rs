@@ -0,0 +1,289 @@
+use crate::keybindings::KeyList;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// User preferences read from `config.toml` in the platform config directory.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Move the selection along with playback when advancing tracks. When
+    /// disabled the list can be browsed freely while music plays.
+    pub follow_play: bool,
+    /// Whether video containers (mp4, m4v, mov) are listed for their audio track.
+    pub video_containers: VideoContainers,
+    /// File overwritten with the current track on every track change.
+    pub now_playing_file: Option<PathBuf>,
+    /// Template for the now-playing text; see `template::format_track`.
+    pub now_playing_format: String,
+    /// Glob patterns skipped while scanning, matched against paths both
+    /// relative to the music directory and in full.
+    pub exclude: Vec<String>,
+    /// When not empty, only files matching one of these globs are listed.
+    /// Matched like `exclude`, after the extension check.
+    pub include: Vec<String>,
+    /// Directories offered by the quick jump list, in order.
+    pub pinned_dirs: Vec<PathBuf>,
+    /// What Enter does while a search has no matches.
+    pub on_empty_search_enter: EmptySearchEnter,
+    /// What Enter does on a folder in the folder view.
+    pub folder_enter: FolderEnter,
+    /// What next/previous and auto-advance do when they land on a file that
+    /// previously failed to decode.
+    pub on_bad_file: BadFileAction,
+    /// Which tracks shuffle leans towards.
+    pub shuffle_weighting: ShuffleWeighting,
+    /// Pause when the system suspends or the session locks. Needs the
+    /// `logind` feature and is ignored on other platforms.
+    pub pause_on_suspend: bool,
+    /// Resume on wake/unlock if playback was paused by a suspend or lock.
+    pub resume_on_wake: bool,
+    /// Publish playback on D-Bus for media keys and desktop widgets. Only
+    /// used when built with the `mpris` feature.
+    pub mpris: bool,
+    /// Ask before quitting with a track playing; `--confirm-quit` turns it
+    /// on for one run.
+    pub confirm_quit: bool,
+    /// Ask before a loaded playlist replaces a non-empty list.
+    pub confirm_playlist_replace: bool,
+    /// How elapsed times and durations are displayed.
+    pub time_format: TimeFormat,
+    /// Start each list row with the zero-padded track number from the tags.
+    pub show_track_numbers: bool,
+    /// Open the output device at startup and play a moment of silence so the
+    /// first track starts instantly. When off, the device is only opened on
+    /// the first play.
+    pub prewarm_audio: bool,
+    /// Sample rate to open the output at, in Hz, instead of the device
+    /// default; `--sample-rate` takes precedence.
+    pub sample_rate: Option<u32>,
+    /// Decode ahead of the output and pause when it runs dry, instead of
+    /// letting a slow disk or network share stutter.
+    pub pause_on_underrun: bool,
+    /// How much has to be decoded again, in milliseconds, before playback
+    /// picks up after an underrun.
+    pub rebuffer_ms: u64,
+    /// Seconds to rewind when resuming from pause, to pick the thread back up.
+    pub resume_rewind_secs: u64,
+    /// Seconds of silence between a track ending and the next one starting.
+    /// Only applies to auto-advance; next/previous start immediately.
+    pub inter_track_delay_secs: u64,
+    /// Milliseconds to fade in when a track starts or playback resumes, and
+    /// to fade out on stop and pause. 0 turns fading off.
+    pub fade_ms: u64,
+    /// Milliseconds the end of a track overlaps the start of the next on
+    /// auto-advance, one fading out as the other fades in. 0 keeps the
+    /// gapless transition.
+    pub crossfade_ms: u64,
+    /// Move on as soon as a track falls silent for good instead of playing
+    /// out its trailing silence. Each track is decoded a second time in the
+    /// background to find where that is.
+    pub skip_silence: bool,
+    /// Soft-limit peaks that the volume and gains would push past full
+    /// scale, instead of letting them clip.
+    pub limiter: bool,
+    /// Samples quieter than this, in dBFS, count as silence for `skip_silence`.
+    pub silence_threshold_db: f32,
+    /// Trailing silence shorter than this many milliseconds is played as is.
+    pub min_silence_ms: u64,
+    /// Colors for a dark or light terminal background, or none at all.
+    pub theme: ThemeName,
+    /// TOML file with colors to use on top of `theme`.
+    pub theme_file: Option<PathBuf>,
+    /// Pick dark or light from the system preference instead of `theme`,
+    /// switching live when it changes. Falls back to `theme` where the
+    /// platform has no such preference.
+    pub theme_follow_system: bool,
+    /// Highest volume `+` can reach, in percent. Above 100 amplifies quiet
+    /// files at the risk of clipping.
+    pub max_volume_percent: u32,
+    /// Directory opened when none is given on the command line.
+    pub default_directory: Option<PathBuf>,
+    /// With no directory argument and no `default_directory`, start with a
+    /// directory browser instead of scanning the current directory.
+    pub pick_directory_on_start: bool,
+    /// Pause when `-` takes the volume to 0%, and resume on the next `+`.
+    pub pause_at_zero_volume: bool,
+    /// Quit, not just stop, when the sleep timer runs out.
+    pub sleep_timer_quit: bool,
+    /// Remember where playback got to in tracks at least this many minutes
+    /// long, to resume next session. 0 turns it off.
+    pub remember_position_min_minutes: u64,
+    /// Output device to play on, by name; the system default when unset.
+    pub output_device: Option<String>,
+    /// Unix socket to accept commands on; `--control-socket` takes precedence.
+    pub control_socket: Option<PathBuf>,
+    /// Show the spectrum visualizer at startup.
+    pub visualizer: bool,
+    /// Show the playing track's embedded cover art (needs a truecolor terminal).
+    pub album_art: bool,
+    /// Play tracks at the loudness given by their ReplayGain tags.
+    pub normalize: bool,
+    /// Key overrides from the `[keys]` table, by action name.
+    pub keys: HashMap<String, KeyList>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoContainers {
+    /// Never list video containers.
+    #[default]
+    Skip,
+    /// List a video container only when no audio file shares its basename.
+    PreferAudio,
+    /// List every video container alongside the audio files.
+    Include,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptySearchEnter {
+    /// Clear the search and show the whole list again.
+    Clear,
+    /// Do nothing and keep editing the query.
+    #[default]
+    Ignore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderEnter {
+    /// Open the folder and list what is in it.
+    #[default]
+    Open,
+    /// Play every track under the folder, as the play_folder key does.
+    Play,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShuffleWeighting {
+    /// Every track is as likely as any other.
+    #[default]
+    Uniform,
+    /// Favorites a bit more often, recently played tracks less often.
+    Balanced,
+    /// Favorites much more often.
+    Favorites,
+    /// Tracks that aren't favorites and haven't been played lately.
+    Discover,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BadFileAction {
+    /// Move on to the next file that isn't flagged.
+    #[default]
+    Skip,
+    /// Stop playback and report the file.
+    Stop,
+    /// Try to play it again anyway.
+    Play,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeFormat {
+    /// `m:ss`, switching to `h:mm:ss` for an hour or more.
+    #[default]
+    Auto,
+    /// Always `m:ss`, with minutes running past 59.
+    Minutes,
+    /// Always `h:mm:ss`.
+    Hours,
+    /// `1h23m`, `4m05s` or `42s`.
+    Compact,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeName {
+    #[default]
+    Dark,
+    Light,
+    /// No colors; states are told apart by bold, underline and reverse video.
+    Monochrome,
+}
+
+impl ThemeName {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(ThemeName::Dark),
+            "light" => Some(ThemeName::Light),
+            "monochrome" => Some(ThemeName::Monochrome),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            follow_play: true,
+            video_containers: VideoContainers::default(),
+            now_playing_file: None,
+            now_playing_format: String::from("{stem}"),
+            exclude: Vec::new(),
+            include: Vec::new(),
+            pinned_dirs: Vec::new(),
+            on_empty_search_enter: EmptySearchEnter::default(),
+            folder_enter: FolderEnter::default(),
+            on_bad_file: BadFileAction::default(),
+            shuffle_weighting: ShuffleWeighting::default(),
+            pause_on_suspend: true,
+            resume_on_wake: false,
+            mpris: true,
+            confirm_quit: false,
+            confirm_playlist_replace: true,
+            time_format: TimeFormat::default(),
+            show_track_numbers: false,
+            prewarm_audio: false,
+            sample_rate: None,
+            pause_on_underrun: false,
+            rebuffer_ms: 2000,
+            resume_rewind_secs: 0,
+            inter_track_delay_secs: 0,
+            fade_ms: 0,
+            crossfade_ms: 0,
+            skip_silence: false,
+            limiter: false,
+            silence_threshold_db: -60.0,
+            min_silence_ms: 2000,
+            theme: ThemeName::default(),
+            theme_file: None,
+            theme_follow_system: false,
+            max_volume_percent: 100,
+            default_directory: None,
+            pick_directory_on_start: false,
+            pause_at_zero_volume: false,
+            sleep_timer_quit: false,
+            remember_position_min_minutes: 10,
+            output_device: None,
+            control_socket: None,
+            visualizer: false,
+            normalize: false,
+            album_art: true,
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("oxiplayer").join("config.toml"))
+    }
+
+    /// `max_volume_percent` as a sink gain, never below 100%.
+    pub fn max_volume(&self) -> f32 {
+        self.max_volume_percent.max(100) as f32 / 100.0
+    }
+
+    /// Loads the config file, falling back to defaults if it is missing or invalid.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+}
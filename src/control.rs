@@ -0,0 +1,109 @@
+//! A Unix socket that takes line-based commands from other processes, e.g.
+//! a hotkey daemon: `echo next | nc -U /tmp/oxiplayer.sock`.
+
+use crate::commands::Command;
+use anyhow::{bail, Result};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// How long `status` waits for the player to answer.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+/// A client that sends nothing for this long is dropped so the next one
+/// can connect.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The listening socket; the socket file is removed when this is dropped.
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("Could not remove control socket {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Listens on `path`, forwarding commands to the player. Clients are served
+/// one after another on a background thread. A socket file left behind by
+/// a crashed run is replaced; one another instance is listening on is not.
+pub fn spawn(path: &Path, commands: Sender<Command>) -> Result<ControlSocket> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!("another instance is listening on {}", path.display());
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    log::info!("Listening for commands on {}", path.display());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = serve_client(stream, &commands) {
+                        log::debug!("Control client dropped: {}", e);
+                    }
+                }
+                Err(e) => log::warn!("Control socket accept failed: {}", e),
+            }
+        }
+    });
+    Ok(ControlSocket { path: path.to_path_buf() })
+}
+
+/// Answers each line from the client with `ok`, `error: ...` or, for
+/// `status`, the status line.
+fn serve_client(stream: UnixStream, commands: &Sender<Command>) -> io::Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(writer, "{}", handle_line(&line, commands))?;
+    }
+    Ok(())
+}
+
+fn handle_line(line: &str, commands: &Sender<Command>) -> String {
+    let mut words = line.split_whitespace();
+    let command = match (words.next(), words.next(), words.next()) {
+        (Some("play"), None, _) => Command::Play,
+        (Some("pause"), None, _) => Command::Pause,
+        (Some("toggle"), None, _) => Command::TogglePause,
+        (Some("stop"), None, _) => Command::Stop,
+        (Some("next"), None, _) => Command::Next,
+        (Some("prev" | "previous"), None, _) => Command::Previous,
+        (Some("volume"), Some(level), None) => match level.trim_end_matches('%').parse() {
+            Ok(percent) => Command::SetVolume(percent),
+            Err(_) => return format!("error: invalid volume '{}'", level),
+        },
+        (Some("status"), None, _) => {
+            let (reply_tx, reply) = mpsc::channel();
+            if commands.send(Command::Status(reply_tx)).is_err() {
+                return String::from("error: the player has quit");
+            }
+            return reply
+                .recv_timeout(REPLY_TIMEOUT)
+                .unwrap_or_else(|_| String::from("error: no reply from the player"));
+        }
+        _ => {
+            return format!(
+                "error: unknown command '{}' (play, pause, toggle, stop, next, prev, volume N, status)",
+                line.trim()
+            )
+        }
+    };
+    match commands.send(command) {
+        Ok(()) => String::from("ok"),
+        Err(_) => String::from("error: the player has quit"),
+    }
+}
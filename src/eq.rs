@@ -0,0 +1,272 @@
+//! A 10-band graphic equalizer, run as a source in the playback chain.
+
+use rodio::source::SeekError;
+use rodio::Source;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Centre frequencies of the bands, an octave apart.
+pub const BANDS: [f32; 10] = [31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+
+/// Short names for the bands, for the EQ panel.
+pub const BAND_LABELS: [&str; 10] = ["31", "62", "125", "250", "500", "1k", "2k", "4k", "8k", "16k"];
+
+/// Band gains are kept within ±12 dB.
+pub const MAX_GAIN_DB: f32 = 12.0;
+
+/// Bandwidth of each peaking filter; about an octave so neighbouring
+/// bands overlap without leaving dips between them.
+const Q: f32 = 1.41;
+
+pub type Gains = [f32; BANDS.len()];
+
+/// Built-in band settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Flat,
+    BassBoost,
+    Vocal,
+    Treble,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [Preset::Flat, Preset::BassBoost, Preset::Vocal, Preset::Treble];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Preset::Flat => "Flat",
+            Preset::BassBoost => "Bass Boost",
+            Preset::Vocal => "Vocal",
+            Preset::Treble => "Treble",
+        }
+    }
+
+    pub fn gains(self) -> Gains {
+        match self {
+            Preset::Flat => [0.0; BANDS.len()],
+            Preset::BassBoost => [6.0, 6.0, 5.0, 3.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            Preset::Vocal => [-2.0, -2.0, -1.0, 0.0, 2.0, 4.0, 4.0, 2.0, 0.0, -1.0],
+            Preset::Treble => [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.0, 4.0, 5.0, 6.0],
+        }
+    }
+
+    /// The preset after this one, wrapping around.
+    pub fn next(self) -> Preset {
+        let index = Preset::ALL.iter().position(|&preset| preset == self).unwrap_or(0);
+        Preset::ALL[(index + 1) % Preset::ALL.len()]
+    }
+
+    /// The preset whose bands are exactly `gains`, if any.
+    pub fn matching(gains: &Gains) -> Option<Preset> {
+        Preset::ALL.into_iter().find(|preset| &preset.gains() == gains)
+    }
+}
+
+/// Band gains shared between the player and the audio thread.
+#[derive(Debug)]
+pub struct EqSettings {
+    /// `f32` bits, in dB.
+    gains: [AtomicU32; BANDS.len()],
+    /// Bumped on every change so the filters only recompute when needed.
+    version: AtomicU64,
+}
+
+impl Default for EqSettings {
+    fn default() -> Self {
+        EqSettings {
+            gains: std::array::from_fn(|_| AtomicU32::new(0f32.to_bits())),
+            version: AtomicU64::new(0),
+        }
+    }
+}
+
+impl EqSettings {
+    pub fn gains(&self) -> Gains {
+        std::array::from_fn(|band| f32::from_bits(self.gains[band].load(Ordering::Relaxed)))
+    }
+
+    /// Sets band `index` to `gain_db`, clamped to ±[`MAX_GAIN_DB`]. Out of
+    /// range bands are ignored.
+    pub fn set_band(&self, index: usize, gain_db: f32) {
+        if let Some(gain) = self.gains.get(index) {
+            gain.store(gain_db.clamp(-MAX_GAIN_DB, MAX_GAIN_DB).to_bits(), Ordering::Relaxed);
+            self.version.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.gains().iter().all(|&gain| gain == 0.0)
+    }
+
+    fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+}
+
+/// Normalized coefficients of one peaking biquad (RBJ cookbook).
+#[derive(Debug, Clone, Copy)]
+struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Coefficients {
+    /// `None` when the band has no effect: no gain, or above what the
+    /// sample rate can carry.
+    fn peaking(frequency: f32, gain_db: f32, sample_rate: u32) -> Option<Self> {
+        if gain_db == 0.0 || frequency >= sample_rate as f32 / 2.0 {
+            return None;
+        }
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate as f32;
+        let alpha = w0.sin() / (2.0 * Q);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha / a;
+        Some(Coefficients {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * cos / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha / a) / a0,
+        })
+    }
+}
+
+/// Filter memory for one band on one channel (transposed direct form II,
+/// which tolerates coefficient changes between samples).
+#[derive(Debug, Clone, Copy, Default)]
+struct FilterState {
+    z1: f32,
+    z2: f32,
+}
+
+impl FilterState {
+    #[inline]
+    fn process(&mut self, c: &Coefficients, x: f32) -> f32 {
+        let y = c.b0 * x + self.z1;
+        self.z1 = c.b1 * x - c.a1 * y + self.z2;
+        self.z2 = c.b2 * x - c.a2 * y;
+        y
+    }
+}
+
+/// Wraps a 16-bit source and runs it through the bands in an [`EqSettings`].
+/// Changes are picked up at the next frame, keeping the filter memory so
+/// there is no click; with every band flat the samples pass straight through.
+pub struct Equalizer<S> {
+    inner: S,
+    settings: Arc<EqSettings>,
+    /// Version of `settings` the coefficients were computed from.
+    version: u64,
+    sample_rate: u32,
+    /// One entry per active band.
+    coefficients: Vec<(usize, Coefficients)>,
+    /// Indexed by channel, then band.
+    states: Vec<[FilterState; BANDS.len()]>,
+    /// Scales the input down by the largest boost so boosted bands don't clip.
+    preamp: f32,
+    /// Channel of the next sample within its frame.
+    channel: usize,
+}
+
+impl<S> Equalizer<S>
+where
+    S: Source<Item = i16>,
+{
+    pub fn new(inner: S, settings: Arc<EqSettings>) -> Self {
+        let mut eq = Equalizer {
+            inner,
+            settings,
+            version: 0,
+            sample_rate: 0,
+            coefficients: Vec::new(),
+            states: Vec::new(),
+            preamp: 1.0,
+            channel: 0,
+        };
+        eq.update();
+        eq
+    }
+
+    /// Recomputes the filters from the settings and the current stream format.
+    fn update(&mut self) {
+        self.version = self.settings.version();
+        self.sample_rate = self.inner.sample_rate();
+        let gains = self.settings.gains();
+        self.coefficients = BANDS
+            .iter()
+            .zip(gains)
+            .enumerate()
+            .filter_map(|(band, (&frequency, gain))| {
+                Coefficients::peaking(frequency, gain, self.sample_rate).map(|c| (band, c))
+            })
+            .collect();
+        let boost = gains.iter().copied().fold(0.0, f32::max);
+        self.preamp = 10f32.powf(-boost / 20.0);
+        let channels = self.inner.channels().max(1) as usize;
+        if self.coefficients.is_empty() {
+            self.states.clear();
+        } else {
+            self.states.resize(channels, Default::default());
+        }
+    }
+}
+
+impl<S> Iterator for Equalizer<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        if self.channel == 0
+            && (self.settings.version() != self.version || self.inner.sample_rate() != self.sample_rate)
+        {
+            self.update();
+        }
+        let sample = self.inner.next()?;
+        let channel = self.channel;
+        self.channel += 1;
+        if self.channel >= self.inner.channels().max(1) as usize {
+            self.channel = 0;
+        }
+        let Some(states) = self.states.get_mut(channel) else {
+            return Some(sample);
+        };
+        let mut value = sample as f32 * self.preamp;
+        for (band, coefficients) in &self.coefficients {
+            value = states[*band].process(coefficients, value);
+        }
+        Some(value.clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+    }
+}
+
+impl<S> Source for Equalizer<S>
+where
+    S: Source<Item = i16>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+    fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(position)
+    }
+}
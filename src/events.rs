@@ -0,0 +1,33 @@
+use crate::MusicFile;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+/// Playback changes broadcast to background subscribers.
+#[derive(Debug, Clone)]
+pub enum PlayerEvent {
+    TrackStarted(MusicFile),
+    Stopped,
+    /// Paused at this position in the track.
+    Paused(Duration),
+    /// Playing again from this position.
+    Resumed(Duration),
+    /// Jumped to this position.
+    Seeked(Duration),
+}
+
+/// Fans player events out to every subscriber, dropping ones that have hung up.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Vec<Sender<PlayerEvent>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self, subscriber: Sender<PlayerEvent>) {
+        self.subscribers.push(subscriber);
+    }
+
+    pub fn emit(&mut self, event: PlayerEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
@@ -0,0 +1,60 @@
+use crate::audio;
+use crate::config::TimeFormat;
+use crate::template::format_duration;
+use anyhow::Result;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Bytes from the start of a file that go into its content hash.
+const HASH_PREFIX_LEN: u64 = 1024 * 1024;
+
+/// Details shown by the "identify this file" overlay.
+#[derive(Debug, Clone)]
+pub struct FileIdentity {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<String>,
+    /// blake3 of the first MiB plus the file size, cheap enough to compute on demand.
+    pub hash: String,
+    pub format: String,
+}
+
+pub fn identify(path: &Path, time_format: TimeFormat) -> Result<FileIdentity> {
+    let path = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&path)?;
+    let size = metadata.len();
+    let modified = metadata
+        .modified()
+        .ok()
+        .map(|time| humantime::format_rfc3339_seconds(time).to_string());
+
+    let mut prefix = Vec::new();
+    File::open(&path)?
+        .take(HASH_PREFIX_LEN)
+        .read_to_end(&mut prefix)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&prefix);
+    hasher.update(&size.to_le_bytes());
+    let hash = hasher.finalize().to_hex().to_string();
+
+    let format = match audio::probe(&path) {
+        Ok(info) => format!(
+            "{} Hz, {} ch, {} via {}",
+            info.sample_rate,
+            info.channels,
+            info.duration
+                .map_or_else(|| String::from("--:--"), |d| format_duration(d, time_format)),
+            info.backend
+        ),
+        Err(e) => format!("Undecodable ({})", e),
+    };
+
+    Ok(FileIdentity {
+        path,
+        size,
+        modified,
+        hash,
+        format,
+    })
+}
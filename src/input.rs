@@ -0,0 +1,439 @@
+use crate::app::{App, Mode, View};
+use crate::eq::{self, Preset};
+use crate::keybindings::Action;
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::{Margin, Rect};
+
+/// Rows PgUp/PgDn move through the status log.
+const STATUS_LOG_PAGE: usize = 10;
+
+/// What the caller's loop should do after a key has been handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
+/// Applies a key press to `app` using OxiPlayer's default bindings.
+pub fn handle_key(app: &mut App, key: KeyEvent) -> Result<Control> {
+    if key.kind != KeyEventKind::Press {
+        return Ok(Control::Continue);
+    }
+
+    match app.mode {
+        Mode::PinnedDirs { selected } => {
+            handle_pinned_dirs_key(app, key.code, selected)?;
+            return Ok(Control::Continue);
+        }
+        Mode::Search => {
+            handle_search_key(app, key.code)?;
+            return Ok(Control::Continue);
+        }
+        Mode::VolumeEntry => {
+            handle_volume_entry_key(app, key.code);
+            return Ok(Control::Continue);
+        }
+        Mode::SeekEntry => {
+            handle_seek_entry_key(app, key.code);
+            return Ok(Control::Continue);
+        }
+        Mode::ConfirmTrash => {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => app.trash_selected(),
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.mode = Mode::Normal,
+                _ => {}
+            }
+            return Ok(Control::Continue);
+        }
+        Mode::ConfirmPlaylist => {
+            match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => app.apply_pending_playlist(false),
+                KeyCode::Char('a') | KeyCode::Char('A') => app.apply_pending_playlist(true),
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.cancel_pending_playlist(),
+                _ => {}
+            }
+            return Ok(Control::Continue);
+        }
+        Mode::ConfirmQuit => {
+            return Ok(match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => Control::Quit,
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    app.mode = Mode::Normal;
+                    Control::Continue
+                }
+                _ => Control::Continue,
+            });
+        }
+        Mode::Identify => {
+            // Any key dismisses the identity card
+            app.mode = Mode::Normal;
+            app.identity = None;
+            return Ok(Control::Continue);
+        }
+        Mode::DirPicker { selected } => {
+            handle_dir_picker_key(app, key.code, selected)?;
+            return Ok(Control::Continue);
+        }
+        Mode::Devices { selected } => {
+            handle_devices_key(app, key.code, selected);
+            return Ok(Control::Continue);
+        }
+        Mode::Equalizer { selected } => {
+            handle_equalizer_key(app, key.code, selected);
+            return Ok(Control::Continue);
+        }
+        Mode::History { selected } => {
+            handle_history_key(app, key.code, selected)?;
+            return Ok(Control::Continue);
+        }
+        Mode::Queue { selected } => {
+            handle_queue_key(app, key.code, selected)?;
+            return Ok(Control::Continue);
+        }
+        Mode::StatusLog { selected } => {
+            handle_status_log_key(app, key.code, selected);
+            return Ok(Control::Continue);
+        }
+        Mode::Stats => {
+            app.mode = Mode::Normal;
+            return Ok(Control::Continue);
+        }
+        Mode::Normal => {}
+    }
+
+    match app.keys.action(&key) {
+        Some(action) => return run_action(app, action),
+        None => match key.code {
+            KeyCode::Esc if app.view != View::Normal => app.view = View::Normal,
+            KeyCode::Esc if !app.search_query.is_empty() => app.clear_search(),
+            KeyCode::Backspace if app.browser.is_some() => app.browser_up(),
+            _ => {}
+        },
+    }
+    Ok(Control::Continue)
+}
+
+/// Applies a mouse event in the main view: the wheel moves the selection,
+/// a click selects a list row (a double click plays it), and clicking or
+/// dragging on the progress bar seeks.
+pub fn handle_mouse(app: &mut App, mouse: MouseEvent) -> Result<()> {
+    if app.mode != Mode::Normal {
+        return Ok(());
+    }
+    let areas = app.hit_areas.get();
+    let list = areas.list.inner(&Margin { vertical: 1, horizontal: 1 });
+    let progress = areas.progress.inner(&Margin { vertical: 1, horizontal: 1 });
+    let fraction = |column: u16| {
+        (column.saturating_sub(progress.x) as f64 + 0.5) / progress.width.max(1) as f64
+    };
+    match mouse.kind {
+        MouseEventKind::ScrollDown => {
+            run_action(app, Action::Down)?;
+        }
+        MouseEventKind::ScrollUp => {
+            run_action(app, Action::Up)?;
+        }
+        MouseEventKind::Down(MouseButton::Left) => {
+            if contains(list, mouse.column, mouse.row) {
+                app.click_list_row((mouse.row - list.y) as usize, mouse.row)?;
+            } else if contains(progress, mouse.column, mouse.row) {
+                app.drag_seek(fraction(mouse.column));
+            }
+        }
+        MouseEventKind::Drag(MouseButton::Left) if app.seek_drag.is_some() => {
+            app.drag_seek(fraction(mouse.column));
+        }
+        MouseEventKind::Up(MouseButton::Left) => app.finish_seek_drag(),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn contains(area: Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.right() && row >= area.y && row < area.bottom()
+}
+
+/// Performs a bound action from the main view.
+pub fn run_action(app: &mut App, action: Action) -> Result<Control> {
+    if app.browser.is_some() && run_browser_action(app, action)? {
+        return Ok(Control::Continue);
+    }
+    match action {
+        Action::Quit if app.request_quit() => return Ok(Control::Quit),
+        Action::Quit => {}
+        Action::Down => app.next(),
+        Action::Up => app.previous(),
+        Action::PageDown => app.page_down(),
+        Action::PageUp => app.page_up(),
+        Action::First => app.go_first(),
+        Action::Last => app.go_last(),
+        Action::JumpToPlaying => app.jump_to_playing(),
+        Action::Play => app.play_selected()?,
+        Action::Stop => app.stop(),
+        Action::TogglePause => app.toggle_pause(),
+        Action::Next => app.play_next()?,
+        Action::Previous => app.play_previous()?,
+        Action::Restart => app.restart_current(),
+        Action::ResumeSession => app.resume_last_session()?,
+        Action::SeekBackward => app.seek_backward(),
+        Action::SeekForward => app.seek_forward(),
+        Action::LoopStart => app.set_loop_a(),
+        Action::LoopEnd => app.set_loop_b(),
+        Action::ClearLoop => app.clear_loop(),
+        Action::CycleRepeat => app.cycle_repeat(),
+        Action::ToggleShuffle => app.toggle_shuffle(),
+        Action::CycleSort => app.cycle_sort(),
+        Action::ReverseSort => app.toggle_sort_direction(),
+        Action::Enqueue => app.enqueue_selected(),
+        Action::ClearQueue => app.clear_queue(),
+        Action::ShowQueue => app.toggle_queue_panel(),
+        Action::StatusLog => app.toggle_status_log(),
+        Action::Trash => app.request_trash_selected(),
+        Action::EnqueueAll => app.enqueue_visible(),
+        Action::GoTo => app.start_seek_entry(),
+        Action::SavePlaylist => app.save_playlist(),
+        Action::LoadPlaylist => app.load_saved_playlist(),
+        Action::VolumeUp => app.volume_up(),
+        Action::VolumeDown => app.volume_down(),
+        Action::Mute => app.toggle_mute(),
+        Action::SetVolume => app.start_volume_entry(),
+        Action::Normalize => app.toggle_normalize(),
+        Action::SkipSilence => app.toggle_skip_silence(),
+        Action::Limiter => app.toggle_limiter(),
+        Action::TrackGainUp => app.adjust_track_gain(1.0),
+        Action::TrackGainDown => app.adjust_track_gain(-1.0),
+        Action::ClearTrackGain => app.clear_track_gain(),
+        Action::TrackCrossfade => app.cycle_track_crossfade(),
+        Action::SpeedUp => app.speed_up(),
+        Action::SpeedDown => app.speed_down(),
+        Action::Refresh => app.refresh_files()?,
+        Action::RescanFolder => app.refresh_selected_folder(),
+        Action::Trim => app.trim_to_playable(),
+        Action::Search => app.start_search(),
+        Action::PinnedDirs => app.open_pinned_dirs(),
+        Action::BrowseDirectory => app.open_dir_picker(),
+        Action::OutputDevice => app.open_device_picker(),
+        Action::Equalizer => app.open_equalizer(),
+        Action::Identify => app.identify_selected(),
+        Action::TreeView => app.toggle_tree_view(),
+        Action::PlayFolder => app.play_selected_folder()?,
+        Action::SleepTimer => app.cycle_sleep_timer(),
+        Action::StopAfter => app.toggle_stop_after_selected(),
+        Action::StopAfterCurrent => app.toggle_stop_after_current(),
+        Action::Favorite => app.toggle_favorite_selected(),
+        Action::FavoritesOnly => app.toggle_favorites_only(),
+        Action::History => app.open_history(),
+        Action::LibraryStats => app.show_library_stats(),
+        Action::Visualizer => app.toggle_visualizer(),
+        Action::AlbumArt => app.toggle_album_art(),
+        Action::FollowSystemTheme => app.toggle_follow_system_theme(),
+        Action::Banner => app.view = app.view.toggled(View::Banner),
+        Action::NowPlaying => app.view = app.view.toggled(View::NowPlaying),
+        Action::TickRateUp => app.tick_rate_up(),
+        Action::TickRateDown => app.tick_rate_down(),
+        Action::Debug => app.show_debug = !app.show_debug,
+        Action::StressMode => app.toggle_stress_mode(),
+    }
+    Ok(Control::Continue)
+}
+
+/// Actions that mean something different in the folder view. Returns
+/// `false` for the rest, which act as in the flat list.
+fn run_browser_action(app: &mut App, action: Action) -> Result<bool> {
+    match action {
+        Action::Down => app.browser_move(1),
+        Action::Up => app.browser_move(-1),
+        Action::PageDown => app.browser_page_down(),
+        Action::PageUp => app.browser_page_up(),
+        Action::First => app.browser_move(isize::MIN),
+        Action::Last => app.browser_move(isize::MAX),
+        Action::Play => app.browser_activate()?,
+        Action::Enqueue => app.browser_enqueue(),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn handle_search_key(app: &mut App, code: KeyCode) -> Result<()> {
+    match code {
+        KeyCode::Esc => app.clear_search(),
+        KeyCode::Enter => app.submit_search()?,
+        KeyCode::Backspace => app.search_pop(),
+        KeyCode::Down => app.next(),
+        KeyCode::Up => app.previous(),
+        KeyCode::PageDown => app.page_down(),
+        KeyCode::PageUp => app.page_up(),
+        KeyCode::Char(c) => app.search_push(c),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_volume_entry_key(app: &mut App, code: KeyCode) {
+    match code {
+        KeyCode::Esc => app.cancel_volume_entry(),
+        KeyCode::Enter => app.submit_volume_entry(),
+        KeyCode::Backspace => app.volume_entry_pop(),
+        KeyCode::Char(c) => app.volume_entry_push(c),
+        _ => {}
+    }
+}
+
+fn handle_seek_entry_key(app: &mut App, code: KeyCode) {
+    match code {
+        KeyCode::Esc => app.cancel_seek_entry(),
+        KeyCode::Enter => app.submit_seek_entry(),
+        KeyCode::Backspace => app.seek_entry_pop(),
+        KeyCode::Char(c) => app.seek_entry_push(c),
+        _ => {}
+    }
+}
+
+fn handle_pinned_dirs_key(app: &mut App, code: KeyCode, selected: usize) -> Result<()> {
+    let count = app.config.pinned_dirs.len();
+    match code {
+        KeyCode::Esc | KeyCode::Char('d') | KeyCode::Char('q') => app.mode = Mode::Normal,
+        KeyCode::Down | KeyCode::Char('j') => {
+            app.mode = Mode::PinnedDirs { selected: (selected + 1) % count };
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.mode = Mode::PinnedDirs { selected: (selected + count - 1) % count };
+        }
+        KeyCode::Enter => {
+            let dir = app.config.pinned_dirs[selected].clone();
+            app.change_directory(dir)?;
+        }
+        KeyCode::Char(c @ '1'..='9') => {
+            let index = c as usize - '1' as usize;
+            if let Some(dir) = app.config.pinned_dirs.get(index).cloned() {
+                app.change_directory(dir)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_devices_key(app: &mut App, code: KeyCode, selected: usize) {
+    let count = app.output_devices.len();
+    match code {
+        KeyCode::Esc | KeyCode::Char('O') | KeyCode::Char('q') => app.mode = Mode::Normal,
+        KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+            app.mode = Mode::Devices { selected: (selected + 1) % count };
+        }
+        KeyCode::Up | KeyCode::Char('k') if count > 0 => {
+            app.mode = Mode::Devices { selected: (selected + count - 1) % count };
+        }
+        KeyCode::Enter => {
+            if let Some(name) = app.output_devices.get(selected).cloned() {
+                app.set_output_device(&name);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn handle_history_key(app: &mut App, code: KeyCode, selected: usize) -> Result<()> {
+    let count = app.history.len();
+    match code {
+        KeyCode::Esc | KeyCode::Char('H') | KeyCode::Char('q') => app.mode = Mode::Normal,
+        KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+            app.mode = Mode::History { selected: (selected + 1) % count };
+        }
+        KeyCode::Up | KeyCode::Char('k') if count > 0 => {
+            app.mode = Mode::History { selected: (selected + count - 1) % count };
+        }
+        KeyCode::Enter => app.play_from_history(selected)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_queue_key(app: &mut App, code: KeyCode, selected: usize) -> Result<()> {
+    let count = app.queue.len();
+    match code {
+        KeyCode::Esc | KeyCode::Char('Q') | KeyCode::Char('q') => app.mode = Mode::Normal,
+        KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+            app.mode = Mode::Queue { selected: (selected + 1) % count };
+        }
+        KeyCode::Up | KeyCode::Char('k') if count > 0 => {
+            app.mode = Mode::Queue { selected: (selected + count - 1) % count };
+        }
+        KeyCode::Enter => app.play_from_queue(selected)?,
+        KeyCode::Char('d') | KeyCode::Delete => app.remove_from_queue(selected),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn handle_status_log_key(app: &mut App, code: KeyCode, selected: usize) {
+    let last = app.status_log.len().saturating_sub(1);
+    let selected = match code {
+        KeyCode::Esc | KeyCode::Char('l') | KeyCode::Char('q') => {
+            app.mode = Mode::Normal;
+            return;
+        }
+        KeyCode::Down | KeyCode::Char('j') => selected + 1,
+        KeyCode::Up | KeyCode::Char('k') => selected.saturating_sub(1),
+        KeyCode::PageDown => selected + STATUS_LOG_PAGE,
+        KeyCode::PageUp => selected.saturating_sub(STATUS_LOG_PAGE),
+        KeyCode::Home => 0,
+        KeyCode::End => last,
+        _ => return,
+    };
+    app.mode = Mode::StatusLog { selected: selected.min(last) };
+}
+
+fn handle_equalizer_key(app: &mut App, code: KeyCode, selected: usize) {
+    let last = eq::BANDS.len() - 1;
+    match code {
+        KeyCode::Esc | KeyCode::Char('E') | KeyCode::Char('q') => app.mode = Mode::Normal,
+        KeyCode::Left | KeyCode::Char('h') => {
+            app.mode = Mode::Equalizer { selected: selected.saturating_sub(1) };
+        }
+        KeyCode::Right | KeyCode::Char('l') => {
+            app.mode = Mode::Equalizer { selected: (selected + 1).min(last) };
+        }
+        KeyCode::Up | KeyCode::Char('k') => app.adjust_eq_band(selected, 1.0),
+        KeyCode::Down | KeyCode::Char('j') => app.adjust_eq_band(selected, -1.0),
+        KeyCode::Char('p') => app.cycle_eq_preset(),
+        KeyCode::Char('0') => app.set_eq_preset(Preset::Flat),
+        _ => {}
+    }
+}
+
+fn handle_dir_picker_key(app: &mut App, code: KeyCode, selected: usize) -> Result<()> {
+    let Some(ref mut picker) = app.dir_picker else {
+        app.mode = Mode::Normal;
+        return Ok(());
+    };
+    let count = picker.entries.len();
+    match code {
+        KeyCode::Esc | KeyCode::Char('q') => app.close_dir_picker(),
+        KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+            app.mode = Mode::DirPicker { selected: (selected + 1) % count };
+        }
+        KeyCode::Up | KeyCode::Char('k') if count > 0 => {
+            app.mode = Mode::DirPicker { selected: (selected + count - 1) % count };
+        }
+        KeyCode::Right | KeyCode::Char('l') => {
+            picker.enter(selected);
+            app.mode = Mode::DirPicker { selected: 0 };
+        }
+        KeyCode::Left | KeyCode::Char('h') | KeyCode::Backspace => {
+            let selected = picker.up();
+            app.mode = Mode::DirPicker { selected };
+        }
+        KeyCode::Enter => {
+            let dir = picker.entries.get(selected).unwrap_or(&picker.dir).clone();
+            app.change_directory(dir)?;
+        }
+        KeyCode::Char(' ') => {
+            let dir = picker.dir.clone();
+            app.change_directory(dir)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
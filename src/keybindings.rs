@@ -0,0 +1,334 @@
+use anyhow::{bail, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Something a key in the main view can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    First,
+    Last,
+    JumpToPlaying,
+    Play,
+    Stop,
+    TogglePause,
+    Next,
+    Previous,
+    Restart,
+    ResumeSession,
+    SeekBackward,
+    SeekForward,
+    GoTo,
+    LoopStart,
+    LoopEnd,
+    ClearLoop,
+    CycleRepeat,
+    ToggleShuffle,
+    CycleSort,
+    ReverseSort,
+    Enqueue,
+    ClearQueue,
+    ShowQueue,
+    StatusLog,
+    Trash,
+    EnqueueAll,
+    SavePlaylist,
+    LoadPlaylist,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    SetVolume,
+    Normalize,
+    SkipSilence,
+    Limiter,
+    TrackGainUp,
+    TrackGainDown,
+    ClearTrackGain,
+    TrackCrossfade,
+    SpeedUp,
+    SpeedDown,
+    Refresh,
+    RescanFolder,
+    Trim,
+    Search,
+    PinnedDirs,
+    BrowseDirectory,
+    OutputDevice,
+    Equalizer,
+    Identify,
+    TreeView,
+    PlayFolder,
+    StopAfter,
+    StopAfterCurrent,
+    Favorite,
+    History,
+    FavoritesOnly,
+    SleepTimer,
+    LibraryStats,
+    Visualizer,
+    AlbumArt,
+    FollowSystemTheme,
+    Banner,
+    NowPlaying,
+    TickRateUp,
+    TickRateDown,
+    Debug,
+    StressMode,
+}
+
+/// Every action with its name in `config.toml` and its default keys.
+const ACTIONS: &[(Action, &str, &[&str])] = &[
+    (Action::Quit, "quit", &["q"]),
+    (Action::Down, "down", &["down", "j"]),
+    (Action::Up, "up", &["up", "k"]),
+    (Action::PageDown, "page_down", &["pagedown", "ctrl-d"]),
+    (Action::PageUp, "page_up", &["pageup", "ctrl-u"]),
+    (Action::First, "first", &["home"]),
+    (Action::Last, "last", &["end"]),
+    (Action::JumpToPlaying, "jump_to_playing", &["."]),
+    (Action::Play, "play", &["enter"]),
+    (Action::Stop, "stop", &["s"]),
+    (Action::TogglePause, "pause", &["space"]),
+    (Action::Next, "next", &["n"]),
+    (Action::Previous, "previous", &["p"]),
+    (Action::Restart, "restart", &["0"]),
+    (Action::ResumeSession, "resume_session", &["c"]),
+    (Action::SeekBackward, "seek_backward", &["left"]),
+    (Action::SeekForward, "seek_forward", &["right"]),
+    (Action::GoTo, "go_to", &["g"]),
+    (Action::LoopStart, "loop_start", &["("]),
+    (Action::LoopEnd, "loop_end", &[")"]),
+    (Action::ClearLoop, "clear_loop", &["x"]),
+    (Action::CycleRepeat, "repeat", &["o"]),
+    (Action::ToggleShuffle, "shuffle", &["z"]),
+    (Action::CycleSort, "sort", &["S"]),
+    (Action::ReverseSort, "reverse_sort", &["R"]),
+    (Action::Enqueue, "enqueue", &["a"]),
+    (Action::EnqueueAll, "enqueue_all", &["ctrl-a"]),
+    (Action::ClearQueue, "clear_queue", &["A"]),
+    (Action::ShowQueue, "show_queue", &["Q"]),
+    (Action::StatusLog, "status_log", &["l"]),
+    (Action::Trash, "trash", &["X"]),
+    (Action::SavePlaylist, "save_playlist", &["W"]),
+    (Action::LoadPlaylist, "load_playlist", &["ctrl-o"]),
+    (Action::VolumeUp, "volume_up", &["+", "="]),
+    (Action::VolumeDown, "volume_down", &["-"]),
+    (Action::Mute, "mute", &["m"]),
+    (Action::SetVolume, "set_volume", &["v"]),
+    (Action::Normalize, "normalize", &["N"]),
+    (Action::SkipSilence, "skip_silence", &["Y"]),
+    (Action::Limiter, "limiter", &["M"]),
+    (Action::TrackGainUp, "track_gain_up", &["}"]),
+    (Action::TrackGainDown, "track_gain_down", &["{"]),
+    (Action::ClearTrackGain, "clear_track_gain", &["|"]),
+    (Action::TrackCrossfade, "track_crossfade", &["ctrl-f"]),
+    (Action::SpeedUp, "speed_up", &[">"]),
+    (Action::SpeedDown, "speed_down", &["<"]),
+    (Action::Refresh, "refresh", &["r"]),
+    (Action::RescanFolder, "rescan_folder", &["u"]),
+    (Action::Trim, "trim", &["t"]),
+    (Action::Search, "search", &["/"]),
+    (Action::PinnedDirs, "pinned_dirs", &["d"]),
+    (Action::BrowseDirectory, "browse_directory", &["D"]),
+    (Action::OutputDevice, "output_device", &["O"]),
+    (Action::Equalizer, "equalizer", &["E"]),
+    (Action::Identify, "identify", &["i"]),
+    (Action::TreeView, "tree_view", &["f"]),
+    (Action::PlayFolder, "play_folder", &["ctrl-p"]),
+    (Action::StopAfter, "stop_after", &["P"]),
+    (Action::StopAfterCurrent, "stop_after_current", &["ctrl-e"]),
+    (Action::Favorite, "favorite", &["*"]),
+    (Action::FavoritesOnly, "favorites_only", &["F"]),
+    (Action::History, "history", &["H"]),
+    (Action::SleepTimer, "sleep_timer", &["w"]),
+    (Action::LibraryStats, "library_stats", &["L"]),
+    (Action::Visualizer, "visualizer", &["V"]),
+    (Action::AlbumArt, "album_art", &["C"]),
+    (Action::FollowSystemTheme, "follow_system_theme", &["T"]),
+    (Action::Banner, "banner", &["B"]),
+    (Action::NowPlaying, "now_playing", &["Z"]),
+    (Action::TickRateUp, "tick_rate_up", &["]"]),
+    (Action::TickRateDown, "tick_rate_down", &["["]),
+    (Action::Debug, "debug", &["f12"]),
+    (Action::StressMode, "stress_mode", &["f11"]),
+];
+
+/// One key or a list of keys for an action in the `[keys]` table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    fn specs(&self) -> &[String] {
+        match self {
+            KeyList::One(spec) => std::slice::from_ref(spec),
+            KeyList::Many(specs) => specs,
+        }
+    }
+}
+
+/// A key with the modifiers that have to be held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Shift is part of the character for printable keys ('A' vs 'a'), and
+    /// terminals disagree on whether they also report it as a modifier.
+    fn normalized(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if matches!(code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Key { code, modifiers }
+    }
+}
+
+/// Parses a key spec such as `j`, `J`, `ctrl-d`, `alt-enter`, `shift-tab`,
+/// `space`, `pagedown` or `f5`. Modifier and key names are case-insensitive;
+/// single characters are taken as written.
+fn parse_key(spec: &str) -> Result<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = spec;
+    // A trailing '-' is the minus key itself, as in "ctrl--"
+    while let Some((modifier, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => bail!("unknown modifier '{}' in '{}'", modifier, spec),
+        };
+        rest = key;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::Char(c.to_ascii_uppercase()),
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest.to_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                Some(n @ 1..=24) => KeyCode::F(n),
+                _ => bail!("unknown key '{}'", spec),
+            },
+        },
+    };
+    Ok(Key::normalized(code, modifiers))
+}
+
+/// Maps keys in the main view to actions.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    map: HashMap<Key, Action>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings::new(&HashMap::new()).0
+    }
+}
+
+impl KeyBindings {
+    /// Builds the bindings from the defaults, with each action named in
+    /// `overrides` taking only the keys given there. Problems are returned as
+    /// messages and the offending entries skipped.
+    pub fn new(overrides: &HashMap<String, KeyList>) -> (Self, Vec<String>) {
+        let mut errors = Vec::new();
+        for name in overrides.keys() {
+            if !ACTIONS.iter().any(|&(_, action_name, _)| action_name == name) {
+                errors.push(format!("unknown action '{}'", name));
+            }
+        }
+
+        let mut map = HashMap::new();
+        for &(action, name, defaults) in ACTIONS {
+            if !overrides.contains_key(name) {
+                for spec in defaults {
+                    map.insert(parse_key(spec).expect("default key specs are valid"), action);
+                }
+            }
+        }
+        // Configured keys go in last so they win over another action's default
+        for &(action, name, _) in ACTIONS {
+            let Some(keys) = overrides.get(name) else {
+                continue;
+            };
+            for spec in keys.specs() {
+                match parse_key(spec) {
+                    Ok(key) => {
+                        if let Some(previous) = map.insert(key, action).filter(|&previous| previous != action) {
+                            log::info!("Key '{}' moved from {:?} to {:?}", spec, previous, action);
+                        }
+                    }
+                    Err(e) => errors.push(format!("{}: {}", name, e)),
+                }
+            }
+        }
+        (KeyBindings { map }, errors)
+    }
+
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        self.map.get(&Key::normalized(key.code, key.modifiers)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn default_keys_are_bound_once() {
+        let mut seen = HashMap::new();
+        for &(action, _, defaults) in ACTIONS {
+            for spec in defaults {
+                let key = parse_key(spec).unwrap();
+                if let Some(other) = seen.insert(key, action) {
+                    panic!("'{}' is a default for both {:?} and {:?}", spec, other, action);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn readme_example_leaves_the_defaults_alone() {
+        let overrides = HashMap::from([
+            ("down".to_string(), KeyList::Many(vec!["down".into(), "j".into(), "ctrl-n".into()])),
+            ("up".to_string(), KeyList::Many(vec!["up".into(), "k".into(), "ctrl-p".into()])),
+        ]);
+        let (bindings, errors) = KeyBindings::new(&overrides);
+        assert!(errors.is_empty());
+        assert_eq!(bindings.action(&ctrl('p')), Some(Action::Up));
+        assert_eq!(bindings.action(&ctrl('n')), Some(Action::Down));
+        assert_eq!(bindings.action(&ctrl('e')), Some(Action::StopAfterCurrent));
+    }
+}
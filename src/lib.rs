@@ -0,0 +1,76 @@
+//! OxiPlayer's player state, audio engine and UI, usable from other ratatui
+//! applications.
+//!
+//! The `oxiplayer` binary is a thin loop around this crate. A host app can
+//! embed the player the same way:
+//!
+//! ```no_run
+//! use oxiplayer::input::{self, Control};
+//! use oxiplayer::{ui, App};
+//! use std::path::PathBuf;
+//!
+//! # fn run<B: ratatui::backend::Backend>(terminal: &mut ratatui::Terminal<B>) -> anyhow::Result<()> {
+//! let mut app = App::new(PathBuf::from("/music"))?;
+//! loop {
+//!     terminal.draw(|f| {
+//!         let area = f.size();
+//!         ui::render_into(f, area, &app);
+//!     })?;
+//!
+//!     // Auto-advance, background scan results and other per-frame bookkeeping
+//!     app.tick()?;
+//!
+//!     if crossterm::event::poll(app.poll_timeout())? {
+//!         if let crossterm::event::Event::Key(key) = crossterm::event::read()? {
+//!             if input::handle_key(&mut app, key)? == Control::Quit {
+//!                 break;
+//!             }
+//!         }
+//!     }
+//! }
+//! app.save_state()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Hosts with their own key bindings can call the `App` methods directly
+//! (`play_selected`, `toggle_pause`, `play_next`, `volume_up`, ...) instead of
+//! going through [`input::handle_key`].
+
+pub mod app;
+pub mod art;
+pub mod audio;
+pub mod browser;
+pub mod commands;
+pub mod config;
+#[cfg(unix)]
+pub mod control;
+pub mod eq;
+pub mod events;
+pub mod identify;
+pub mod input;
+pub mod keybindings;
+pub mod limiter;
+pub mod logging;
+pub mod picker;
+pub mod playlist;
+#[cfg(all(feature = "logind", target_os = "linux"))]
+mod logind;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+mod mpris;
+mod nowplaying;
+pub mod scan;
+mod sources;
+pub mod state;
+pub mod stats;
+pub mod tags;
+pub mod template;
+pub mod theme;
+pub mod trim;
+pub mod ui;
+pub mod visualizer;
+pub mod watch;
+
+pub use app::{App, Mode, MusicFile, RepeatMode, View};
+pub use audio::AudioPlayer;
+pub use commands::Command;
@@ -0,0 +1,203 @@
+//! A soft limiter, run as a source in the playback chain, that rounds off
+//! peaks the sink volume would otherwise push past full scale.
+
+use rodio::source::SeekError;
+use rodio::Source;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Output level, as a fraction of full scale, where limiting starts; below
+/// it samples pass unchanged.
+const THRESHOLD: f32 = 0.8;
+
+/// Maps a sample at `level` (1.0 is full scale) onto the limiter's curve:
+/// unchanged up to [`THRESHOLD`], then bent with `tanh` so it approaches
+/// full scale without ever reaching it. The slope is continuous at the
+/// threshold, so there is no audible knee.
+pub fn soft_clip(level: f32) -> f32 {
+    let magnitude = level.abs();
+    if magnitude <= THRESHOLD {
+        return level;
+    }
+    let headroom = 1.0 - THRESHOLD;
+    let limited = THRESHOLD + headroom * ((magnitude - THRESHOLD) / headroom).tanh();
+    limited.copysign(level)
+}
+
+/// Limiter switch and the sink volume it limits against, shared between
+/// the player and the audio thread.
+#[derive(Debug)]
+pub struct LimiterSettings {
+    enabled: AtomicBool,
+    /// `f32` bits of the sink volume.
+    gain: AtomicU32,
+    /// Samples bent so far, for showing when the limiter engages.
+    limited: AtomicU64,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        LimiterSettings {
+            enabled: AtomicBool::new(false),
+            gain: AtomicU32::new(1f32.to_bits()),
+            limited: AtomicU64::new(0),
+        }
+    }
+}
+
+impl LimiterSettings {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Tells the limiter the volume the sink plays at.
+    pub fn set_gain(&self, gain: f32) {
+        self.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The volume last passed to [`LimiterSettings::set_gain`].
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+
+    /// How many samples have been limited since the player started; it
+    /// going up means the limiter is engaging.
+    pub fn limited(&self) -> u64 {
+        self.limited.load(Ordering::Relaxed)
+    }
+}
+
+/// Wraps a 16-bit source and limits it as it will sound after the sink
+/// volume: each sample is scaled by the volume, bent by [`soft_clip`] and
+/// scaled back, so the sink's own scaling lands it on the curve.
+pub struct Limiter<S> {
+    inner: S,
+    settings: Arc<LimiterSettings>,
+}
+
+impl<S> Limiter<S> {
+    pub fn new(inner: S, settings: Arc<LimiterSettings>) -> Self {
+        Limiter { inner, settings }
+    }
+}
+
+impl<S> Iterator for Limiter<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.inner.next()?;
+        if !self.settings.is_enabled() {
+            return Some(sample);
+        }
+        let gain = self.settings.gain();
+        let level = sample as f32 / i16::MAX as f32 * gain;
+        if gain <= 0.0 || level.abs() <= THRESHOLD {
+            return Some(sample);
+        }
+        self.settings.limited.fetch_add(1, Ordering::Relaxed);
+        let limited = soft_clip(level) / gain * i16::MAX as f32;
+        Some(limited.clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+    }
+}
+
+impl<S> Source for Limiter<S>
+where
+    S: Source<Item = i16>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+    fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn soft_clip_passes_levels_below_the_threshold() {
+        assert_eq!(soft_clip(0.5), 0.5);
+        assert_eq!(soft_clip(THRESHOLD), THRESHOLD);
+        assert_eq!(soft_clip(0.0), 0.0);
+    }
+
+    #[test]
+    fn soft_clip_bends_peaks_under_full_scale() {
+        assert!(close(soft_clip(1.0), 0.952));
+        assert!(soft_clip(2.0) < 1.0);
+        assert!(soft_clip(100.0) <= 1.0);
+        assert!(soft_clip(1.5) > soft_clip(1.0));
+    }
+
+    #[test]
+    fn soft_clip_is_odd() {
+        for level in [0.3, 0.8, 0.9, 1.0, 2.0] {
+            assert_eq!(soft_clip(-level), -soft_clip(level));
+        }
+    }
+
+    #[test]
+    fn soft_clip_is_continuous_at_the_threshold() {
+        let step = 1e-4;
+        let above = soft_clip(THRESHOLD + step);
+        // Unit slope on both sides, so the curve just carries on
+        assert!((above - (THRESHOLD + step)).abs() < 1e-6);
+        assert!(soft_clip(THRESHOLD - step) < above);
+    }
+
+    #[test]
+    fn limiter_bends_samples_the_volume_would_push_over() {
+        let settings = Arc::new(LimiterSettings::default());
+        settings.set_enabled(true);
+        settings.set_gain(2.0);
+        let input = vec![8192, 16384, -16384, 32767];
+        let output: Vec<i16> = Limiter::new(SamplesBuffer::new(1, 44_100, input), settings.clone()).collect();
+
+        // 0.25 of full scale is 0.5 after the volume, under the threshold
+        assert_eq!(output[0], 8192);
+        let expected = (soft_clip(16384.0 / 32767.0 * 2.0) / 2.0 * 32767.0) as i16;
+        assert_eq!(output[1], expected);
+        assert_eq!(output[2], -expected);
+        // Even a full-scale sample stays under full scale once doubled
+        assert!((output[3] as f32 / 32767.0 * 2.0) < 1.0);
+        assert_eq!(settings.limited(), 3);
+    }
+
+    #[test]
+    fn limiter_leaves_samples_alone_when_disabled() {
+        let settings = Arc::new(LimiterSettings::default());
+        settings.set_gain(2.0);
+        let input = vec![8192, 16384, -16384, 32767];
+        let output: Vec<i16> = Limiter::new(SamplesBuffer::new(1, 44_100, input.clone()), settings.clone()).collect();
+        assert_eq!(output, input);
+        assert_eq!(settings.limited(), 0);
+    }
+}
@@ -0,0 +1,45 @@
+use anyhow::Result;
+use log::LevelFilter;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Environment variable holding the log level, e.g. `OXIPLAYER_LOG=debug`.
+pub const LOG_ENV: &str = "OXIPLAYER_LOG";
+
+/// Where the log file is written, next to `config.toml`.
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("oxiplayer").join("oxiplayer.log"))
+}
+
+/// The level asked for through [`LOG_ENV`], if any. Unknown values mean `debug`.
+pub fn level_from_env() -> Option<LevelFilter> {
+    let value = std::env::var(LOG_ENV).ok()?;
+    Some(value.parse().unwrap_or(LevelFilter::Debug))
+}
+
+/// Sends `log` records at `level` and above to the log file, appending to it.
+/// Nothing goes to stdout/stderr, which belong to the TUI.
+pub fn init(level: LevelFilter) -> Result<PathBuf> {
+    let path = path().ok_or_else(|| anyhow::anyhow!("no config directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fern::Dispatch::new()
+        .format(|out, message, record| {
+            out.finish(format_args!(
+                "{} {:<5} {}: {}",
+                humantime::format_rfc3339_millis(SystemTime::now()),
+                record.level(),
+                record.target(),
+                message
+            ))
+        })
+        // Dependencies only get to report warnings and errors
+        .level(LevelFilter::Warn)
+        .level_for("oxiplayer", level)
+        .chain(fern::log_file(&path)?)
+        .apply()?;
+    Ok(path)
+}
@@ -0,0 +1,80 @@
+//! Suspend and screen-lock notifications from systemd-logind over D-Bus.
+
+use crate::commands::Command;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+trait Session {
+    #[zbus(signal)]
+    fn lock(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn unlock(&self) -> zbus::Result<()>;
+}
+
+/// Listens for sleep/wake and lock/unlock on background threads, sending
+/// [`Command::Suspend`] and [`Command::Wake`] to the player.
+pub fn spawn(commands: Sender<Command>) {
+    let sleep_commands = commands.clone();
+    thread::spawn(move || {
+        if let Err(e) = watch_sleep(sleep_commands) {
+            log::warn!("Not watching for system sleep: {}", e);
+        }
+    });
+    thread::spawn(move || {
+        if let Err(e) = watch_lock(commands) {
+            log::warn!("Not watching for session lock: {}", e);
+        }
+    });
+}
+
+fn watch_sleep(commands: Sender<Command>) -> zbus::Result<()> {
+    let connection = zbus::blocking::Connection::system()?;
+    let manager = ManagerProxyBlocking::new(&connection)?;
+    for signal in manager.receive_prepare_for_sleep()? {
+        let command = if *signal.args()?.start() {
+            Command::Suspend
+        } else {
+            Command::Wake
+        };
+        if commands.send(command).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn watch_lock(commands: Sender<Command>) -> zbus::Result<()> {
+    let connection = zbus::blocking::Connection::system()?;
+    let session = SessionProxyBlocking::new(&connection)?;
+    let unlock_commands = commands.clone();
+    let unlocks = session.receive_unlock()?;
+    thread::spawn(move || {
+        for _ in unlocks {
+            if unlock_commands.send(Command::Wake).is_err() {
+                break;
+            }
+        }
+    });
+    for _ in session.receive_lock()? {
+        if commands.send(Command::Suspend).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -1,237 +1,155 @@
 use anyhow::Result;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use ratatui::{
-    backend::CrosstermBackend,
-    widgets::ListState,
-    Terminal,
-};
+use oxiplayer::input::{self, Control};
+use oxiplayer::config::Config;
+use oxiplayer::events::PlayerEvent;
+use oxiplayer::state::State;
+use oxiplayer::theme::{self, Theme};
+#[cfg(unix)]
+use oxiplayer::control;
+use oxiplayer::{logging, playlist, ui, App, RepeatMode};
+use ratatui::{backend::CrosstermBackend, Terminal};
 use std::io;
-use std::path::PathBuf;
-use walkdir::WalkDir;
-
-mod audio;
-mod ui;
-
-use audio::AudioPlayer;
-
-#[derive(Clone)]
-pub struct MusicFile {
-    pub path: PathBuf,
-    pub name: String,
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
+
+/// A terminal music player.
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Music directories to scan and merge into one list, or an M3U playlist
+    #[arg(value_name = "DIR|PLAYLIST")]
+    paths: Vec<PathBuf>,
+    /// Only keep tracks N through M of the sorted list
+    #[arg(long, value_name = "N-M", value_parser = parse_range)]
+    range: Option<(usize, usize)>,
+    /// Play on this output device (falls back to the default if not found)
+    #[arg(long, value_name = "NAME")]
+    device: Option<String>,
+    /// dark, light, monochrome or a theme file, for this run
+    #[arg(long, value_name = "NAME|FILE")]
+    theme: Option<String>,
+    /// Start playing the first track as soon as the list is loaded
+    #[arg(long)]
+    play: bool,
+    /// Play without the UI, printing each track as it starts
+    #[arg(long, visible_alias = "no-ui")]
+    headless: bool,
+    /// Start with shuffle on
+    #[arg(long)]
+    shuffle: bool,
+    /// What happens when a track finishes
+    #[arg(long, value_name = "all|one|off", value_parser = parse_repeat)]
+    repeat: Option<RepeatMode>,
+    /// Accept commands on this Unix socket
+    #[arg(long, value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+    /// Add a scan include pattern (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+    /// Add a scan exclude pattern (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Rescan when files change under the music directory
+    #[arg(long)]
+    watch: bool,
+    /// Open the output at this rate instead of the device default
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..))]
+    sample_rate: Option<u32>,
+    /// Ask before quitting with a track playing
+    #[arg(long)]
+    confirm_quit: bool,
+    /// Write a debug log (OXIPLAYER_LOG picks another level)
+    #[arg(long)]
+    log: bool,
 }
 
-pub struct App {
-    pub music_files: Vec<MusicFile>,
-    pub selected_index: usize,
-    pub list_state: ListState,
-    pub audio_player: AudioPlayer,
-    pub current_playing: Option<String>,
-    pub status_message: String,
-    pub music_directory: PathBuf,
-    pub is_paused: bool,
-    pub volume: f32,
-}
-
-impl App {
-    pub fn new(music_dir: PathBuf) -> Result<Self> {
-        let music_files = Self::scan_music_files(&music_dir)?;
-        let mut list_state = ListState::default();
-        if !music_files.is_empty() {
-            list_state.select(Some(0));
-        }
-
-        let status_message = if music_files.is_empty() {
-            String::from("No music files found - Press 'r' to refresh or 'q' to quit")
-        } else {
-            String::from("Ready - Use ↑/↓ to navigate, Enter to play (auto-advances to next song), 'q' to quit")
-        };
-
-        Ok(App {
-            music_files,
-            selected_index: 0,
-            list_state,
-            audio_player: AudioPlayer::new()?,
-            current_playing: None,
-            status_message,
-            music_directory: music_dir,
-            is_paused: false,
-            volume: 0.7,
+fn main() -> Result<()> {
+    let mut args = Args::parse();
+    let log_level = logging::level_from_env().or(args.log.then_some(log::LevelFilter::Debug));
+    let mut music_dirs = std::mem::take(&mut args.paths);
+    let mut config = Config::load();
+    config.include.append(&mut args.include);
+    config.exclude.append(&mut args.exclude);
+    config.sample_rate = args.sample_rate.or(config.sample_rate);
+    config.confirm_quit |= args.confirm_quit;
+    let theme = args.theme.take().map(|spec| {
+        theme::resolve(&spec, Theme::named(config.theme)).unwrap_or_else(|e| {
+            eprintln!("Error: Invalid theme '{}': {:#}", spec, e);
+            std::process::exit(1);
         })
-    }
-
-    fn scan_music_files(dir: &PathBuf) -> Result<Vec<MusicFile>> {
-        let mut files = Vec::new();
-        let music_extensions = ["mp3", "wav", "flac", "ogg", "m4a", "aac"];
-
-        for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if let Some(extension) = path.extension() {
-                if let Some(ext_str) = extension.to_str() {
-                    if music_extensions.contains(&ext_str.to_lowercase().as_str()) {
-                        if let Some(name) = path.file_name() {
-                            files.push(MusicFile {
-                                path: path.to_path_buf(),
-                                name: name.to_string_lossy().to_string(),
-                            });
-                        }
-                    }
-                }
-            }
-        }
-
-        files.sort_by(|a, b| a.name.cmp(&b.name));
-        Ok(files)
-    }
-
-    pub fn next(&mut self) {
-        if !self.music_files.is_empty() {
-            self.selected_index = (self.selected_index + 1) % self.music_files.len();
-            self.list_state.select(Some(self.selected_index));
-        }
-    }
-
-    pub fn previous(&mut self) {
-        if !self.music_files.is_empty() {
-            if self.selected_index == 0 {
-                self.selected_index = self.music_files.len() - 1;
-            } else {
-                self.selected_index -= 1;
-            }
-            self.list_state.select(Some(self.selected_index));
-        }
-    }
-
-    pub fn play_selected(&mut self) -> Result<()> {
-        if self.music_files.is_empty() {
-            self.status_message = String::from("No music files available to play");
-            return Ok(());
-        }
-
-        if let Some(file) = self.music_files.get(self.selected_index) {
-            match self.audio_player.play(&file.path) {
-                Ok(_) => {
-                    self.current_playing = Some(file.name.clone());
-                    self.is_paused = false;
-                    self.audio_player.set_volume(self.volume);
-                    self.status_message = format!("♪ Playing: {}", file.name);
-                }
-                Err(e) => {
-                    self.status_message = format!("Error playing file: {}", e);
-                }
-            }
+    });
+    let control_socket = args.control_socket.take().or_else(|| config.control_socket.clone());
+    if music_dirs.is_empty() {
+        let state = State::load();
+        let saved_dirs = if state.music_roots.is_empty() {
+            state.music_directory.into_iter().collect()
         } else {
-            self.status_message = String::from("No file selected");
-        }
-        Ok(())
+            state.music_roots
+        };
+        music_dirs = match config.default_directory.clone() {
+            Some(dir) => vec![dir],
+            None => saved_dirs.into_iter().filter(|dir| dir.is_dir()).collect(),
+        };
     }
-
-    pub fn stop(&mut self) {
-        self.audio_player.stop();
-        self.current_playing = None;
-        self.is_paused = false;
-        self.status_message = String::from("Stopped");
+    let pick_directory = music_dirs.is_empty() && config.pick_directory_on_start;
+    if music_dirs.is_empty() {
+        music_dirs.push(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
     }
 
-    pub fn pause(&mut self) {
-        if self.current_playing.is_some() && !self.is_paused {
-            self.audio_player.pause();
-            self.is_paused = true;
-            self.status_message = String::from("Paused");
-        }
+    // Missing directories are skipped as long as one is left to scan
+    let (music_dirs, missing): (Vec<PathBuf>, Vec<PathBuf>) = music_dirs.into_iter().partition(|dir| dir.exists());
+    for dir in &missing {
+        eprintln!("Warning: Directory '{}' does not exist, skipping it", dir.display());
     }
-
-    pub fn resume(&mut self) {
-        if self.current_playing.is_some() && self.is_paused {
-            self.audio_player.resume();
-            self.is_paused = false;
-            if let Some(ref name) = self.current_playing {
-                self.status_message = format!("♪ Playing: {}", name);
-            }
-        }
-    }
-
-    pub fn toggle_pause(&mut self) {
-        if self.current_playing.is_some() {
-            if self.is_paused {
-                self.resume();
-            } else {
-                self.pause();
-            }
-        }
+    if music_dirs.is_empty() {
+        Args::command().error(ErrorKind::ValueValidation, "None of the given directories exist").exit();
     }
+    // A playlist is played on its own, so any other paths are ignored
+    let playlist_path = music_dirs.first().filter(|path| playlist::is_playlist(path)).cloned();
 
-    pub fn play_next(&mut self) -> Result<()> {
-        if !self.music_files.is_empty() {
-            let was_at_end = self.selected_index == self.music_files.len() - 1;
-            self.next();
-            self.play_selected()?;
-
-            // Show special message when looping back to start
-            if was_at_end {
-                if let Some(file) = self.music_files.get(0) {
-                    self.status_message = format!("♪ Looped to beginning - Playing: {}", file.name);
-                }
-            }
-        }
-        Ok(())
-    }
+    // A playlist's folder isn't the library, so there is nothing to watch
+    let watch = args.watch && playlist_path.is_none();
 
-    pub fn play_previous(&mut self) -> Result<()> {
-        if !self.music_files.is_empty() {
-            self.previous();
-            self.play_selected()?;
+    // Logging goes to a file only, since the terminal belongs to the UI
+    if let Some(level) = log_level {
+        match logging::init(level) {
+            Ok(path) => log::info!("OxiPlayer {} logging to {}", env!("CARGO_PKG_VERSION"), path.display()),
+            Err(e) => eprintln!("Warning: could not start logging: {}", e),
         }
-        Ok(())
     }
-
-    pub fn volume_up(&mut self) {
-        self.volume = (self.volume + 0.1).min(1.0);
-        self.audio_player.set_volume(self.volume);
-        self.status_message = format!("Volume: {}%", (self.volume * 100.0) as u8);
-    }
-
-    pub fn volume_down(&mut self) {
-        self.volume = (self.volume - 0.1).max(0.0);
-        self.audio_player.set_volume(self.volume);
-        self.status_message = format!("Volume: {}%", (self.volume * 100.0) as u8);
+    for dir in &missing {
+        log::warn!("Skipping missing directory {}", dir.display());
     }
 
-    pub fn refresh_files(&mut self) -> Result<()> {
-        self.music_files = Self::scan_music_files(&self.music_directory)?;
-        if self.selected_index >= self.music_files.len() && !self.music_files.is_empty() {
-            self.selected_index = self.music_files.len() - 1;
+    if args.headless {
+        let mut app = match playlist_path {
+            Some(ref path) => App::from_playlist(path, config)?,
+            None => App::with_config(music_dirs, config)?,
+        };
+        apply_playback_options(&mut app, &args);
+        if watch {
+            app.watch_library();
         }
-        if !self.music_files.is_empty() {
-            self.list_state.select(Some(self.selected_index));
-        } else {
-            self.list_state.select(None);
+        let socket = open_control_socket(&mut app, control_socket.as_deref());
+        if control_socket.is_some() && socket.is_none() {
+            eprintln!("{}", app.status_message);
         }
-        if self.music_files.is_empty() {
-            self.status_message = String::from("No music files found in directory");
-        } else {
-            self.status_message = format!("Refreshed - Found {} music files", self.music_files.len());
+        let res = run_headless(&mut app);
+        app.stop();
+        if let Err(err) = app.save_state() {
+            eprintln!("Warning: could not save state: {}", err);
         }
-        Ok(())
-    }
-}
-
-fn main() -> Result<()> {
-    // Get music directory from command line args or use default
-    let args: Vec<String> = std::env::args().collect();
-    let music_dir = if args.len() > 1 {
-        PathBuf::from(&args[1])
-    } else {
-        std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
-    };
-
-    if !music_dir.exists() {
-        eprintln!("Error: Directory '{}' does not exist", music_dir.display());
-        eprintln!("Usage: {} [music_directory]", args[0]);
-        std::process::exit(1);
+        return res;
     }
 
     // Setup terminal
@@ -242,10 +160,31 @@ fn main() -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
 
     // Create app
-    let mut app = App::new(music_dir)?;
+    let mut app = if let Some(ref path) = playlist_path {
+        App::from_playlist(path, config)?
+    } else if pick_directory {
+        let mut app = App::with_directory_picker(music_dirs[0].clone())?;
+        app.config.include = config.include;
+        app.config.exclude = config.exclude;
+        app
+    } else {
+        App::with_config(music_dirs, config)?
+    };
+    apply_playback_options(&mut app, &args);
+    if watch {
+        app.watch_library();
+    }
+    let _control = open_control_socket(&mut app, control_socket.as_deref());
+    if let Some(theme) = theme {
+        app.set_theme(theme);
+    }
+    if args.play {
+        app.play_when_loaded()?;
+    }
 
     // Main loop
     let res = run_app(&mut terminal, &mut app);
+    let saved = app.save_state();
 
     // Restore terminal
     disable_raw_mode()?;
@@ -257,8 +196,12 @@ fn main() -> Result<()> {
     terminal.show_cursor()?;
 
     if let Err(err) = res {
+        log::error!("Exited with error: {:?}", err);
         println!("{:?}", err);
     }
+    if let Err(err) = saved {
+        eprintln!("Warning: could not save state: {}", err);
+    }
 
     Ok(())
 }
@@ -268,39 +211,123 @@ fn run_app<B: ratatui::backend::Backend>(
     app: &mut App,
 ) -> Result<()> {
     loop {
+        let draw_started = Instant::now();
         terminal.draw(|f| ui::draw(f, app))?;
+        app.frame_stats.record(draw_started.elapsed());
 
         // Check if current song has finished and auto-play next
-        if app.current_playing.is_some() && !app.is_paused && app.audio_player.is_empty() {
-            app.status_message = String::from("Auto-advancing to next song...");
-            app.play_next()?;
+        app.tick()?;
+        if app.quit_requested {
+            return Ok(());
         }
 
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Char('q') => return Ok(()),
-                    KeyCode::Down | KeyCode::Char('j') => app.next(),
-                    KeyCode::Up | KeyCode::Char('k') => app.previous(),
-                    KeyCode::Enter => {
-                        app.play_selected()?;
-                    }
-                    KeyCode::Char('s') => app.stop(),
-                    KeyCode::Char(' ') => app.toggle_pause(),
-                    KeyCode::Char('+') => app.volume_up(),
-                    KeyCode::Char('-') => app.volume_down(),
-                    KeyCode::Char('r') => {
-                        app.refresh_files()?;
-                    }
-                    KeyCode::Char('n') => {
-                        app.play_next()?;
-                    }
-                    KeyCode::Char('p') => {
-                        app.play_previous()?;
-                    }
-                    _ => {}
+        if !event::poll(app.poll_timeout())? {
+            continue;
+        }
+
+        match event::read()? {
+            Event::Key(key) => {
+                let control = input::handle_key(app, key)?;
+                if control == Control::Quit {
+                    return Ok(());
                 }
             }
+            Event::Mouse(mouse) => input::handle_mouse(app, mouse)?,
+            _ => {}
         }
     }
 }
+
+/// Applies the command line options shared by the TUI and headless mode.
+fn apply_playback_options(app: &mut App, args: &Args) {
+    if let Some((first, last)) = args.range {
+        app.restrict_to_range(first, last);
+    }
+    if let Some(ref name) = args.device {
+        app.set_output_device(name);
+    }
+    if args.shuffle && !app.shuffle {
+        app.toggle_shuffle();
+    }
+    if let Some(repeat) = args.repeat {
+        app.repeat = repeat;
+    }
+}
+
+/// Starts the control socket if one is configured, reporting failures in
+/// the status bar.
+#[cfg(unix)]
+fn open_control_socket(app: &mut App, path: Option<&Path>) -> Option<control::ControlSocket> {
+    let path = path?;
+    match control::spawn(path, app.command_sender()) {
+        Ok(socket) => Some(socket),
+        Err(e) => {
+            log::warn!("Control socket {} not available: {:#}", path.display(), e);
+            app.set_status(format!("Control socket {} not available: {:#}", path.display(), e));
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn open_control_socket(app: &mut App, path: Option<&Path>) -> Option<()> {
+    if path.is_some() {
+        app.set_status("Control sockets are only supported on Unix");
+    }
+    None
+}
+
+/// Plays through the library without a terminal UI, printing each track as
+/// it starts, until the end of the list (with repeat off) or Ctrl-C.
+fn run_headless(app: &mut App) -> Result<()> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build();
+        match runtime {
+            Ok(runtime) => {
+                if runtime.block_on(tokio::signal::ctrl_c()).is_ok() {
+                    flag.store(true, Ordering::SeqCst);
+                }
+            }
+            Err(e) => log::warn!("Not handling Ctrl-C: {}", e),
+        }
+    });
+
+    let (events_tx, events) = mpsc::channel();
+    app.events.subscribe(events_tx);
+    app.play_when_loaded()?;
+    let mut played = false;
+    while !interrupted.load(Ordering::SeqCst) {
+        app.tick()?;
+        if app.quit_requested {
+            break;
+        }
+        for event in events.try_iter() {
+            if let PlayerEvent::TrackStarted(file) = event {
+                println!("♪ {}", file.display_name());
+                played = true;
+            }
+        }
+        if !app.scanning && app.current_playing.is_none() {
+            if !played {
+                // Nothing could be played; say why (no files, no device, ...)
+                eprintln!("{}", app.status_message);
+            }
+            break;
+        }
+        thread::sleep(app.poll_timeout());
+    }
+    Ok(())
+}
+
+/// Parses a 1-based inclusive track range such as `5-12`.
+fn parse_range(spec: &str) -> Result<(usize, usize), String> {
+    spec.split_once('-')
+        .and_then(|(first, last)| Some((first.trim().parse().ok()?, last.trim().parse().ok()?)))
+        .ok_or_else(|| String::from("expected N-M (e.g. 5-12)"))
+}
+
+fn parse_repeat(spec: &str) -> Result<RepeatMode, String> {
+    RepeatMode::parse(spec).ok_or_else(|| String::from("expected all, one or off"))
+}
@@ -0,0 +1,318 @@
+//! The MPRIS D-Bus interface, so media keys and desktop media widgets can
+//! see and control playback.
+
+use crate::commands::Command;
+use crate::events::PlayerEvent;
+use crate::MusicFile;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use zbus::zvariant::{ObjectPath, Value};
+use zbus::{blocking, interface, SignalContext};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.oxiplayer";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// The `org.mpris.MediaPlayer2` root interface. The player has no window to
+/// raise and is only quit from the terminal.
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "OxiPlayer"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// The `org.mpris.MediaPlayer2.Player` interface. Method calls are sent to
+/// the app as [`Command`]s; its state is kept up to date from [`PlayerEvent`]s.
+struct Player {
+    commands: Sender<Command>,
+    /// The current track and a number that identifies it for `mpris:trackid`.
+    track: Option<(u64, MusicFile)>,
+    tracks_started: u64,
+    status: Status,
+    /// Position at the last event; playback has run on since `since` unless
+    /// paused or stopped.
+    position: Duration,
+    since: Option<Instant>,
+}
+
+impl Player {
+    fn send(&self, command: Command) {
+        if let Err(e) = self.commands.send(command) {
+            log::debug!("MPRIS command {:?} dropped, the player has gone", e.0);
+        }
+    }
+
+    fn current_position(&self) -> Duration {
+        self.position + self.since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    /// Applies `event`, returning the position to announce with a `Seeked`
+    /// signal when the event was a jump.
+    fn apply(&mut self, event: PlayerEvent) -> Option<Duration> {
+        let now = Instant::now();
+        match event {
+            PlayerEvent::TrackStarted(file) => {
+                self.tracks_started += 1;
+                self.track = Some((self.tracks_started, file));
+                self.status = Status::Playing;
+                self.position = Duration::ZERO;
+                self.since = Some(now);
+            }
+            PlayerEvent::Stopped => {
+                self.track = None;
+                self.status = Status::Stopped;
+                self.position = Duration::ZERO;
+                self.since = None;
+            }
+            PlayerEvent::Paused(position) => {
+                self.status = Status::Paused;
+                self.position = position;
+                self.since = None;
+            }
+            PlayerEvent::Resumed(position) => {
+                self.status = Status::Playing;
+                self.position = position;
+                self.since = Some(now);
+            }
+            PlayerEvent::Seeked(position) => {
+                self.position = position;
+                self.since = (self.status == Status::Playing).then_some(now);
+                return Some(position);
+            }
+        }
+        None
+    }
+}
+
+fn micros(duration: Duration) -> i64 {
+    duration.as_micros().try_into().unwrap_or(i64::MAX)
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play(&self) {
+        self.send(Command::Play);
+    }
+
+    fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    fn play_pause(&self) {
+        self.send(Command::TogglePause);
+    }
+
+    fn stop(&self) {
+        self.send(Command::Stop);
+    }
+
+    fn next(&self) {
+        self.send(Command::Next);
+    }
+
+    fn previous(&self) {
+        self.send(Command::Previous);
+    }
+
+    /// Jumps by `offset` microseconds from the current position.
+    fn seek(&self, offset: i64) {
+        if self.track.is_none() {
+            return;
+        }
+        let position = micros(self.current_position()).saturating_add(offset).max(0);
+        self.send(Command::Seek(Duration::from_micros(position as u64)));
+    }
+
+    /// Jumps to `position` microseconds, ignored unless `track_id` is still
+    /// the current track.
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        let current = self.track.as_ref().map(|(id, _)| track_path(*id));
+        if position < 0 || current.as_deref() != Some(track_id.as_str()) {
+            return;
+        }
+        self.send(Command::Seek(Duration::from_micros(position as u64)));
+    }
+
+    #[zbus(signal)]
+    async fn seeked(context: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        match self.status {
+            Status::Playing => "Playing",
+            Status::Paused => "Paused",
+            Status::Stopped => "Stopped",
+        }
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, Value<'static>> {
+        let mut metadata = HashMap::new();
+        let Some((id, ref file)) = self.track else {
+            return metadata;
+        };
+        if let Ok(path) = ObjectPath::try_from(track_path(id)) {
+            metadata.insert(String::from("mpris:trackid"), Value::from(path));
+        }
+        let title = file.title.clone().unwrap_or_else(|| file.name.clone());
+        metadata.insert(String::from("xesam:title"), Value::from(title));
+        if let Some(ref artist) = file.artist {
+            metadata.insert(String::from("xesam:artist"), Value::from(vec![artist.clone()]));
+        }
+        if let Some(ref album) = file.album {
+            metadata.insert(String::from("xesam:album"), Value::from(album.clone()));
+        }
+        if let Some(length) = file.duration {
+            metadata.insert(String::from("mpris:length"), Value::from(micros(length)));
+        }
+        let url = format!("file://{}", file.path.display());
+        metadata.insert(String::from("xesam:url"), Value::from(url));
+        metadata
+    }
+
+    /// Estimated from the last event; clients poll it rather than being told.
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        micros(self.current_position())
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+fn track_path(id: u64) -> String {
+    format!("/org/oxiplayer/track/{}", id)
+}
+
+/// Publishes the player on the session bus from a background thread.
+/// Method calls arrive on `commands`; the returned sender takes the player
+/// events that keep the published state current.
+pub fn spawn(commands: Sender<Command>) -> Sender<PlayerEvent> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        if let Err(e) = serve(commands, rx) {
+            log::warn!("MPRIS interface not available: {}", e);
+        }
+    });
+    tx
+}
+
+fn serve(commands: Sender<Command>, events: Receiver<PlayerEvent>) -> zbus::Result<()> {
+    let player = Player {
+        commands,
+        track: None,
+        tracks_started: 0,
+        status: Status::Stopped,
+        position: Duration::ZERO,
+        since: None,
+    };
+    // zbus answers method calls on its own executor thread; this one only
+    // pushes out changes
+    let connection = blocking::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Root)?
+        .serve_at(OBJECT_PATH, player)?
+        .build()?;
+    let player = connection.object_server().interface::<_, Player>(OBJECT_PATH)?;
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    log::info!("Published MPRIS interface as {}", BUS_NAME);
+
+    for event in events {
+        let context = player.signal_context();
+        let mut iface = player.get_mut();
+        let seeked = iface.apply(event);
+        runtime.block_on(async {
+            if let Some(position) = seeked {
+                Player::seeked(context, micros(position)).await?;
+            }
+            iface.playback_status_changed(context).await?;
+            iface.metadata_changed(context).await?;
+            iface.can_pause_changed(context).await?;
+            iface.can_seek_changed(context).await
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use crate::events::PlayerEvent;
+use crate::template::format_track;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+/// Spawns a subscriber that mirrors the current track into `path`, e.g. for an
+/// OBS text source. The file is emptied when playback stops.
+pub fn spawn_writer(path: PathBuf, template: String) -> Sender<PlayerEvent> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for event in rx {
+            let text = match event {
+                PlayerEvent::TrackStarted(file) => format_track(&template, &file),
+                PlayerEvent::Stopped => String::new(),
+                PlayerEvent::Paused(_) | PlayerEvent::Resumed(_) | PlayerEvent::Seeked(_) => continue,
+            };
+            if let Err(e) = write_atomic(&path, &text) {
+                log::warn!("Could not write now-playing file {}: {}", path.display(), e);
+            }
+        }
+    });
+    tx
+}
+
+/// Writes through a temporary file and renames it into place so readers never
+/// see a partially written file.
+fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
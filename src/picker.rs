@@ -0,0 +1,46 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A directory browser used to choose the music directory.
+#[derive(Debug, Clone)]
+pub struct DirPicker {
+    /// The directory whose subdirectories are listed.
+    pub dir: PathBuf,
+    /// Subdirectories of `dir`, hidden ones left out, sorted by name.
+    pub entries: Vec<PathBuf>,
+}
+
+impl DirPicker {
+    pub fn open(dir: &Path) -> Self {
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let mut entries: Vec<PathBuf> = fs::read_dir(&dir)
+            .map(|read| {
+                read.filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+                    .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+                    .map(|entry| entry.path())
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort();
+        DirPicker { dir, entries }
+    }
+
+    /// Moves into the subdirectory at `index`.
+    pub fn enter(&mut self, index: usize) {
+        if let Some(dir) = self.entries.get(index) {
+            *self = DirPicker::open(dir);
+        }
+    }
+
+    /// Moves to the parent directory, returning the index of the directory
+    /// just left so it can stay highlighted.
+    pub fn up(&mut self) -> usize {
+        let Some(parent) = self.dir.parent() else {
+            return 0;
+        };
+        let left = self.dir.clone();
+        *self = DirPicker::open(parent);
+        self.entries.iter().position(|entry| *entry == left).unwrap_or(0)
+    }
+}
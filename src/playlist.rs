@@ -0,0 +1,47 @@
+use crate::MusicFile;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether `path` looks like an M3U playlist rather than a directory.
+pub fn is_playlist(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8"))
+}
+
+/// Writes an extended M3U playlist. Tracks under the playlist's directory are
+/// stored relative to it so the folder can be moved as a whole.
+pub fn save_playlist(path: &Path, files: &[MusicFile]) -> Result<()> {
+    let base = path.parent().unwrap_or(Path::new(""));
+    let mut contents = String::from("#EXTM3U\n");
+    for file in files {
+        let seconds = file.duration.map_or(-1, |d| d.as_secs() as i64);
+        contents.push_str(&format!("#EXTINF:{},{}\n", seconds, file.display_name()));
+        let entry = file.path.strip_prefix(base).unwrap_or(&file.path);
+        contents.push_str(&format!("{}\n", entry.display()));
+    }
+    fs::write(path, contents).with_context(|| format!("writing {}", path.display()))
+}
+
+/// Reads the tracks listed in an M3U playlist, in order. Relative entries are
+/// resolved against the playlist's directory; missing files are skipped.
+pub fn load_playlist(path: &Path) -> Result<Vec<MusicFile>> {
+    let contents = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let files = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| base.join(PathBuf::from(line)))
+        .filter(|track| {
+            let exists = track.is_file();
+            if !exists {
+                log::warn!("Playlist entry not found: {}", track.display());
+            }
+            exists
+        })
+        .map(MusicFile::from_path)
+        .collect();
+    Ok(files)
+}
@@ -0,0 +1,413 @@
+use crate::config::{Config, VideoContainers};
+use crate::MusicFile;
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use walkdir::WalkDir;
+
+const MUSIC_EXTENSIONS: [&str; 6] = ["mp3", "wav", "flac", "ogg", "m4a", "aac"];
+const VIDEO_EXTENSIONS: [&str; 3] = ["mp4", "m4v", "mov"];
+/// How many files a scan finds between progress reports.
+const PROGRESS_EVERY: usize = 50;
+
+/// What the list is ordered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Duration,
+    DateModified,
+    Artist,
+}
+
+impl SortKey {
+    pub fn next(self) -> Self {
+        match self {
+            SortKey::Name => SortKey::Duration,
+            SortKey::Duration => SortKey::DateModified,
+            SortKey::DateModified => SortKey::Artist,
+            SortKey::Artist => SortKey::Name,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Duration => "duration",
+            SortKey::DateModified => "date modified",
+            SortKey::Artist => "artist",
+        }
+    }
+}
+
+/// What a scan skipped because of the configured exclude patterns.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanStats {
+    pub excluded_dirs: usize,
+    pub excluded_files: usize,
+}
+
+impl ScanStats {
+    /// A suffix for status messages, empty when nothing was excluded.
+    pub fn summary(&self) -> String {
+        if self.excluded_dirs == 0 && self.excluded_files == 0 {
+            String::new()
+        } else {
+            format!(
+                " (excluded {} folders, {} files)",
+                self.excluded_dirs, self.excluded_files
+            )
+        }
+    }
+
+    fn add(&mut self, other: ScanStats) {
+        self.excluded_dirs += other.excluded_dirs;
+        self.excluded_files += other.excluded_files;
+    }
+}
+
+/// Progress reported by a background scan started with [`spawn`].
+#[derive(Debug, Clone)]
+pub enum ScanUpdate {
+    /// Music files found so far.
+    Found(usize),
+    /// The sorted library.
+    Finished(Vec<MusicFile>, ScanStats),
+}
+
+pub fn scan_music_files(dir: &Path, config: &Config) -> Result<(Vec<MusicFile>, ScanStats)> {
+    let (mut files, stats) = walk(dir, dir, config, &mut |_| {});
+    sort_files(&mut files);
+    Ok((files, stats))
+}
+
+/// Scans each of `roots` in turn on a background thread, reporting the
+/// count found as it goes, and merges the results into one list. A file
+/// reached through more than one root (nested roots, symlinks) is listed once.
+pub fn spawn(roots: Vec<PathBuf>, config: Config) -> Receiver<ScanUpdate> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut files = Vec::new();
+        let mut stats = ScanStats::default();
+        for root in &roots {
+            let before = files.len();
+            let (found, root_stats) = walk(root, root, &config, &mut |found| {
+                let _ = tx.send(ScanUpdate::Found(before + found));
+            });
+            files.extend(found);
+            stats.add(root_stats);
+        }
+        if roots.len() > 1 {
+            let mut seen = HashSet::new();
+            files.retain(|file| seen.insert(fs::canonicalize(&file.path).unwrap_or_else(|_| file.path.clone())));
+        }
+        sort_files(&mut files);
+        let _ = tx.send(ScanUpdate::Finished(files, stats));
+    });
+    rx
+}
+
+/// Lists the immediate contents of `dir` under the music root `root`:
+/// subfolders and music files, each sorted by name. Hidden and excluded
+/// entries are left out, as a scan would.
+pub fn list_dir(root: &Path, dir: &Path, config: &Config) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let include_video = config.video_containers != VideoContainers::Skip;
+    let filter = PathFilter::new(root, config);
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Could not list {}: {}", dir.display(), e);
+            return (dirs, files);
+        }
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if hidden || filter.is_excluded(&path) {
+            continue;
+        }
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            dirs.push(path);
+        } else if (has_extension(&path, &MUSIC_EXTENSIONS)
+            || (include_video && has_extension(&path, &VIDEO_EXTENSIONS)))
+            && filter.is_included(&path)
+        {
+            files.push(path);
+        }
+    }
+
+    if config.video_containers == VideoContainers::PreferAudio {
+        drop_shadowed_videos(&mut files);
+    }
+    dirs.sort();
+    files.sort();
+    (dirs, files)
+}
+
+/// Scans only `folder`, which lies under the music root `root`. Exclude
+/// patterns are still matched relative to `root`. The result is unsorted.
+pub fn scan_folder(root: &Path, folder: &Path, config: &Config) -> (Vec<MusicFile>, ScanStats) {
+    walk(root, folder, config, &mut |_| {})
+}
+
+pub fn sort_files(files: &mut [MusicFile]) {
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+}
+
+/// Sorts by `key`, ties broken by name. Tracks missing the key (no known
+/// length, no artist tag, unreadable modification time) go last in either
+/// direction.
+pub fn sort_files_by(files: &mut [MusicFile], key: SortKey, descending: bool) {
+    match key {
+        SortKey::Name if descending => files.sort_by(|a, b| b.name.cmp(&a.name)),
+        SortKey::Name => sort_files(files),
+        SortKey::Duration => files.sort_by(|a, b| {
+            known_first(a.duration, b.duration, descending).then_with(|| a.name.cmp(&b.name))
+        }),
+        SortKey::DateModified => {
+            let modified: HashMap<_, _> = files
+                .iter()
+                .map(|file| {
+                    let time = fs::metadata(&file.path).and_then(|metadata| metadata.modified()).ok();
+                    (file.path.clone(), time)
+                })
+                .collect();
+            files.sort_by(|a, b| {
+                known_first(modified[&a.path], modified[&b.path], descending).then_with(|| a.name.cmp(&b.name))
+            });
+        }
+        SortKey::Artist => files.sort_by(|a, b| {
+            let artist_album = |file: &MusicFile| {
+                let album = file.album.as_deref().unwrap_or_default().to_lowercase();
+                file.artist.as_ref().map(|artist| (artist.to_lowercase(), album))
+            };
+            known_first(artist_album(a), artist_album(b), descending).then_with(|| a.name.cmp(&b.name))
+        }),
+    }
+}
+
+fn known_first<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Calls `on_found` with the running count every [`PROGRESS_EVERY`] files.
+fn walk(
+    root: &Path,
+    start: &Path,
+    config: &Config,
+    on_found: &mut dyn FnMut(usize),
+) -> (Vec<MusicFile>, ScanStats) {
+    let mut paths = Vec::new();
+    let mut stats = ScanStats::default();
+    let include_video = config.video_containers != VideoContainers::Skip;
+    let filter = PathFilter::new(root, config);
+    let mut not_included = 0;
+
+    let walker = WalkDir::new(start).into_iter().filter_entry(|entry| {
+        // Never exclude the starting folder; excluded directories are pruned whole
+        let excluded = entry.depth() > 0 && filter.is_excluded(entry.path());
+        if excluded {
+            if entry.file_type().is_dir() {
+                stats.excluded_dirs += 1;
+            } else {
+                stats.excluded_files += 1;
+            }
+        }
+        !excluded
+    });
+
+    let walker = walker.filter_map(|entry| match entry {
+        Ok(entry) => Some(entry),
+        Err(e) => {
+            log::debug!("Skipping unreadable entry: {}", e);
+            None
+        }
+    });
+    for entry in walker {
+        let path = entry.path();
+        if let Some(extension) = path.extension() {
+            if let Some(ext_str) = extension.to_str() {
+                let ext = ext_str.to_lowercase();
+                if MUSIC_EXTENSIONS.contains(&ext.as_str())
+                    || (include_video && VIDEO_EXTENSIONS.contains(&ext.as_str()))
+                {
+                    if !filter.is_included(path) {
+                        not_included += 1;
+                        continue;
+                    }
+                    paths.push(path.to_path_buf());
+                    if paths.len() % PROGRESS_EVERY == 0 {
+                        on_found(paths.len());
+                    }
+                }
+            }
+        }
+    }
+    stats.excluded_files += not_included;
+
+    if config.video_containers == VideoContainers::PreferAudio {
+        drop_shadowed_videos(&mut paths);
+    }
+
+    // Tags are read last so files dropped above are never opened
+    let files: Vec<MusicFile> = paths.into_iter().map(MusicFile::from_path).collect();
+
+    log::info!("Scanned {}: {} files{}", start.display(), files.len(), stats.summary());
+    (files, stats)
+}
+
+/// Hides a video container when an audio file with the same basename sits beside it.
+fn drop_shadowed_videos(paths: &mut Vec<PathBuf>) {
+    let audio_stems: HashSet<PathBuf> = paths
+        .iter()
+        .filter(|path| !has_extension(path, &VIDEO_EXTENSIONS))
+        .map(|path| path.with_extension(""))
+        .collect();
+    paths.retain(|path| {
+        !has_extension(path, &VIDEO_EXTENSIONS)
+            || !audio_stems.contains(&path.with_extension(""))
+    });
+}
+
+/// The `include` and `exclude` globs from the config. Each pattern is tried
+/// against a path relative to the music root and against the full path, so
+/// both `Samples/` and `/home/me/Music/Samples/` work.
+struct PathFilter {
+    root: PathBuf,
+    full_root: PathBuf,
+    /// `None` lets every file through.
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl PathFilter {
+    fn new(root: &Path, config: &Config) -> Self {
+        PathFilter {
+            root: root.to_path_buf(),
+            full_root: std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf()),
+            include: (!config.include.is_empty()).then(|| build_globset(&config.include, "include")),
+            exclude: build_globset(&config.exclude, "exclude"),
+        }
+    }
+
+    fn matches(&self, set: &GlobSet, path: &Path) -> bool {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => set.is_match(relative) || set.is_match(self.full_root.join(relative)),
+            Err(_) => set.is_match(path),
+        }
+    }
+
+    /// Whether the file or folder `path` is excluded.
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.matches(&self.exclude, path)
+    }
+
+    /// Whether the file `path` matches an include pattern, if there are any.
+    fn is_included(&self, path: &Path) -> bool {
+        self.include.as_ref().is_none_or(|include| self.matches(include, path))
+    }
+}
+
+/// Builds a matcher from glob patterns. Trailing slashes are accepted so
+/// `Samples/` matches the folder. Invalid patterns are skipped.
+fn build_globset(patterns: &[String], kind: &str) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern.trim_end_matches('/')) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => log::warn!("Ignoring invalid {} pattern {:?}: {}", kind, pattern, e),
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir holding `files`, all empty.
+    fn tree(name: &str, files: &[&str]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("oxiplayer-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        root
+    }
+
+    fn relative(root: &Path, files: &[MusicFile]) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = files
+            .iter()
+            .map(|file| file.path.strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    const FILES: [&str; 5] = [
+        "a.flac",
+        "b.mp3",
+        "album/c.flac",
+        "album/notes.txt",
+        "samples/kick.flac",
+    ];
+
+    #[test]
+    fn exclude_skips_matching_files() {
+        let root = tree("exclude", &FILES);
+        let config = Config {
+            exclude: vec!["**/samples/**".to_string()],
+            ..Config::default()
+        };
+        let (files, stats) = walk(&root, &root, &config, &mut |_| {});
+        assert_eq!(
+            relative(&root, &files),
+            [PathBuf::from("a.flac"), PathBuf::from("album/c.flac"), PathBuf::from("b.mp3")]
+        );
+        assert_eq!(stats.excluded_dirs, 0);
+        assert_eq!(stats.excluded_files, 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn include_keeps_only_matching_files() {
+        let root = tree("include", &FILES);
+        let config = Config {
+            include: vec!["**/*.flac".to_string()],
+            ..Config::default()
+        };
+        let (files, stats) = walk(&root, &root, &config, &mut |_| {});
+        assert_eq!(
+            relative(&root, &files),
+            [PathBuf::from("a.flac"), PathBuf::from("album/c.flac"), PathBuf::from("samples/kick.flac")]
+        );
+        assert_eq!(stats.excluded_dirs, 0);
+        assert_eq!(stats.excluded_files, 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,532 @@
+use rodio::source::SeekError;
+use rodio::{Sample, Source};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Mono samples the visualizer looks at; a bit more than one FFT window.
+const TAP_CAPACITY: usize = 4096;
+/// Frames collected on the audio thread before handing them over.
+const TAP_BATCH: usize = 512;
+/// Samples per chunk a [`Prefetch`] hands from its decoding thread to the output.
+const PREFETCH_CHUNK: usize = 4096;
+
+/// How far the output has pulled through a source, shared with the audio thread.
+#[derive(Debug, Default)]
+pub struct Progress {
+    samples: AtomicU64,
+    exhausted: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    /// Samples from the start of the track up to where the output has read
+    /// (all channels counted), so seeks move it along with the source.
+    pub fn samples(&self) -> u64 {
+        self.samples.load(Ordering::Relaxed)
+    }
+
+    /// Moves the count to `position` in a stream of the given format.
+    pub fn set_position(&self, position: Duration, sample_rate: u32, channels: u16) {
+        let frames = (position.as_secs_f64() * sample_rate as f64) as u64;
+        self.samples.store(frames * channels as u64, Ordering::Relaxed);
+    }
+
+    /// Whether the decoder ran out of data, as opposed to being dropped early.
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+
+    /// Makes the source end the next time it is pulled, e.g. to drop a
+    /// queued track that is no longer wanted.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Wraps a source and records its progress into a shared [`Progress`].
+pub struct Tracked<S> {
+    inner: S,
+    progress: Arc<Progress>,
+}
+
+impl<S> Tracked<S> {
+    pub fn new(inner: S, progress: Arc<Progress>) -> Self {
+        Tracked { inner, progress }
+    }
+}
+
+impl<S> Iterator for Tracked<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<S::Item> {
+        if self.progress.cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+        match self.inner.next() {
+            Some(sample) => {
+                self.progress.samples.fetch_add(1, Ordering::Relaxed);
+                Some(sample)
+            }
+            None => {
+                self.progress.exhausted.store(true, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+
+impl<S> Source for Tracked<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(position)?;
+        self.progress.set_position(position, self.inner.sample_rate(), self.inner.channels());
+        Ok(())
+    }
+}
+
+/// A track's own volume factor, shared with the audio thread so it can
+/// change while the track plays.
+#[derive(Debug)]
+pub struct Gain {
+    /// `f32` bits of the factor.
+    factor: AtomicU32,
+}
+
+impl Gain {
+    pub fn new(factor: f32) -> Self {
+        Gain { factor: AtomicU32::new(factor.to_bits()) }
+    }
+
+    pub fn set(&self, factor: f32) {
+        self.factor.store(factor.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.factor.load(Ordering::Relaxed))
+    }
+}
+
+/// Wraps a 16-bit source and scales it by a shared [`Gain`].
+pub struct Scaled<S> {
+    inner: S,
+    gain: Arc<Gain>,
+}
+
+impl<S> Scaled<S> {
+    pub fn new(inner: S, gain: Arc<Gain>) -> Self {
+        Scaled { inner, gain }
+    }
+}
+
+impl<S> Iterator for Scaled<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.inner.next()?;
+        let factor = self.gain.get();
+        if factor == 1.0 {
+            return Some(sample);
+        }
+        Some((sample as f32 * factor) as i16)
+    }
+}
+
+impl<S> Source for Scaled<S>
+where
+    S: Source<Item = i16>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(position)
+    }
+}
+
+/// The most recent output, mixed down to mono, shared with the visualizer.
+/// Collection is off until enabled so playback pays nothing for it.
+#[derive(Debug, Default)]
+pub struct SampleTap {
+    enabled: AtomicBool,
+    samples: Mutex<VecDeque<f32>>,
+}
+
+impl SampleTap {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Copies out the buffered samples, oldest first.
+    pub fn snapshot(&self) -> Vec<f32> {
+        self.samples.lock().map(|samples| samples.iter().copied().collect()).unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.clear();
+        }
+    }
+
+    /// Never blocks the audio thread; a batch is dropped if the UI holds the lock.
+    fn push(&self, batch: &[f32]) {
+        if let Ok(mut samples) = self.samples.try_lock() {
+            samples.extend(batch);
+            let excess = samples.len().saturating_sub(TAP_CAPACITY);
+            samples.drain(..excess);
+        }
+    }
+}
+
+/// Wraps a 16-bit source and copies what it plays into a [`SampleTap`].
+pub struct Tapped<S> {
+    inner: S,
+    tap: Arc<SampleTap>,
+    frame_sum: f32,
+    frame_pos: u16,
+    batch: Vec<f32>,
+}
+
+impl<S> Tapped<S> {
+    pub fn new(inner: S, tap: Arc<SampleTap>) -> Self {
+        Tapped { inner, tap, frame_sum: 0.0, frame_pos: 0, batch: Vec::with_capacity(TAP_BATCH) }
+    }
+}
+
+impl<S> Iterator for Tapped<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.inner.next()?;
+        if self.tap.is_enabled() {
+            self.frame_sum += sample as f32 / i16::MAX as f32;
+            self.frame_pos += 1;
+            let channels = self.inner.channels().max(1);
+            if self.frame_pos >= channels {
+                self.batch.push(self.frame_sum / channels as f32);
+                self.frame_sum = 0.0;
+                self.frame_pos = 0;
+                if self.batch.len() >= TAP_BATCH {
+                    self.tap.push(&self.batch);
+                    self.batch.clear();
+                }
+            }
+        }
+        Some(sample)
+    }
+}
+
+impl<S> Source for Tapped<S>
+where
+    S: Source<Item = i16>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+    fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(position)
+    }
+}
+
+/// The state a [`Prefetch`] source shares with the player.
+#[derive(Debug, Default)]
+pub struct Buffer {
+    /// Samples decoded but not yet played.
+    buffered: AtomicU64,
+    /// The decoder has handed over its last sample.
+    decoded: AtomicBool,
+    /// The output found the buffer empty before the decoder was done.
+    starved: AtomicBool,
+    /// Silence played while starved, which isn't part of the track.
+    padding: AtomicU64,
+}
+
+impl Buffer {
+    /// Samples (all channels counted) decoded ahead of the output.
+    pub fn buffered(&self) -> u64 {
+        self.buffered.load(Ordering::Relaxed)
+    }
+
+    pub fn is_decoded(&self) -> bool {
+        self.decoded.load(Ordering::Relaxed)
+    }
+
+    /// Whether the output ran dry since the last call.
+    pub fn take_starved(&self) -> bool {
+        self.starved.swap(false, Ordering::Relaxed)
+    }
+
+    /// Samples of silence filled in while the output ran dry.
+    pub fn padding(&self) -> u64 {
+        self.padding.load(Ordering::Relaxed)
+    }
+}
+
+/// Decodes a source on its own thread ahead of the output, so a slow disk
+/// or network share stalls the buffer instead of the audio callback. While
+/// the buffer is empty and the decoder isn't done it plays silence and
+/// marks the shared [`Buffer`] as starved, which the end of the track
+/// never does.
+pub struct Prefetch {
+    rx: Receiver<Vec<i16>>,
+    chunk: Vec<i16>,
+    pos: usize,
+    /// Rest of the silent frame being played while starved.
+    silence_left: u16,
+    channels: u16,
+    sample_rate: u32,
+    total_duration: Option<Duration>,
+    buffer: Arc<Buffer>,
+}
+
+impl Prefetch {
+    /// Starts decoding `inner`, keeping up to `ahead` of it buffered. The
+    /// first quarter of that is decoded before returning so playback
+    /// doesn't start out starved.
+    pub fn new<S>(mut inner: S, ahead: Duration, buffer: Arc<Buffer>) -> Self
+    where
+        S: Source<Item = i16> + Send + 'static,
+    {
+        let channels = inner.channels().max(1);
+        let sample_rate = inner.sample_rate();
+        let total_duration = inner.total_duration();
+        let chunk_len = (PREFETCH_CHUNK / channels as usize).max(1) * channels as usize;
+        let samples_ahead = ahead.as_secs_f64() * sample_rate as f64 * channels as f64;
+        let capacity = ((samples_ahead / chunk_len as f64).ceil() as usize).max(2);
+        let (tx, rx) = mpsc::sync_channel(capacity);
+
+        let mut finished = false;
+        for _ in 0..capacity.div_ceil(4) {
+            if !send_chunk(&mut inner, chunk_len, &tx, &buffer) {
+                finished = true;
+                break;
+            }
+        }
+        if finished {
+            buffer.decoded.store(true, Ordering::Relaxed);
+        } else {
+            let buffer = buffer.clone();
+            thread::spawn(move || {
+                while send_chunk(&mut inner, chunk_len, &tx, &buffer) {}
+                buffer.decoded.store(true, Ordering::Relaxed);
+            });
+        }
+
+        Prefetch {
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+            silence_left: 0,
+            channels,
+            sample_rate,
+            total_duration,
+            buffer,
+        }
+    }
+}
+
+/// Decodes the next chunk of `inner` into the channel. Returns `false` once
+/// the source is done or the output has gone away.
+fn send_chunk<S: Source<Item = i16>>(inner: &mut S, len: usize, tx: &SyncSender<Vec<i16>>, buffer: &Buffer) -> bool {
+    let chunk: Vec<i16> = inner.by_ref().take(len).collect();
+    let full = chunk.len() == len;
+    if chunk.is_empty() {
+        return false;
+    }
+    buffer.buffered.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    tx.send(chunk).is_ok() && full
+}
+
+impl Iterator for Prefetch {
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        if self.silence_left > 0 {
+            self.silence_left -= 1;
+            self.buffer.padding.fetch_add(1, Ordering::Relaxed);
+            return Some(0);
+        }
+        if self.pos == self.chunk.len() {
+            match self.rx.try_recv() {
+                Ok(chunk) => {
+                    self.buffer.buffered.fetch_sub(chunk.len() as u64, Ordering::Relaxed);
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                // Chunks are whole frames, so the silence is too
+                Err(TryRecvError::Empty) if !self.buffer.is_decoded() => {
+                    self.buffer.starved.store(true, Ordering::Relaxed);
+                    self.silence_left = self.channels - 1;
+                    self.buffer.padding.fetch_add(1, Ordering::Relaxed);
+                    return Some(0);
+                }
+                Err(_) => return None,
+            }
+        }
+        let sample = self.chunk[self.pos];
+        self.pos += 1;
+        Some(sample)
+    }
+}
+
+impl Source for Prefetch {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Counts up from 1 and stalls once, as a slow disk would.
+    struct Stalling {
+        next: i16,
+        len: i16,
+        stall_at: i16,
+        stall: Duration,
+    }
+
+    impl Iterator for Stalling {
+        type Item = i16;
+
+        fn next(&mut self) -> Option<i16> {
+            if self.next > self.len {
+                return None;
+            }
+            if self.next == self.stall_at {
+                thread::sleep(self.stall);
+            }
+            self.next += 1;
+            Some(self.next - 1)
+        }
+    }
+
+    impl Source for Stalling {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            2
+        }
+
+        fn sample_rate(&self) -> u32 {
+            8_000
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    #[test]
+    fn prefetch_fills_a_stall_with_silence_and_keeps_every_sample() {
+        let len = 20_000;
+        let source = Stalling { next: 1, len, stall_at: 15_000, stall: Duration::from_millis(300) };
+        let buffer = Arc::new(Buffer::default());
+        let mut prefetch = Prefetch::new(source, Duration::from_millis(500), buffer.clone());
+
+        let started = Instant::now();
+        let samples: Vec<i16> = prefetch.by_ref().collect();
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert!(buffer.take_starved());
+        assert!(buffer.is_decoded());
+
+        let padding = samples.iter().filter(|&&sample| sample == 0).count();
+        assert_eq!(padding as u64, buffer.padding());
+        assert_eq!(padding % 2, 0);
+        let played: Vec<i16> = samples.into_iter().filter(|&sample| sample != 0).collect();
+        assert_eq!(played, (1..=len).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn prefetch_of_a_short_source_ends_without_starving() {
+        let source = Stalling { next: 1, len: 100, stall_at: 0, stall: Duration::ZERO };
+        let buffer = Arc::new(Buffer::default());
+        let samples: Vec<i16> = Prefetch::new(source, Duration::from_secs(1), buffer.clone()).collect();
+        assert_eq!(samples.len(), 100);
+        assert!(!buffer.take_starved());
+    }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+
+/// Session state remembered between runs, stored as `state.toml` next to the config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub tick_rate_ms: Option<u64>,
+    /// Volume to use on a device with no remembered volume of its own.
+    pub volume: Option<f32>,
+    /// Directory opened when none is given on the command line.
+    pub music_directory: Option<PathBuf>,
+    /// Every directory of the library when there were several; takes the
+    /// place of `music_directory` then.
+    pub music_roots: Vec<PathBuf>,
+    /// Track selected on startup.
+    pub last_played: Option<PathBuf>,
+    /// Where playback of `last_played` got to, kept for long tracks only.
+    pub last_position_ms: Option<u64>,
+    /// Last volume used on each output device, keyed by device name.
+    pub device_volumes: HashMap<String, f32>,
+    /// Equalizer band gains in dB, lowest band first.
+    pub eq_gains: Vec<f32>,
+    /// Tracks marked as favorites, by path.
+    pub favorites: BTreeSet<PathBuf>,
+    /// Extra gain in dB for single tracks, on top of the volume.
+    pub track_gains: HashMap<PathBuf, f32>,
+    /// Crossfade length in ms out of single tracks, in place of
+    /// `crossfade_ms`; 0 cuts straight to the next track.
+    pub crossfade_overrides: HashMap<PathBuf, u64>,
+    /// Recently started tracks, newest first.
+    pub history: VecDeque<PathBuf>,
+}
+
+impl State {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("oxiplayer").join("state.toml"))
+    }
+
+    /// Loads the saved state, falling back to defaults if it is missing or corrupt.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("No config directory available"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use crate::audio;
+use crate::MusicFile;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// How many entries the largest/longest lists keep.
+const TOP_N: usize = 5;
+
+/// Group for tracks without the tag a breakdown goes by.
+pub const UNKNOWN: &str = "Unknown";
+
+/// Overview of the library shown by the stats dashboard.
+#[derive(Debug, Clone, Default)]
+pub struct LibraryStats {
+    pub tracks: usize,
+    pub total_size: u64,
+    pub total_duration: Duration,
+    /// Tracks the decoder could not report a length for; not in `total_duration`.
+    pub unknown_duration: usize,
+    /// Track count per lowercase extension, most common first.
+    pub by_extension: Vec<(String, usize)>,
+    pub largest: Vec<(String, u64)>,
+    pub longest: Vec<(String, Duration)>,
+    /// Tracks per artist tag, most first, untagged ones under [`UNKNOWN`].
+    pub by_artist: Vec<Group>,
+    /// Tracks per album and album artist, like `by_artist`.
+    pub by_album: Vec<Group>,
+}
+
+/// Tracks sharing an artist or album.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    pub tracks: usize,
+    /// Length of the tracks in the group that have a known one.
+    pub duration: Duration,
+}
+
+/// Collects [`Group`]s by name while the files are read.
+#[derive(Default)]
+struct Groups(HashMap<String, Group>);
+
+impl Groups {
+    fn add(&mut self, name: Option<&str>, duration: Option<Duration>) {
+        let name = name.map(str::trim).filter(|name| !name.is_empty()).unwrap_or(UNKNOWN);
+        let group = self.0.entry(name.to_string()).or_insert_with(|| Group {
+            name: name.to_string(),
+            tracks: 0,
+            duration: Duration::ZERO,
+        });
+        group.tracks += 1;
+        group.duration += duration.unwrap_or_default();
+    }
+
+    /// Largest groups first, ties by name.
+    fn into_sorted(self) -> Vec<Group> {
+        let mut groups: Vec<Group> = self.0.into_values().collect();
+        groups.sort_by(|a, b| b.tracks.cmp(&a.tracks).then_with(|| a.name.cmp(&b.name)));
+        groups
+    }
+}
+
+/// The album `file` is counted under, with its artist so that albums that
+/// share a title stay apart.
+fn album_name(file: &MusicFile) -> Option<String> {
+    let album = file.album.as_deref()?.trim();
+    if album.is_empty() {
+        return None;
+    }
+    Some(match file.artist.as_deref().map(str::trim).filter(|artist| !artist.is_empty()) {
+        Some(artist) => format!("{} - {}", album, artist),
+        None => album.to_string(),
+    })
+}
+
+/// Progress reported while gathering [`LibraryStats`].
+#[derive(Debug, Clone)]
+pub enum StatsUpdate {
+    Checked { done: usize, total: usize },
+    Finished(LibraryStats),
+}
+
+/// Reads the size and header of every file on a background thread.
+pub fn spawn(files: Vec<MusicFile>) -> Receiver<StatsUpdate> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let total = files.len();
+        let mut stats = LibraryStats { tracks: total, ..Default::default() };
+        let mut extensions: HashMap<String, usize> = HashMap::new();
+        let mut sizes = Vec::with_capacity(total);
+        let mut durations = Vec::with_capacity(total);
+        let mut artists = Groups::default();
+        let mut albums = Groups::default();
+
+        for (i, file) in files.into_iter().enumerate() {
+            let extension = file
+                .path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| String::from("(none)"));
+            *extensions.entry(extension).or_default() += 1;
+
+            if let Ok(metadata) = fs::metadata(&file.path) {
+                stats.total_size += metadata.len();
+                sizes.push((file.name.clone(), metadata.len()));
+            }
+            let duration = file
+                .duration
+                .or_else(|| audio::probe(&file.path).ok().and_then(|info| info.duration));
+            artists.add(file.artist.as_deref(), duration);
+            albums.add(album_name(&file).as_deref(), duration);
+            match duration {
+                Some(duration) => {
+                    stats.total_duration += duration;
+                    durations.push((file.name, duration));
+                }
+                None => stats.unknown_duration += 1,
+            }
+
+            if tx.send(StatsUpdate::Checked { done: i + 1, total }).is_err() {
+                return;
+            }
+        }
+
+        stats.by_extension = extensions.into_iter().collect();
+        stats.by_extension.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sizes.sort_by_key(|&(_, size)| std::cmp::Reverse(size));
+        sizes.truncate(TOP_N);
+        stats.largest = sizes;
+        durations.sort_by_key(|&(_, length)| std::cmp::Reverse(length));
+        durations.truncate(TOP_N);
+        stats.longest = durations;
+        stats.by_artist = artists.into_sorted();
+        stats.by_album = albums.into_sorted();
+
+        let _ = tx.send(StatsUpdate::Finished(stats));
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn tagged(artist: Option<&str>, album: Option<&str>) -> MusicFile {
+        MusicFile {
+            path: PathBuf::from("/music/a.mp3"),
+            name: String::from("a.mp3"),
+            artist: artist.map(String::from),
+            album: album.map(String::from),
+            title: None,
+            track_number: None,
+            duration: None,
+            replay_gain: None,
+        }
+    }
+
+    #[test]
+    fn untagged_tracks_are_grouped_as_unknown() {
+        let mut groups = Groups::default();
+        let minute = Duration::from_secs(60);
+        groups.add(Some("Low"), Some(minute));
+        groups.add(None, Some(minute));
+        groups.add(Some("  "), None);
+        groups.add(Some("Low"), None);
+        groups.add(Some("Air"), Some(minute));
+
+        let groups = groups.into_sorted();
+        let summary: Vec<_> = groups.iter().map(|g| (g.name.as_str(), g.tracks, g.duration)).collect();
+        assert_eq!(summary, [("Low", 2, minute), (UNKNOWN, 2, minute), ("Air", 1, minute)]);
+    }
+
+    #[test]
+    fn albums_are_told_apart_by_artist() {
+        assert_eq!(album_name(&tagged(Some("Low"), Some("Hits"))).as_deref(), Some("Hits - Low"));
+        assert_eq!(album_name(&tagged(None, Some("Hits"))).as_deref(), Some("Hits"));
+        assert_eq!(album_name(&tagged(Some("Low"), Some(""))), None);
+    }
+}
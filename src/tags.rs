@@ -0,0 +1,63 @@
+use lofty::prelude::*;
+use lofty::tag::{ItemKey, Tag};
+use std::path::Path;
+use std::time::Duration;
+
+/// Tag fields shown in place of the file name when present.
+#[derive(Debug, Clone, Default)]
+pub struct Tags {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    pub track_number: Option<u32>,
+    pub duration: Option<Duration>,
+    /// Linear factor from the ReplayGain tags.
+    pub replay_gain: Option<f32>,
+}
+
+/// Reads the primary tag (or the first one found) and the stream length.
+/// Missing or corrupt tags leave the fields `None` rather than failing.
+pub fn read(path: &Path) -> Tags {
+    let tagged = match lofty::read_from_path(path) {
+        Ok(tagged) => tagged,
+        Err(e) => {
+            log::debug!("No tags read from {}: {}", path.display(), e);
+            return Tags::default();
+        }
+    };
+
+    let duration = Some(tagged.properties().duration()).filter(|d| !d.is_zero());
+    let Some(tag) = tagged.primary_tag().or_else(|| tagged.first_tag()) else {
+        return Tags { duration, ..Default::default() };
+    };
+    let text = |value: Option<std::borrow::Cow<str>>| {
+        value
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    Tags {
+        artist: text(tag.artist()),
+        album: text(tag.album()),
+        title: text(tag.title()),
+        track_number: tag.track(),
+        duration,
+        replay_gain: replay_gain(tag),
+    }
+}
+
+/// The track gain (or the album gain when there is none) as a linear
+/// factor, lowered if needed so the tagged peak stays below full scale.
+fn replay_gain(tag: &Tag) -> Option<f32> {
+    let number = |key: ItemKey| {
+        let value = tag.get_string(&key)?.trim();
+        let value = value.strip_suffix("dB").or_else(|| value.strip_suffix("db")).unwrap_or(value);
+        value.trim().parse::<f32>().ok().filter(|value| value.is_finite())
+    };
+    let gain_db = number(ItemKey::ReplayGainTrackGain).or_else(|| number(ItemKey::ReplayGainAlbumGain))?;
+    let peak = number(ItemKey::ReplayGainTrackPeak)
+        .or_else(|| number(ItemKey::ReplayGainAlbumPeak))
+        .filter(|&peak| peak > 0.0);
+    let factor = 10f32.powf(gain_db / 20.0);
+    Some(peak.map_or(factor, |peak| factor.min(1.0 / peak)))
+}
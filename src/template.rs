@@ -0,0 +1,67 @@
+use crate::config::TimeFormat;
+use crate::MusicFile;
+use std::time::Duration;
+
+/// Expands `{name}`, `{stem}` and `{path}` placeholders in `template` for `file`.
+pub fn format_track(template: &str, file: &MusicFile) -> String {
+    let stem = file
+        .path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| file.name.clone());
+
+    template
+        .replace("{name}", &file.name)
+        .replace("{stem}", &stem)
+        .replace("{path}", &file.path.display().to_string())
+}
+
+/// Formats a duration for display in the given style.
+pub fn format_duration(duration: Duration, format: TimeFormat) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    match format {
+        TimeFormat::Auto if hours == 0 => format!("{}:{:02}", minutes, seconds),
+        TimeFormat::Auto | TimeFormat::Hours => {
+            format!("{}:{:02}:{:02}", hours, minutes, seconds)
+        }
+        TimeFormat::Minutes => format!("{}:{:02}", secs / 60, seconds),
+        TimeFormat::Compact if hours > 0 => format!("{}h{:02}m", hours, minutes),
+        TimeFormat::Compact if minutes > 0 => format!("{}m{:02}s", minutes, seconds),
+        TimeFormat::Compact => format!("{}s", seconds),
+    }
+}
+
+/// Parses a typed time: a number of seconds, `m:ss` or `h:mm:ss`. `None`
+/// for anything else, including seconds or minutes past 59 after a colon.
+pub fn parse_timestamp(text: &str) -> Option<Duration> {
+    let fields: Vec<&str> = text.split(':').collect();
+    if fields.len() > 3 || fields.iter().any(|field| field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let mut secs: u64 = 0;
+    for (i, field) in fields.iter().enumerate() {
+        let value: u64 = field.parse().ok()?;
+        if i > 0 && value >= 60 {
+            return None;
+        }
+        secs = secs.checked_mul(60)?.checked_add(value)?;
+    }
+    Some(Duration::from_secs(secs))
+}
+
+/// Formats a byte count with a binary unit, e.g. `3.4 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
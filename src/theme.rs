@@ -0,0 +1,180 @@
+use crate::config::ThemeName;
+use anyhow::{anyhow, Context, Result};
+use ratatui::style::Color;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// How often the system dark/light preference is re-read while following it.
+const SYSTEM_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Every color the UI draws with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Borders and regular text.
+    pub text: Color,
+    /// Hints and placeholders.
+    pub muted: Color,
+    /// Background of the selected list row.
+    pub highlight_bg: Color,
+    /// Titles, labels and overlay borders.
+    pub accent: Color,
+    /// The playing track, progress and other "all good" states.
+    pub playing: Color,
+    /// Text of the selected list row.
+    pub selected: Color,
+    /// Paused state, warnings and icons.
+    pub attention: Color,
+    /// Errors, unplayable files and the stopped state.
+    pub error: Color,
+    /// Pins, speed and other markers.
+    pub marker: Color,
+    /// Set for the monochrome theme; states are shown with attributes instead.
+    pub monochrome: bool,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            text: Color::White,
+            muted: Color::Gray,
+            highlight_bg: Color::DarkGray,
+            accent: Color::Cyan,
+            playing: Color::Green,
+            selected: Color::Yellow,
+            attention: Color::Yellow,
+            error: Color::Red,
+            marker: Color::Magenta,
+            monochrome: false,
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            text: Color::Black,
+            muted: Color::DarkGray,
+            highlight_bg: Color::Gray,
+            accent: Color::Blue,
+            ..Theme::dark()
+        }
+    }
+
+    pub fn monochrome() -> Self {
+        Theme {
+            text: Color::Reset,
+            muted: Color::Reset,
+            highlight_bg: Color::Reset,
+            accent: Color::Reset,
+            playing: Color::Reset,
+            selected: Color::Reset,
+            attention: Color::Reset,
+            error: Color::Reset,
+            marker: Color::Reset,
+            monochrome: true,
+        }
+    }
+
+    pub fn named(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Dark => Theme::dark(),
+            ThemeName::Light => Theme::light(),
+            ThemeName::Monochrome => Theme::monochrome(),
+        }
+    }
+}
+
+/// A theme file: colors to change, on top of `base` or the theme it is applied to.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<ThemeName>,
+    text: Option<String>,
+    muted: Option<String>,
+    highlight_bg: Option<String>,
+    accent: Option<String>,
+    playing: Option<String>,
+    selected: Option<String>,
+    attention: Option<String>,
+    error: Option<String>,
+    marker: Option<String>,
+}
+
+/// Reads a TOML theme file. Colors not set in it come from its `base` theme,
+/// or from `fallback` when it names none.
+pub fn load(path: &Path, fallback: Theme) -> Result<Theme> {
+    let contents = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let file: ThemeFile = toml::from_str(&contents).with_context(|| format!("parsing {}", path.display()))?;
+
+    let mut theme = file.base.map_or(fallback, Theme::named);
+    let fields = [
+        (&file.text, &mut theme.text),
+        (&file.muted, &mut theme.muted),
+        (&file.highlight_bg, &mut theme.highlight_bg),
+        (&file.accent, &mut theme.accent),
+        (&file.playing, &mut theme.playing),
+        (&file.selected, &mut theme.selected),
+        (&file.attention, &mut theme.attention),
+        (&file.error, &mut theme.error),
+        (&file.marker, &mut theme.marker),
+    ];
+    for (value, color) in fields {
+        if let Some(value) = value {
+            *color = parse_color(value)?;
+        }
+    }
+    Ok(theme)
+}
+
+/// A built-in theme name (`dark`, `light`, `monochrome`) or a theme file path.
+pub fn resolve(spec: &str, fallback: Theme) -> Result<Theme> {
+    match ThemeName::parse(spec) {
+        Some(name) => Ok(Theme::named(name)),
+        None => load(Path::new(spec), fallback),
+    }
+}
+
+/// Accepts `#RRGGBB`, a color name such as `cyan` or `light-blue`, or an
+/// ANSI palette index.
+fn parse_color(value: &str) -> Result<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)
+            .ok_or_else(|| anyhow!("invalid color '{}', expected #RRGGBB", value))?;
+        return Ok(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+    }
+    Color::from_str(value).map_err(|_| anyhow!("unknown color '{}'", value))
+}
+
+/// Whether the terminal should get colors at all, following the `NO_COLOR`
+/// convention (https://no-color.org) and treating `TERM=dumb` as monochrome.
+pub fn colors_supported() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+    !no_color && !dumb
+}
+
+/// Reads the system preference, or `None` where the platform doesn't expose one.
+fn detect() -> Option<ThemeName> {
+    match dark_light::detect() {
+        dark_light::Mode::Dark => Some(ThemeName::Dark),
+        dark_light::Mode::Light => Some(ThemeName::Light),
+        dark_light::Mode::Default => None,
+    }
+}
+
+/// Polls the system dark/light preference on a background thread and sends
+/// it on every poll. The thread exits once the receiver is dropped.
+pub fn watch_system() -> Receiver<Option<ThemeName>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        while tx.send(detect()).is_ok() {
+            thread::sleep(SYSTEM_POLL_INTERVAL);
+        }
+    });
+    rx
+}
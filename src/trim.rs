@@ -0,0 +1,31 @@
+use crate::audio;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Progress reported by a background playability check.
+#[derive(Debug, Clone)]
+pub enum TrimUpdate {
+    Checked { done: usize, total: usize },
+    Unplayable(PathBuf),
+    Finished,
+}
+
+/// Opens the header of every file on a background thread, reporting which
+/// ones no decoder backend accepts. Nothing is fully decoded.
+pub fn spawn(paths: Vec<PathBuf>) -> Receiver<TrimUpdate> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let total = paths.len();
+        for (i, path) in paths.into_iter().enumerate() {
+            if audio::probe(&path).is_err() && tx.send(TrimUpdate::Unplayable(path)).is_err() {
+                return;
+            }
+            if tx.send(TrimUpdate::Checked { done: i + 1, total }).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(TrimUpdate::Finished);
+    });
+    rx
+}
@@ -1,13 +1,83 @@
-use crate::App;
+use crate::app::HitAreas;
+use crate::browser::{Browser, BrowserEntry};
+use crate::eq;
+use crate::identify::FileIdentity;
+use crate::picker::DirPicker;
+use crate::stats::{Group, LibraryStats};
+use crate::template::{format_duration, format_size};
+use crate::theme::Theme;
+use crate::visualizer;
+use crate::{App, Mode, MusicFile, View};
+use figlet_rs::FIGfont;
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{
+        block::{Position, Title},
+        Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Sparkline, Wrap,
+    },
     Frame,
 };
+use std::sync::OnceLock;
+use std::time::Duration;
 
 pub fn draw(f: &mut Frame, app: &App) {
+    render_into(f, f.size(), app);
+}
+
+/// Renders the whole player UI, overlays included, inside `area`. Host
+/// applications embedding OxiPlayer can give it any part of their frame.
+pub fn render_into(f: &mut Frame, area: Rect, app: &App) {
+    app.hit_areas.set(HitAreas::default());
+    match app.view {
+        View::Banner => {
+            draw_banner(f, area, app);
+            if app.monochrome {
+                strip_colors(f, area);
+            }
+            return;
+        }
+        View::NowPlaying => draw_now_playing(f, area, app),
+        View::Normal => draw_main(f, area, app),
+    }
+
+    if let Mode::PinnedDirs { selected } = app.mode {
+        draw_pinned_dirs(f, area, app, selected);
+    }
+
+    if let (Mode::DirPicker { selected }, Some(ref picker)) = (app.mode, &app.dir_picker) {
+        draw_dir_picker(f, area, app, picker, selected);
+    }
+
+    if let Mode::Devices { selected } = app.mode {
+        draw_devices(f, area, app, selected);
+    }
+
+    if let Mode::Equalizer { selected } = app.mode {
+        draw_equalizer(f, area, app, selected);
+    }
+
+    if let Some(ref identity) = app.identity {
+        draw_identity(f, area, identity, &app.theme);
+    }
+
+    if let (Mode::Stats, Some(ref stats)) = (app.mode, &app.library_stats) {
+        draw_library_stats(f, area, stats, app);
+    }
+
+    if app.show_debug || app.stress_mode {
+        draw_debug_overlay(f, area, app);
+    }
+
+    if app.monochrome {
+        strip_colors(f, area);
+    }
+}
+
+/// The header, the list beside the info panel, and the footer.
+fn draw_main(f: &mut Frame, area: Rect, app: &App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -15,7 +85,7 @@ pub fn draw(f: &mut Frame, app: &App) {
             Constraint::Min(0),     // Main content
             Constraint::Length(3),  // Footer/Status
         ])
-        .split(f.size());
+        .split(area);
 
     // Draw header
     draw_header(f, chunks[0], app);
@@ -30,51 +100,324 @@ pub fn draw(f: &mut Frame, app: &App) {
         .split(chunks[1]);
 
     draw_file_list(f, main_chunks[0], app);
-    draw_info_panel(f, main_chunks[1], app);
+    match app.mode {
+        Mode::History { selected } => draw_history(f, main_chunks[1], app, selected),
+        _ => draw_info_panel(f, main_chunks[1], app),
+    }
 
     // Draw footer
     draw_footer(f, chunks[2], app);
 }
 
+/// Drops every color but keeps bold/underline/reverse, which carry the
+/// playing and selected states on their own.
+fn strip_colors(f: &mut Frame, area: Rect) {
+    f.buffer_mut()
+        .set_style(area, Style::default().fg(Color::Reset).bg(Color::Reset));
+}
+
+/// Volume text, in yellow once amplifying past 100%.
+/// Cells in the volume bar when the panel is wide enough.
+const VOLUME_BAR_WIDTH: u16 = 10;
+
+/// "Volume: ████░░░░░░ 40%", dropping the bar when `width` can't fit it.
+fn volume_line(app: &App, width: u16) -> Line<'static> {
+    let label = "Volume: ";
+    let percent = format!("{}%", app.volume_percent());
+    let mut spans = vec![Span::styled(label, Style::default().fg(app.theme.accent))];
+
+    let muted_len = if app.muted { " MUTED".len() } else { 0 };
+    let room = width.saturating_sub((label.len() + percent.len() + muted_len) as u16 + 1);
+    let cells = room.min(VOLUME_BAR_WIDTH);
+    if cells >= 3 {
+        // The bar spans 0 to the configured maximum, so it is full at the cap
+        let max = app.config.max_volume();
+        let filled = ((app.volume / max).clamp(0.0, 1.0) * cells as f32).round() as u16;
+        for cell in 0..cells {
+            if cell < filled {
+                let level = (cell + 1) as f32 / cells as f32 * max;
+                spans.push(Span::styled("█", Style::default().fg(volume_color(level, &app.theme))));
+            } else {
+                spans.push(Span::styled("░", Style::default().fg(app.theme.muted)));
+            }
+        }
+        spans.push(Span::raw(" "));
+    }
+
+    spans.push(Span::styled(percent, volume_style(app)));
+    if app.muted {
+        spans.push(Span::styled(" MUTED", Style::default().fg(app.theme.error).add_modifier(Modifier::BOLD)));
+    }
+    Line::from(spans)
+}
+
+/// Green for quiet, yellow towards full volume, red once amplifying (in the
+/// default theme).
+fn volume_color(level: f32, theme: &Theme) -> Color {
+    if level <= 0.6 {
+        theme.playing
+    } else if level <= 1.0 {
+        theme.attention
+    } else {
+        theme.error
+    }
+}
+
+/// " (1.5x)" next to the status when not playing at normal speed.
+fn speed_label(app: &App) -> String {
+    if app.speed == 1.0 {
+        String::new()
+    } else {
+        format!(" ({:.1}x)", app.speed)
+    }
+}
+
+/// The gain normalization applies to the playing track, when it is on.
+fn normalization_label(app: &App) -> Option<String> {
+    if !app.normalize {
+        return None;
+    }
+    Some(match app.playing_file() {
+        Some(file) if file.replay_gain.is_some() => {
+            format!("{:+.1} dB", 20.0 * app.normalization_gain(file).log10())
+        }
+        Some(_) => String::from("no ReplayGain tags"),
+        None => String::from("On"),
+    })
+}
+
+fn volume_style(app: &App) -> Style {
+    if app.volume > 1.0 {
+        Style::default().fg(app.theme.attention)
+    } else {
+        Style::default().fg(app.theme.text)
+    }
+}
+
+/// Style of the selected row in lists; reversed video without colors.
+fn highlight_style(app: &App) -> Style {
+    let style = Style::default().add_modifier(Modifier::BOLD);
+    if app.monochrome {
+        style.add_modifier(Modifier::REVERSED)
+    } else {
+        style.bg(app.theme.highlight_bg)
+    }
+}
+
+/// The bundled figlet font, parsed on first use.
+fn banner_font() -> Option<&'static FIGfont> {
+    static FONT: OnceLock<Option<FIGfont>> = OnceLock::new();
+    FONT.get_or_init(|| FIGfont::standard().ok()).as_ref()
+}
+
+/// Renders `text` as figlet art if the font loads and the result fits in `width`.
+fn banner_lines(text: &str, width: u16) -> Option<Vec<String>> {
+    let figure = banner_font()?.convert(text)?.to_string();
+    let lines: Vec<String> = figure.lines().map(str::to_string).collect();
+    let fits = lines.iter().all(|line| line.chars().count() <= width as usize);
+    fits.then_some(lines)
+}
+
+/// Full-screen now playing: the track title in large letters, progress and a clock.
+fn draw_banner(f: &mut Frame, area: Rect, app: &App) {
+    let title = app
+        .playing_file()
+        .and_then(|file| {
+            file.title
+                .clone()
+                .or_else(|| file.path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        })
+        .unwrap_or_else(|| String::from("Nothing playing"));
+    let title_style = Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD);
+    let title_lines: Vec<Line> = match banner_lines(&title, area.width) {
+        Some(lines) => lines.into_iter().map(|line| Line::styled(line, title_style)).collect(),
+        None => vec![Line::styled(title, title_style)],
+    };
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(title_lines.len() as u16), // Title
+            Constraint::Length(1),
+            Constraint::Length(1), // Progress
+            Constraint::Length(1),
+            Constraint::Length(1), // Clock
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    f.render_widget(Paragraph::new(title_lines).alignment(Alignment::Center), rows[1]);
+
+    let time_format = app.config.time_format;
+    let elapsed = app.audio_player.position();
+    let progress = centered_rect(area.width * 2 / 3, 1, rows[3]);
+    match app.current_duration.filter(|_| app.current_playing.is_some()) {
+        Some(total) if !total.is_zero() => {
+            let ratio = (elapsed.as_secs_f64() / total.as_secs_f64()).clamp(0.0, 1.0);
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(app.theme.playing).bg(app.theme.highlight_bg))
+                .ratio(ratio)
+                .label(format!(
+                    "{} / {}",
+                    format_duration(elapsed, time_format),
+                    format_duration(total, time_format)
+                ));
+            f.render_widget(gauge, progress);
+        }
+        _ if app.current_playing.is_some() => {
+            let text = Paragraph::new(format_duration(elapsed, time_format))
+                .alignment(Alignment::Center);
+            f.render_widget(text, progress);
+        }
+        _ => {}
+    }
+
+    let clock = Paragraph::new(chrono::Local::now().format("%H:%M").to_string())
+        .style(Style::default().fg(app.theme.muted))
+        .alignment(Alignment::Center);
+    f.render_widget(clock, rows[5]);
+}
+
+/// The playing track alone on screen: cover, title, artist, album and a
+/// wide progress bar.
+fn draw_now_playing(f: &mut Frame, area: Rect, app: &App) {
+    let Some(file) = app.playing_file() else {
+        let idle = Paragraph::new(Span::styled("Nothing playing", Style::default().fg(app.theme.muted)))
+            .alignment(Alignment::Center);
+        f.render_widget(idle, centered_rect(area.width, 1, area));
+        return;
+    };
+
+    let cover = match app.album_art {
+        Some(ref art) if app.show_album_art => art.lines(area.width, area.height / 2),
+        _ => Vec::new(),
+    };
+    let mut details = vec![Line::styled(
+        file.title.clone().unwrap_or_else(|| file.name.clone()),
+        Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD),
+    )];
+    if let Some(ref artist) = file.artist {
+        details.push(Line::styled(artist.clone(), Style::default().fg(app.theme.text)));
+    }
+    if let Some(ref album) = file.album {
+        details.push(Line::styled(album.clone(), Style::default().fg(app.theme.muted)));
+    }
+    if app.is_paused {
+        details.push(Line::styled("Paused", Style::default().fg(app.theme.attention)));
+    }
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(cover.len() as u16), // Cover
+            Constraint::Length(if cover.is_empty() { 0 } else { 1 }),
+            Constraint::Length(details.len() as u16), // Title, artist, album
+            Constraint::Length(1),
+            Constraint::Length(1), // Progress
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    f.render_widget(Paragraph::new(cover), rows[1]);
+    f.render_widget(Paragraph::new(details).alignment(Alignment::Center), rows[3]);
+
+    let time_format = app.config.time_format;
+    let elapsed = app.audio_player.position();
+    let progress = centered_rect(area.width * 4 / 5, 1, rows[5]);
+    let (ratio, label) = match app.current_duration {
+        Some(total) if !total.is_zero() => (
+            (elapsed.as_secs_f64() / total.as_secs_f64()).clamp(0.0, 1.0),
+            format!("{} / {}", format_duration(elapsed, time_format), format_duration(total, time_format)),
+        ),
+        _ => (0.0, format_duration(elapsed, time_format)),
+    };
+    let gauge = Gauge::default()
+        .gauge_style(Style::default().fg(app.theme.playing).bg(app.theme.highlight_bg))
+        .ratio(ratio)
+        .label(label);
+    f.render_widget(gauge, progress);
+}
+
+/// A rectangle of at most `width` x `height` centered in `area`.
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
 fn draw_header(f: &mut Frame, area: Rect, app: &App) {
     let title = Paragraph::new(vec![
         Line::from(vec![
-            Span::styled("🎵 ", Style::default().fg(Color::Yellow)),
-            Span::styled("OxiPlayer", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::styled("🎵 ", Style::default().fg(app.theme.attention)),
+            Span::styled("OxiPlayer", Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
             Span::raw(" - Rust TUI Music Player"),
         ]),
         Line::from(vec![
-            Span::raw("Directory: "),
+            Span::raw(if app.music_roots.len() > 1 { "Library: " } else { "Directory: " }),
             Span::styled(
-                app.music_directory.display().to_string(),
-                Style::default().fg(Color::Green),
+                app.roots_label(),
+                Style::default().fg(app.theme.playing),
+            ),
+            Span::styled(
+                if app.music_files.is_empty() { String::new() } else { format!("  {}", app.library_total.label()) },
+                Style::default().fg(app.theme.muted),
             ),
         ]),
     ])
     .block(
         Block::default()
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::White)),
+            .border_style(Style::default().fg(app.theme.text)),
     );
     f.render_widget(title, area);
 }
 
 fn draw_file_list(f: &mut Frame, area: Rect, app: &App) {
+    if let Some(ref browser) = app.browser {
+        draw_browser(f, area, app, browser);
+        return;
+    }
+
+    if app.music_files.is_empty() && app.scanning {
+        let scanning = Paragraph::new(vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                format!("Scanning {}...", app.roots_label()),
+                Style::default().fg(app.theme.muted),
+            )),
+        ])
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(scanning_title(app))
+                .border_style(Style::default().fg(app.theme.text)),
+        );
+        f.render_widget(scanning, area);
+        return;
+    }
+
     if app.music_files.is_empty() {
         let empty_message = vec![
             Line::from(""),
             Line::from(vec![
-                Span::styled("No music files found", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::styled("No music files found", Style::default().fg(app.theme.attention).add_modifier(Modifier::BOLD)),
             ]),
             Line::from(""),
             Line::from(vec![
                 Span::raw("Supported formats: "),
-                Span::styled("MP3, WAV, FLAC, OGG, M4A, AAC", Style::default().fg(Color::Cyan)),
+                Span::styled("MP3, WAV, FLAC, OGG, M4A, AAC", Style::default().fg(app.theme.accent)),
             ]),
             Line::from(""),
             Line::from(vec![
                 Span::raw("Press "),
-                Span::styled("'r'", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                Span::styled("'r'", Style::default().fg(app.theme.playing).add_modifier(Modifier::BOLD)),
                 Span::raw(" to refresh the file list"),
             ]),
             Line::from(""),
@@ -82,7 +425,7 @@ fn draw_file_list(f: &mut Frame, area: Rect, app: &App) {
                 Span::raw("Or specify a different directory when starting:"),
             ]),
             Line::from(vec![
-                Span::styled("cargo run /path/to/music", Style::default().fg(Color::Gray)),
+                Span::styled("cargo run /path/to/music", Style::default().fg(app.theme.muted)),
             ]),
         ];
 
@@ -91,142 +434,420 @@ fn draw_file_list(f: &mut Frame, area: Rect, app: &App) {
                 Block::default()
                     .borders(Borders::ALL)
                     .title("Music Files (0/0)")
-                    .border_style(Style::default().fg(Color::White)),
+                    .border_style(Style::default().fg(app.theme.text)),
             );
         f.render_widget(empty_widget, area);
         return;
     }
 
+    // Marked with `*` and shown alone with `F`
+    let heading = if app.favorites_only { "Favorites" } else { "Music Files" };
+
+    if app.visible.is_empty() {
+        let (message, key, action) = if app.search_query.is_empty() {
+            (String::from("No favorites here"), "F", " to show all tracks")
+        } else {
+            (format!("No matches for '{}'", app.search_query), "Esc", " to clear the search")
+        };
+        let no_matches = vec![
+            Line::from(""),
+            Line::from(vec![
+                Span::styled(
+                    message,
+                    Style::default().fg(app.theme.attention).add_modifier(Modifier::BOLD),
+                ),
+            ]),
+            Line::from(""),
+            Line::from(vec![
+                Span::raw("Press "),
+                Span::styled(key, Style::default().fg(app.theme.playing).add_modifier(Modifier::BOLD)),
+                Span::raw(action),
+            ]),
+        ];
+
+        let no_matches_widget = Paragraph::new(no_matches)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("{} (0/{} matching \"{}\")", heading, app.music_files.len(), app.search_query))
+                    .border_style(Style::default().fg(app.theme.text)),
+            );
+        f.render_widget(no_matches_widget, area);
+        return;
+    }
+
     let items: Vec<ListItem> = app
-        .music_files
+        .visible
         .iter()
-        .enumerate()
+        .map(|&i| (i, &app.music_files[i]))
         .map(|(i, file)| {
-            let style = if Some(&file.name) == app.current_playing.as_ref() {
-                Style::default()
-                    .fg(Color::Green)
-                    .add_modifier(Modifier::BOLD)
+            let style = if app.is_playing(file) {
+                let style = Style::default()
+                    .fg(app.theme.playing)
+                    .add_modifier(Modifier::BOLD);
+                if app.monochrome {
+                    style.add_modifier(Modifier::UNDERLINED)
+                } else {
+                    style
+                }
             } else if i == app.selected_index {
                 Style::default()
-                    .fg(Color::Yellow)
+                    .fg(app.theme.selected)
                     .add_modifier(Modifier::BOLD)
+            } else if app.bad_files.contains(&file.path) {
+                Style::default().fg(app.theme.error)
             } else {
-                Style::default().fg(Color::White)
+                Style::default().fg(app.theme.text)
             };
 
-            let prefix = if Some(&file.name) == app.current_playing.as_ref() {
+            let prefix = if app.is_playing(file) {
                 "♪ "
             } else if i == app.selected_index {
                 "> "
+            } else if app.bad_files.contains(&file.path) {
+                "✗ "
             } else {
                 "  "
             };
 
-            ListItem::new(Line::from(vec![
-                Span::raw(prefix),
-                Span::styled(&file.name, style),
-            ]))
+            let mut spans = vec![Span::raw(prefix)];
+            if app.config.show_track_numbers {
+                let number = file.track_number.map_or_else(|| String::from("  "), |n| format!("{:02}", n));
+                spans.push(Span::styled(format!("{}  ", number), Style::default().fg(app.theme.muted)));
+            }
+            spans.push(Span::styled(file.display_name(), style));
+            if app.favorites.contains(&file.path) {
+                spans.push(Span::styled(" ★", Style::default().fg(app.theme.marker)));
+            }
+            if app.stop_after.as_ref() == Some(&file.path) {
+                spans.push(Span::styled(" ⏹", Style::default().fg(app.theme.marker)));
+            }
+            ListItem::new(Line::from(spans))
         })
         .collect();
 
-    let title = format!("Music Files ({}/{})",
-                       app.selected_index + 1,
-                       app.music_files.len());
+    let position = app.visible_position().map_or(0, |pos| pos + 1);
+    let title = if app.scanning {
+        scanning_title(app)
+    } else if app.search_query.is_empty() {
+        format!("{} ({}/{}) {}", heading, position, app.music_files.len(), sort_label(app))
+    } else {
+        format!("{} ({}/{} matching \"{}\") {}",
+                heading,
+                app.visible.len(),
+                app.music_files.len(),
+                app.search_query,
+                sort_label(app))
+    };
 
     let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .title(title)
-                .border_style(Style::default().fg(Color::White)),
+                .border_style(Style::default().fg(app.theme.text)),
         )
-        .highlight_style(
-            Style::default()
-                .bg(Color::DarkGray)
-                .add_modifier(Modifier::BOLD),
+        .highlight_style(highlight_style(app));
+
+    // Inside the borders; used for paging
+    let height = area.height.saturating_sub(2) as usize;
+    app.list_height.set(height);
+
+    let mut list_state = app.list_state.clone();
+    render_scrolled_list(f, area, app, list, &mut list_state, app.visible.len());
+}
+
+/// Draws `list` with the selection kept near the middle instead of at the
+/// edges, and a scrollbar once its `len` rows overflow the area.
+fn render_scrolled_list(f: &mut Frame, area: Rect, app: &App, list: List, list_state: &mut ListState, len: usize) {
+    let height = area.height.saturating_sub(2) as usize;
+    if let Some(selected) = list_state.selected() {
+        let max_offset = len.saturating_sub(height);
+        *list_state.offset_mut() = selected.saturating_sub(height / 2).min(max_offset);
+    }
+    f.render_stateful_widget(list, area, list_state);
+    app.hit_areas.set(HitAreas { list: area, list_offset: list_state.offset(), ..app.hit_areas.get() });
+
+    if len > height {
+        let mut scrollbar_state = ScrollbarState::new(len).position(list_state.selected().unwrap_or(0));
+        let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+            .begin_symbol(None)
+            .end_symbol(None)
+            .style(Style::default().fg(app.theme.muted));
+        f.render_stateful_widget(
+            scrollbar,
+            area.inner(&Margin { vertical: 1, horizontal: 0 }),
+            &mut scrollbar_state,
         );
+    }
+}
+
+fn draw_browser(f: &mut Frame, area: Rect, app: &App, browser: &Browser) {
+    let items: Vec<ListItem> = if browser.entries.is_empty() {
+        vec![ListItem::new(Span::styled("  (empty folder)", Style::default().fg(app.theme.muted)))]
+    } else {
+        browser
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let prefix = if i == browser.selected { "> " } else { "  " };
+                let line = match entry {
+                    BrowserEntry::Dir(_) if browser.is_parent(entry) => Line::from(vec![
+                        Span::raw(prefix),
+                        Span::styled("..", Style::default().fg(app.theme.muted)),
+                    ]),
+                    BrowserEntry::Dir(dir) => {
+                        let name = dir.file_name().map_or_else(
+                            || dir.display().to_string(),
+                            |name| name.to_string_lossy().to_string(),
+                        );
+                        Line::from(vec![
+                            Span::raw(prefix),
+                            Span::styled(format!("{}/", name), Style::default().fg(app.theme.accent)),
+                        ])
+                    }
+                    BrowserEntry::Track(file) => {
+                        let playing = app.is_playing(file);
+                        let style = if playing {
+                            Style::default().fg(app.theme.playing).add_modifier(Modifier::BOLD)
+                        } else if app.bad_files.contains(&file.path) {
+                            Style::default().fg(app.theme.error)
+                        } else {
+                            Style::default().fg(app.theme.text)
+                        };
+                        let mut spans = vec![
+                            Span::raw(if playing { "♪ " } else { prefix }),
+                            Span::styled(file.display_name(), style),
+                        ];
+                        if app.favorites.contains(&file.path) {
+                            spans.push(Span::styled(" ★", Style::default().fg(app.theme.marker)));
+                        }
+                        Line::from(spans)
+                    }
+                };
+                ListItem::new(line)
+            })
+            .collect()
+    };
+
+    let relative = browser.dir.strip_prefix(&browser.root).unwrap_or(&browser.dir);
+    let title = format!(
+        "Folders: /{} ({}/{})",
+        relative.display(),
+        (browser.selected + 1).min(browser.entries.len()),
+        browser.entries.len()
+    );
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(app.theme.text)),
+        )
+        .highlight_style(highlight_style(app));
 
-    f.render_stateful_widget(list, area, &mut app.list_state.clone());
+    app.list_height.set(area.height.saturating_sub(2) as usize);
+    let mut list_state = ListState::default();
+    list_state.select(Some(browser.selected));
+    render_scrolled_list(f, area, app, list, &mut list_state, browser.entries.len());
+}
+
+/// The sort key with an arrow for the direction, e.g. "[duration ↓]".
+fn sort_label(app: &App) -> String {
+    format!("[{} {}]", app.sort_key.label(), if app.sort_descending { "↓" } else { "↑" })
+}
+
+fn scanning_title(app: &App) -> String {
+    format!("Scanning… ({} found)", app.scan_found)
 }
 
 fn draw_info_panel(f: &mut Frame, area: Rect, app: &App) {
     let info_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(8),  // Currently playing info
+            Constraint::Length(14), // Currently playing info
+            Constraint::Length(3),  // Progress
+            Constraint::Length(if app.show_album_art && app.current_playing.is_some() { ALBUM_ART_HEIGHT } else { 0 }), // Cover
+            Constraint::Length(if app.show_visualizer { 8 } else { 0 }), // Visualizer
             Constraint::Min(0),     // Controls help
         ])
         .split(area);
 
     // Currently playing section
-    let currently_playing = if let Some(ref playing) = app.current_playing {
+    let mut currently_playing = if let Some(playing) = app.playing_name() {
         let status_text = if app.is_paused { "Paused" } else { "Playing" };
-        let status_color = if app.is_paused { Color::Yellow } else { Color::Green };
+        let status_color = if app.is_paused { app.theme.attention } else { app.theme.playing };
 
         vec![
             Line::from(vec![
-                Span::styled("Now Playing:", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                Span::styled("Now Playing:", Style::default().fg(app.theme.playing).add_modifier(Modifier::BOLD)),
             ]),
             Line::from(""),
             Line::from(vec![
-                Span::styled("♪ ", Style::default().fg(Color::Yellow)),
-                Span::raw(playing),
+                Span::styled("♪ ", Style::default().fg(app.theme.attention)),
+                Span::raw(app.playing_file().map_or_else(|| playing.clone(), |file| file.display_name())),
             ]),
             Line::from(""),
             Line::from(vec![
-                Span::styled("Status: ", Style::default().fg(Color::Cyan)),
+                Span::styled("Status: ", Style::default().fg(app.theme.accent)),
                 Span::styled(status_text, Style::default().fg(status_color)),
+                Span::styled(speed_label(app), Style::default().fg(app.theme.marker)),
             ]),
-            Line::from(vec![
-                Span::styled("Volume: ", Style::default().fg(Color::Cyan)),
-                Span::styled(format!("{}%", (app.volume * 100.0) as u8), Style::default().fg(Color::White)),
-            ]),
+            volume_line(app, info_chunks[0].width.saturating_sub(2)),
         ]
     } else {
         vec![
             Line::from(vec![
-                Span::styled("Now Playing:", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)),
+                Span::styled("Now Playing:", Style::default().fg(app.theme.muted).add_modifier(Modifier::BOLD)),
             ]),
             Line::from(""),
             Line::from(vec![
-                Span::styled("No music playing", Style::default().fg(Color::Gray)),
+                Span::styled("No music playing", Style::default().fg(app.theme.muted)),
             ]),
             Line::from(""),
             Line::from(vec![
-                Span::styled("Status: ", Style::default().fg(Color::Cyan)),
-                Span::styled("Stopped", Style::default().fg(Color::Red)),
-            ]),
-            Line::from(vec![
-                Span::styled("Volume: ", Style::default().fg(Color::Cyan)),
-                Span::styled(format!("{}%", (app.volume * 100.0) as u8), Style::default().fg(Color::White)),
+                Span::styled("Status: ", Style::default().fg(app.theme.accent)),
+                Span::styled("Stopped", Style::default().fg(app.theme.error)),
             ]),
+            volume_line(app, info_chunks[0].width.saturating_sub(2)),
         ]
     };
 
+    currently_playing.push(Line::from(vec![
+        Span::styled("Repeat: ", Style::default().fg(app.theme.accent)),
+        Span::styled(app.repeat.label(), Style::default().fg(app.theme.text)),
+        Span::styled("  Shuffle: ", Style::default().fg(app.theme.accent)),
+        Span::styled(if app.shuffle { "On" } else { "Off" }, Style::default().fg(app.theme.text)),
+    ]));
+    if let Some(remaining) = app.sleep_remaining() {
+        currently_playing.push(Line::from(vec![
+            Span::styled("Sleep in: ", Style::default().fg(app.theme.accent)),
+            Span::styled(
+                format_duration(remaining + Duration::from_millis(999), app.config.time_format),
+                Style::default().fg(app.theme.text),
+            ),
+        ]));
+    }
+    if let Some(label) = normalization_label(app) {
+        currently_playing.push(Line::from(vec![
+            Span::styled("Normalize: ", Style::default().fg(app.theme.accent)),
+            Span::styled(label, Style::default().fg(app.theme.text)),
+        ]));
+    }
+    if app.audio_player.limiter().is_enabled() {
+        let (label, color) = if app.limiter_engaged() {
+            ("Limiting", app.theme.attention)
+        } else {
+            ("On", app.theme.text)
+        };
+        currently_playing.push(Line::from(vec![
+            Span::styled("Limiter: ", Style::default().fg(app.theme.accent)),
+            Span::styled(label, Style::default().fg(color)),
+        ]));
+    }
+    if let Some(db) = app.current_playing.as_ref().and_then(|path| app.track_gains.get(path)) {
+        currently_playing.push(Line::from(vec![
+            Span::styled("Track gain: ", Style::default().fg(app.theme.accent)),
+            Span::styled(format!("{:+} dB", db), Style::default().fg(app.theme.marker)),
+        ]));
+    }
+    if let Some(pinned) = app.stop_after_file() {
+        currently_playing.push(Line::from(vec![
+            Span::styled("Stop after: ", Style::default().fg(app.theme.marker)),
+            Span::raw(&pinned.name),
+        ]));
+    }
+
     let now_playing = Paragraph::new(currently_playing)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .title("Player Info")
-                .border_style(Style::default().fg(Color::White)),
+                .border_style(Style::default().fg(app.theme.text)),
         );
     f.render_widget(now_playing, info_chunks[0]);
 
+    draw_progress(f, info_chunks[1], app);
+    if app.show_album_art && app.current_playing.is_some() {
+        draw_album_art(f, info_chunks[2], app);
+    }
+    if app.show_visualizer {
+        draw_visualizer(f, info_chunks[3], app);
+    }
+
+    if let Mode::Queue { selected } = app.mode {
+        draw_queue(f, info_chunks[4], app, selected);
+        return;
+    }
+    if let Mode::StatusLog { selected } = app.mode {
+        draw_status_log(f, info_chunks[4], app, selected);
+        return;
+    }
+
     // Controls help section
     let controls = vec![
         Line::from(vec![
-            Span::styled("Controls:", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::styled("Controls:", Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
         ]),
         Line::from(""),
         Line::from("↑/k    - Move up"),
         Line::from("↓/j    - Move down"),
+        Line::from("PgUp/PgDn - Page up/down"),
+        Line::from("Home/End  - First/last track"),
+        Line::from(".      - Jump to playing track"),
         Line::from("Enter  - Play selected"),
         Line::from("n      - Play next song"),
         Line::from("p      - Play previous song"),
         Line::from("Space  - Pause/Resume"),
         Line::from("s      - Stop playback"),
+        Line::from("←/→    - Seek -/+ 5s"),
+        Line::from("g      - Go to a time"),
+        Line::from("0      - Restart current track"),
+        Line::from("(/)/x  - Loop A/B marks, clear loop"),
+        Line::from("c      - Resume last session"),
+        Line::from("o      - Repeat: all/one/off"),
+        Line::from("z      - Shuffle on/off"),
+        Line::from("S/R    - Sort key/direction"),
+        Line::from("a/A    - Queue selected/clear queue"),
+        Line::from("Ctrl-a - Queue everything shown"),
+        Line::from("Q      - Queue (Enter plays, d removes)"),
+        Line::from("l      - Status message log"),
+        Line::from("X      - Move track to trash"),
+        Line::from("W      - Save queue/list as M3U"),
+        Line::from("Ctrl-o - Load the saved M3U"),
         Line::from("+/-    - Volume up/down"),
+        Line::from("m      - Mute/unmute"),
+        Line::from("v      - Type an exact volume"),
+        Line::from("N      - Loudness normalization"),
+        Line::from("Y      - Skip trailing silence"),
+        Line::from("M      - Limiter on/off"),
+        Line::from("{/}/|  - Track gain down/up/clear"),
+        Line::from("Ctrl-f - Track crossfade length"),
+        Line::from("</>    - Speed down/up"),
         Line::from("r      - Refresh files"),
+        Line::from("u      - Rescan selected folder"),
+        Line::from("t      - Remove unplayable files"),
+        Line::from("/      - Search (Esc to clear)"),
+        Line::from("d      - Pinned directories"),
+        Line::from("D      - Browse for a directory"),
+        Line::from("O      - Output device"),
+        Line::from("E      - Equalizer"),
+        Line::from("i      - Identify selected file"),
+        Line::from("f      - Folder view on/off"),
+        Line::from("Ctrl-p - Play the selected folder"),
+        Line::from("B      - Now playing banner"),
+        Line::from("Z      - Now playing view"),
+        Line::from("V      - Visualizer on/off"),
+        Line::from("C      - Cover art on/off"),
+        Line::from("P      - Stop after selected track"),
+        Line::from("Ctrl-e - Stop after current track"),
+        Line::from("*/F    - Favorite/favorites only"),
+        Line::from("H      - Recently played"),
+        Line::from("w      - Sleep timer 15/30/60 min/off"),
+        Line::from("L      - Library stats"),
+        Line::from("T      - Follow system theme"),
+        Line::from("[/]    - Refresh rate down/up"),
         Line::from("q      - Quit"),
     ];
 
@@ -235,26 +856,636 @@ fn draw_info_panel(f: &mut Frame, area: Rect, app: &App) {
             Block::default()
                 .borders(Borders::ALL)
                 .title("Help")
-                .border_style(Style::default().fg(Color::White)),
+                .border_style(Style::default().fg(app.theme.text)),
         );
-    f.render_widget(help, info_chunks[1]);
+    f.render_widget(help, info_chunks[4]);
+}
+
+/// The queue in play order. Entries no longer in the list are greyed out,
+/// and one that is also the playing track is highlighted.
+fn draw_queue(f: &mut Frame, area: Rect, app: &App, selected: usize) {
+    let items: Vec<ListItem> = if app.queue.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "Queue is empty - Esc, then 'a' to add the selected track",
+            Style::default().fg(app.theme.muted),
+        ))]
+    } else {
+        app.queue
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let file = app.music_files.iter().find(|f| &f.path == path);
+                let name = file.map_or_else(
+                    || path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                    MusicFile::display_name,
+                );
+                let style = if file.is_none() {
+                    Style::default().fg(app.theme.muted)
+                } else if Some(path) == app.current_playing.as_ref() {
+                    Style::default().fg(app.theme.playing).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(app.theme.text)
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{:>2} ", i + 1), Style::default().fg(app.theme.accent)),
+                    Span::styled(name, style),
+                ]))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Queue ({}) - Enter plays, d removes, Esc closes", app.queue.len()))
+                .border_style(Style::default().fg(app.theme.text)),
+        )
+        .highlight_style(highlight_style(app));
+    let mut state = ListState::default();
+    state.select((!app.queue.is_empty()).then_some(selected));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// Recent status messages, newest first, stamped with the time of day they
+/// appeared.
+fn draw_status_log(f: &mut Frame, area: Rect, app: &App, selected: usize) {
+    let now = chrono::Local::now();
+    let items: Vec<ListItem> = if app.status_log.is_empty() {
+        vec![ListItem::new(Span::styled("No messages yet", Style::default().fg(app.theme.muted)))]
+    } else {
+        app.status_log
+            .iter()
+            .rev()
+            .map(|(at, message)| {
+                let time = chrono::Duration::from_std(at.elapsed())
+                    .map_or(now, |elapsed| now - elapsed)
+                    .format("%H:%M:%S");
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{} ", time), Style::default().fg(app.theme.muted)),
+                    Span::styled(message.as_str(), Style::default().fg(app.theme.text)),
+                ]))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Messages ({}) - l or Esc closes", app.status_log.len()))
+                .border_style(Style::default().fg(app.theme.text)),
+        )
+        .highlight_style(highlight_style(app));
+    let mut state = ListState::default();
+    state.select((!app.status_log.is_empty()).then_some(selected));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// The play history, newest first. Tracks outside the current list are
+/// greyed out since they can't be played from here.
+fn draw_history(f: &mut Frame, area: Rect, app: &App, selected: usize) {
+    let items: Vec<ListItem> = if app.history.is_empty() {
+        vec![ListItem::new(Span::styled("Nothing played yet", Style::default().fg(app.theme.muted)))]
+    } else {
+        app.history
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let file = app.music_files.iter().find(|f| &f.path == path);
+                let name = file.map_or_else(
+                    || path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                    MusicFile::display_name,
+                );
+                let style = if file.is_none() {
+                    Style::default().fg(app.theme.muted)
+                } else if Some(path) == app.current_playing.as_ref() {
+                    Style::default().fg(app.theme.playing).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(app.theme.text)
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{:>2} ", i + 1), Style::default().fg(app.theme.accent)),
+                    Span::styled(name, style),
+                ]))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Recently Played ({}) - Enter plays, Esc closes", app.history.len()))
+                .border_style(Style::default().fg(app.theme.text)),
+        )
+        .highlight_style(highlight_style(app));
+    let mut state = ListState::default();
+    state.select((!app.history.is_empty()).then_some(selected));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// `[1:23 / 3:45 · 37%]` for the loaded track, or just the position when
+/// the length is unknown; `None` when stopped. Paused, it stays put along
+/// with the position.
+fn footer_progress(app: &App) -> Option<String> {
+    app.current_playing.as_ref()?;
+    let time_format = app.config.time_format;
+    let elapsed = app.audio_player.position();
+    Some(match app.current_duration {
+        Some(total) if !total.is_zero() => format!(
+            "[{} / {} · {}%]",
+            format_duration(elapsed, time_format),
+            format_duration(total, time_format),
+            (elapsed.as_secs_f64() / total.as_secs_f64() * 100.0).clamp(0.0, 100.0) as u32
+        ),
+        _ => format!("[{}]", format_duration(elapsed, time_format)),
+    })
+}
+
+/// Elapsed/total gauge for the current track; empty with `--:--` when the
+/// length is unknown.
+fn draw_progress(f: &mut Frame, area: Rect, app: &App) {
+    let time_format = app.config.time_format;
+    let (ratio, label) = if app.current_playing.is_some() {
+        let elapsed = app.seek_drag.unwrap_or_else(|| app.audio_player.position());
+        match app.current_duration {
+            Some(total) if !total.is_zero() => (
+                (elapsed.as_secs_f64() / total.as_secs_f64()).clamp(0.0, 1.0),
+                format!("{} / {}", format_duration(elapsed, time_format), format_duration(total, time_format)),
+            ),
+            _ => (0.0, format!("{} / --:--", format_duration(elapsed, time_format))),
+        }
+    } else {
+        (0.0, String::from("--:-- / --:--"))
+    };
+
+    let title = match (app.loop_a, app.loop_b) {
+        (None, None) => String::from("Progress"),
+        (a, b) => {
+            let mark = |mark: Option<Duration>| mark.map_or_else(|| String::from("?"), |mark| format_duration(mark, time_format));
+            format!("Progress - Loop A {} ⟲ B {}", mark(a), mark(b))
+        }
+    };
+    let gauge = Gauge::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(app.theme.text)),
+        )
+        .gauge_style(Style::default().fg(app.theme.playing).bg(app.theme.highlight_bg))
+        .ratio(ratio)
+        .label(label);
+    f.render_widget(gauge, area);
+    app.hit_areas.set(HitAreas { progress: area, ..app.hit_areas.get() });
+}
+
+/// Rows for the cover, borders included; 10 rows inside fit 20 pixels.
+const ALBUM_ART_HEIGHT: u16 = 12;
+
+fn draw_album_art(f: &mut Frame, area: Rect, app: &App) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Cover")
+        .border_style(Style::default().fg(app.theme.text));
+    let inner = block.inner(area);
+    let lines = app
+        .album_art
+        .as_ref()
+        .map(|art| art.lines(inner.width, inner.height))
+        .unwrap_or_default();
+    let cover = if lines.is_empty() {
+        let text = if app.album_art.is_some() { "No cover art" } else { "Loading cover…" };
+        Paragraph::new(Span::styled(text, Style::default().fg(app.theme.muted))).alignment(Alignment::Center)
+    } else {
+        Paragraph::new(lines)
+    };
+    f.render_widget(cover.block(block), area);
+}
+
+/// Spectrum bars, one per column, from the samples the player is tapping.
+fn draw_visualizer(f: &mut Frame, area: Rect, app: &App) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Visualizer")
+        .border_style(Style::default().fg(app.theme.text));
+    let bands = block.inner(area).width as usize;
+    let levels = if app.is_paused {
+        vec![0.0; bands]
+    } else {
+        visualizer::spectrum(&app.audio_player.tap().snapshot(), bands)
+    };
+    let data: Vec<u64> = levels.iter().map(|level| (level * 100.0) as u64).collect();
+
+    let sparkline = Sparkline::default()
+        .block(block)
+        .data(&data)
+        .max(100)
+        .style(Style::default().fg(app.theme.accent));
+    f.render_widget(sparkline, area);
 }
 
 fn draw_footer(f: &mut Frame, area: Rect, app: &App) {
     let status_style = if app.current_playing.is_some() {
-        Style::default().fg(Color::Green)
+        Style::default().fg(app.theme.playing)
+    } else {
+        Style::default().fg(app.theme.attention)
+    };
+
+    let line = if app.mode == Mode::Search {
+        Line::from(vec![
+            Span::styled("Search: ", Style::default().fg(app.theme.accent)),
+            Span::raw(&app.search_query),
+            Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+        ])
+    } else if app.mode == Mode::VolumeEntry {
+        Line::from(vec![
+            Span::styled("Volume: ", Style::default().fg(app.theme.accent)),
+            Span::raw(&app.volume_input),
+            Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+            Span::styled("% (Enter sets, Esc cancels)", Style::default().fg(app.theme.muted)),
+        ])
+    } else if app.mode == Mode::SeekEntry {
+        Line::from(vec![
+            Span::styled("Go to: ", Style::default().fg(app.theme.accent)),
+            Span::raw(&app.seek_input),
+            Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+            Span::styled(" (m:ss or seconds, +/- for relative; Enter seeks, Esc cancels)", Style::default().fg(app.theme.muted)),
+        ])
+    } else if app.mode == Mode::ConfirmTrash {
+        let name = app.music_files.get(app.selected_index).map_or("", |file| file.name.as_str());
+        Line::from(vec![
+            Span::styled(
+                format!("Move {} to the trash?", name),
+                Style::default().fg(app.theme.attention).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" (y/n)", Style::default().fg(app.theme.muted)),
+        ])
+    } else if app.mode == Mode::ConfirmPlaylist {
+        let count = app.pending_playlist.as_ref().map_or(0, |(_, files)| files.len());
+        Line::from(vec![
+            Span::styled(
+                format!("Replace the list with the playlist's {} tracks?", count),
+                Style::default().fg(app.theme.attention).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" (y/n, a appends)", Style::default().fg(app.theme.muted)),
+        ])
+    } else if app.mode == Mode::ConfirmQuit {
+        Line::from(Span::styled(
+            "Really quit? (y/n)",
+            Style::default().fg(app.theme.attention).add_modifier(Modifier::BOLD),
+        ))
     } else {
-        Style::default().fg(Color::Yellow)
+        let mut spans = vec![
+            Span::styled("Status: ", Style::default().fg(app.theme.accent)),
+            Span::styled(&app.status_message, status_style),
+        ];
+        // Only when it fits after the message, so narrow terminals keep the message
+        if let Some(progress) = footer_progress(app) {
+            let used = "Status: ".len() + app.status_message.chars().count() + 1 + progress.chars().count();
+            if used <= area.width.saturating_sub(2) as usize {
+                spans.push(Span::styled(format!(" {}", progress), Style::default().fg(app.theme.muted)));
+            }
+        }
+        Line::from(spans)
     };
 
-    let footer = Paragraph::new(Line::from(vec![
-        Span::styled("Status: ", Style::default().fg(Color::Cyan)),
-        Span::styled(&app.status_message, status_style),
-    ]))
+    let footer = Paragraph::new(line)
     .block(
         Block::default()
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::White)),
+            .border_style(Style::default().fg(app.theme.text)),
     );
     f.render_widget(footer, area);
 }
+
+fn draw_debug_overlay(f: &mut Frame, bounds: Rect, app: &App) {
+    let width = 28.min(bounds.width);
+    let height = 5.min(bounds.height);
+    let area = Rect::new(bounds.x + bounds.width - width, bounds.y, width, height);
+
+    let label = Style::default().fg(app.theme.accent);
+    let debug = Paragraph::new(vec![
+        Line::from(vec![
+            Span::styled("Tick: ", label),
+            Span::raw(format!(
+                "{}ms ({} fps)",
+                app.tick_rate.as_millis(),
+                app.tick_rate_fps()
+            )),
+        ]),
+        Line::from(vec![
+            Span::styled("Draw: ", label),
+            Span::raw(format!("{:.2}ms", app.frame_stats.draw_time.as_secs_f64() * 1000.0)),
+        ]),
+        Line::from(vec![
+            Span::styled("FPS:  ", label),
+            Span::raw(format!("{:.1}", app.frame_stats.fps)),
+        ]),
+    ])
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(if app.stress_mode { "Debug (stress)" } else { "Debug" })
+            .border_style(Style::default().fg(app.theme.marker)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(debug, area);
+}
+
+fn draw_pinned_dirs(f: &mut Frame, bounds: Rect, app: &App, selected: usize) {
+    let items: Vec<ListItem> = app
+        .config
+        .pinned_dirs
+        .iter()
+        .enumerate()
+        .map(|(i, dir)| {
+            let style = if app.music_roots.contains(dir) {
+                Style::default().fg(app.theme.playing)
+            } else {
+                Style::default().fg(app.theme.text)
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", i + 1), Style::default().fg(app.theme.accent)),
+                Span::styled(dir.display().to_string(), style),
+            ]))
+        })
+        .collect();
+
+    let area = centered_rect(60, items.len() as u16 + 2, bounds);
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Pinned Directories (Enter/1-9 to open, Esc to close)")
+                .border_style(Style::default().fg(app.theme.accent)),
+        )
+        .highlight_style(highlight_style(app));
+
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_devices(f: &mut Frame, bounds: Rect, app: &App, selected: usize) {
+    let current = app.audio_player.device_name();
+    let items: Vec<ListItem> = app
+        .output_devices
+        .iter()
+        .map(|name| {
+            if Some(name.as_str()) == current {
+                ListItem::new(Span::styled(format!("{} (in use)", name), Style::default().fg(app.theme.playing)))
+            } else {
+                ListItem::new(Span::styled(name.clone(), Style::default().fg(app.theme.text)))
+            }
+        })
+        .collect();
+
+    let area = centered_rect(60, (items.len() as u16 + 2).min(20), bounds);
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Output Device (Enter to switch, Esc to close)")
+                .border_style(Style::default().fg(app.theme.accent)),
+        )
+        .highlight_style(highlight_style(app));
+
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// dB covered by each row of the equalizer bars.
+const EQ_ROW_DB: i32 = 2;
+/// Width of a band's column in the equalizer panel.
+const EQ_COLUMN: usize = 5;
+
+/// One vertical bar per band, growing up or down from the 0 dB line.
+fn draw_equalizer(f: &mut Frame, bounds: Rect, app: &App, selected: usize) {
+    let gains = app.audio_player.eq_gains();
+    let top = eq::MAX_GAIN_DB as i32;
+    let column_style = |band: usize| {
+        if band == selected {
+            Style::default().fg(app.theme.playing).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(app.theme.text)
+        }
+    };
+
+    let mut lines = Vec::new();
+    for level in (-top..=top).rev().step_by(EQ_ROW_DB as usize) {
+        let axis = if level % 6 == 0 { format!("{:>+3} ", level) } else { String::from("    ") };
+        let mut spans = vec![Span::styled(axis, Style::default().fg(app.theme.muted))];
+        for (band, &gain) in gains.iter().enumerate() {
+            // A row is lit once the gain reaches into it
+            let lit = (level > 0 && gain > (level - EQ_ROW_DB) as f32)
+                || (level < 0 && gain < (level + EQ_ROW_DB) as f32);
+            let cell = if level == 0 {
+                "─".repeat(EQ_COLUMN)
+            } else if lit {
+                format!(" {} ", "█".repeat(EQ_COLUMN - 2))
+            } else {
+                " ".repeat(EQ_COLUMN)
+            };
+            spans.push(Span::styled(cell, column_style(band)));
+        }
+        lines.push(Line::from(spans));
+    }
+    for row in [
+        eq::BAND_LABELS.map(String::from),
+        gains.map(|gain| format!("{:+}", gain)),
+    ] {
+        let mut spans = vec![Span::raw("    ")];
+        spans.extend(
+            row.into_iter()
+                .enumerate()
+                .map(|(band, text)| Span::styled(format!("{:^width$}", text, width = EQ_COLUMN), column_style(band))),
+        );
+        lines.push(Line::from(spans));
+    }
+
+    let width = (4 + EQ_COLUMN * eq::BANDS.len() + 2) as u16;
+    let area = centered_rect(width, lines.len() as u16 + 2, bounds);
+    let panel = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("EQ: {} (←/→ band, ↑/↓ gain, p preset, 0 flat)", app.eq_preset_label()))
+            .border_style(Style::default().fg(app.theme.accent)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(panel, area);
+}
+
+fn draw_dir_picker(f: &mut Frame, bounds: Rect, app: &App, picker: &DirPicker, selected: usize) {
+    let items: Vec<ListItem> = if picker.entries.is_empty() {
+        vec![ListItem::new(Span::styled("(no subdirectories)", Style::default().fg(app.theme.muted)))]
+    } else {
+        picker
+            .entries
+            .iter()
+            .map(|dir| {
+                let name = dir.file_name().map_or_else(
+                    || dir.display().to_string(),
+                    |name| name.to_string_lossy().to_string(),
+                );
+                ListItem::new(format!("{}/", name))
+            })
+            .collect()
+    };
+
+    let area = centered_rect(70, (items.len() as u16 + 2).min(20), bounds);
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(picker.dir.display().to_string())
+                .title(
+                    Title::from(" Enter choose  →/l open  ←/h up  Space this folder  Esc close ")
+                        .position(Position::Bottom),
+                )
+                .border_style(Style::default().fg(app.theme.accent)),
+        )
+        .highlight_style(highlight_style(app));
+
+    let mut state = ListState::default();
+    if !picker.entries.is_empty() {
+        state.select(Some(selected));
+    }
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_identity(f: &mut Frame, bounds: Rect, identity: &FileIdentity, theme: &Theme) {
+    let label = Style::default().fg(theme.accent);
+    let lines = vec![
+        Line::from(vec![
+            Span::styled("Path:     ", label),
+            Span::raw(identity.path.display().to_string()),
+        ]),
+        Line::from(vec![
+            Span::styled("Size:     ", label),
+            Span::raw(format!("{} bytes", identity.size)),
+        ]),
+        Line::from(vec![
+            Span::styled("Modified: ", label),
+            Span::raw(identity.modified.as_deref().unwrap_or("unknown")),
+        ]),
+        Line::from(vec![
+            Span::styled("Hash:     ", label),
+            Span::raw(&identity.hash),
+        ]),
+        Line::from(vec![
+            Span::styled("Format:   ", label),
+            Span::raw(&identity.format),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled("Press any key to close", Style::default().fg(theme.muted))),
+    ];
+
+    let area = centered_rect(90, lines.len() as u16 + 2, bounds);
+    let card = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Identify File")
+                .border_style(Style::default().fg(theme.accent)),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(card, area);
+}
+
+fn draw_library_stats(f: &mut Frame, bounds: Rect, stats: &LibraryStats, app: &App) {
+    let time_format = app.config.time_format;
+    let area = centered_rect(100, 28, bounds);
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Library Stats (any key to close)")
+        .border_style(Style::default().fg(app.theme.accent));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4), // Overview
+            Constraint::Length(9), // Formats, largest, longest
+            Constraint::Min(0),    // Artists, albums
+        ])
+        .split(inner);
+
+    let label = Style::default().fg(app.theme.accent);
+    let mut duration = format_duration(stats.total_duration, time_format);
+    if stats.unknown_duration > 0 {
+        duration.push_str(&format!(" (+{} of unknown length)", stats.unknown_duration));
+    }
+    let overview = Paragraph::new(vec![
+        Line::from(vec![Span::styled("Tracks:   ", label), Span::raw(stats.tracks.to_string())]),
+        Line::from(vec![Span::styled("Size:     ", label), Span::raw(format_size(stats.total_size))]),
+        Line::from(vec![Span::styled("Duration: ", label), Span::raw(duration)]),
+    ]);
+    f.render_widget(overview, rows[0]);
+
+    let panels = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(20), // Formats
+            Constraint::Percentage(40), // Largest
+            Constraint::Percentage(40), // Longest
+        ])
+        .split(rows[1]);
+
+    let formats: Vec<ListItem> = stats
+        .by_extension
+        .iter()
+        .map(|(ext, count)| ListItem::new(format!("{:<6} {}", ext, count)))
+        .collect();
+    let largest: Vec<ListItem> = stats
+        .largest
+        .iter()
+        .map(|(name, size)| ListItem::new(format!("{:>10}  {}", format_size(*size), name)))
+        .collect();
+    let longest: Vec<ListItem> = stats
+        .longest
+        .iter()
+        .map(|(name, length)| {
+            ListItem::new(format!("{:>8}  {}", format_duration(*length, time_format), name))
+        })
+        .collect();
+
+    let groups = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[2]);
+    // As many of the largest groups as fit
+    let group_items = |groups: &[Group]| -> Vec<ListItem> {
+        groups
+            .iter()
+            .map(|group| {
+                let length = format_duration(group.duration, time_format);
+                ListItem::new(format!("{:>5} {:>8}  {}", group.tracks, length, group.name))
+            })
+            .collect()
+    };
+
+    for (items, title, panel) in [
+        (formats, "Formats", panels[0]),
+        (largest, "Largest", panels[1]),
+        (longest, "Longest", panels[2]),
+        (group_items(&stats.by_artist), "Artists (tracks, length)", groups[0]),
+        (group_items(&stats.by_album), "Albums (tracks, length)", groups[1]),
+    ] {
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(app.theme.text)),
+        );
+        f.render_widget(list, panel);
+    }
+}
@@ -0,0 +1,51 @@
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::f32::consts::PI;
+use std::sync::{Arc, OnceLock};
+
+/// Samples per FFT window; at 44.1 kHz this is about 46 ms of audio.
+const FFT_SIZE: usize = 2048;
+/// Quietest level shown, in dB below a full-scale sine.
+const FLOOR_DB: f32 = 60.0;
+
+fn fft() -> &'static Arc<dyn Fft<f32>> {
+    static FFT: OnceLock<Arc<dyn Fft<f32>>> = OnceLock::new();
+    FFT.get_or_init(|| FftPlanner::new().plan_fft_forward(FFT_SIZE))
+}
+
+/// Levels of `bands` log-spaced frequency bands over the latest window of
+/// `samples`, each from 0.0 (at or below the floor) to 1.0. All zero until
+/// a full window has been collected.
+pub fn spectrum(samples: &[f32], bands: usize) -> Vec<f32> {
+    if bands == 0 || samples.len() < FFT_SIZE {
+        return vec![0.0; bands];
+    }
+
+    let window = &samples[samples.len() - FFT_SIZE..];
+    let mut buffer: Vec<Complex<f32>> = window
+        .iter()
+        .enumerate()
+        .map(|(i, &sample)| {
+            // Hann window to keep leakage from smearing the bars together
+            let weight = 0.5 - 0.5 * (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos();
+            Complex::new(sample * weight, 0.0)
+        })
+        .collect();
+    fft().process(&mut buffer);
+
+    // Skip the DC bin; a full-scale sine peaks at about FFT_SIZE / 4 after windowing
+    let bins = FFT_SIZE / 2;
+    let full_scale = FFT_SIZE as f32 / 4.0;
+    (0..bands)
+        .map(|band| {
+            let lo = (bins as f32).powf(band as f32 / bands as f32) as usize;
+            let hi = ((bins as f32).powf((band + 1) as f32 / bands as f32) as usize).max(lo + 1);
+            let peak = buffer[lo.max(1)..hi.min(bins)]
+                .iter()
+                .map(|bin| bin.norm())
+                .fold(0.0, f32::max);
+            let db = 20.0 * (peak / full_scale).max(f32::MIN_POSITIVE).log10();
+            ((db + FLOOR_DB) / FLOOR_DB).clamp(0.0, 1.0)
+        })
+        .collect()
+}
@@ -0,0 +1,72 @@
+//! Watches the music directories so the library can be rescanned when files
+//! are added, removed or renamed.
+
+use anyhow::Result;
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// Changes closer together than this are reported once, so copying an album
+/// in triggers a single rescan.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// A recursive watch on the music directories. Watching stops when this is dropped.
+pub struct LibraryWatcher {
+    _watcher: RecommendedWatcher,
+    changes: Receiver<()>,
+}
+
+impl LibraryWatcher {
+    /// Whether anything changed since the last call.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while self.changes.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
+}
+
+/// Starts watching each of `dirs` and everything below them. Events are
+/// coalesced on a background thread, which exits once the watcher is dropped.
+pub fn spawn(dirs: &[PathBuf]) -> Result<LibraryWatcher> {
+    let (event_tx, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+        Ok(event) if affects_listing(&event.kind) => {
+            let _ = event_tx.send(());
+        }
+        Ok(_) => {}
+        Err(e) => log::debug!("File watcher error: {}", e),
+    })?;
+    for dir in dirs {
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+        log::info!("Watching {} for changes", dir.display());
+    }
+
+    let (tx, changes) = mpsc::channel();
+    thread::spawn(move || {
+        while events.recv().is_ok() {
+            // Wait for a quiet spell before reporting the burst
+            loop {
+                match events.recv_timeout(DEBOUNCE) {
+                    Ok(()) => continue,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            if tx.send(()).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(LibraryWatcher { _watcher: watcher, changes })
+}
+
+/// Only files appearing, disappearing or being renamed change the list;
+/// plain writes (e.g. tag edits) don't.
+fn affects_listing(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_)))
+}